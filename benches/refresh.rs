@@ -0,0 +1,35 @@
+//! Per-turn cost of refreshing and rendering the game world on large maps
+//!
+//! Run with `cargo bench --bench refresh`.
+//!
+//! Limiting `update_map` to the field of view took `refresh` on the 200x200
+//! map from ~346us to ~73us, rendering stayed at ~250-310us.
+use criterion::{criterion_group, criterion_main, Criterion};
+
+use rustlike::dungeon::Layout;
+use rustlike::game::{Difficulty, Game};
+use rustlike::{Dimension, Offscreen};
+
+/// Width/height of the benchmark map
+const MAP_SIZE: i32 = 200;
+
+fn game() -> Game {
+    let layout = Layout::new(Dimension(MAP_SIZE, MAP_SIZE), Dimension(6, 10), 400, 3, 2);
+    Game::new("Bench", layout, 1, Difficulty::Normal)
+}
+
+fn refresh(c: &mut Criterion) {
+    let mut game = game();
+    c.bench_function("refresh", |b| b.iter(|| game.refresh()));
+}
+
+fn render_game_world(c: &mut Criterion) {
+    let game = game();
+    let mut con = Offscreen::new(134, 65);
+    c.bench_function("render_game_world", |b| {
+        b.iter(|| game.render_game_world(&mut con))
+    });
+}
+
+criterion_group!(benches, refresh, render_game_world);
+criterion_main!(benches);
@@ -1,9 +1,11 @@
 //! Map geometry
-#[derive(Debug, PartialEq, Clone, Copy, Default)]
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy, Default, Serialize, Deserialize)]
 pub struct Location(pub i32, pub i32);
-#[derive(Debug, PartialEq, Clone, Copy, Default)]
+#[derive(Debug, PartialEq, Clone, Copy, Default, Serialize, Deserialize)]
 pub struct Direction(pub i32, pub i32);
-#[derive(Debug, PartialEq, Clone, Copy, Default)]
+#[derive(Debug, PartialEq, Clone, Copy, Default, Serialize, Deserialize)]
 pub struct Dimension(pub i32, pub i32);
 
 pub fn translate(
@@ -34,3 +36,16 @@ pub fn translate(
         None
     }
 }
+
+/// The location on the map shown at a location of the view, the reverse of
+/// `translate`
+pub fn translate_back(target: &Dimension, view_loc: &Location, focus: &Location) -> Location {
+    let Dimension(width, height) = target;
+    let Location(x_focus, y_focus) = focus;
+    let Location(view_x, view_y) = view_loc;
+
+    let center_x = width / 2 + 1;
+    let center_y = height / 2 + 1;
+
+    Location(x_focus + view_x - center_x, y_focus + view_y - center_y)
+}
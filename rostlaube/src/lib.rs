@@ -3,24 +3,38 @@ use tcod::console::{Console, Offscreen, Root};
 use tcod::console::{FontLayout, FontType};
 
 // Re-export libtcod modules
+pub use tcod::bsp;
 pub use tcod::colors;
 pub use tcod::console;
 pub use tcod::input;
 pub use tcod::map;
+pub use tcod::pathfinding;
 pub use tcod::system;
 
+/// Print a trace of the engine loop if logging is enabled
+macro_rules! log {
+    ($engine:expr, $($arg:tt)*) => {
+        if $engine.log {
+            println!("ENGINE: {}", format_args!($($arg)*));
+        }
+    };
+}
+
 // Internal
 pub mod geometry;
 pub mod rng;
 pub mod ui;
 pub mod pda;
 pub mod command_line;
+pub mod prompt;
 
 use geometry::Location;
 use command_line::CommandLine;
 
 pub struct Engine {
     running: bool,
+    exit_requested: bool,
+    log: bool,
     root: Root,
 }
 
@@ -36,19 +50,130 @@ pub trait State: std::marker::Sized + std::fmt::Debug {
     ) -> Self::Action;
 
     fn update(&mut self, action: Self::Action, world: &mut Self::World) -> Transition<Self>;
+
+    /// Draw the next frame of an animation on top of the rendered scene
+    ///
+    /// The engine keeps rendering frames before it waits for the next key
+    /// press for as long as this returns `true`.
+    fn animate(&mut self, _con: &mut Offscreen, _world: &mut Self::World) -> bool {
+        false
+    }
+
+    /// Called when the user asks to quit the program, e.g. with CTRL-C.
+    ///
+    /// Returning `Transition::Exit` or `Transition::Quit` lets the engine
+    /// quit. Any other transition intercepts the request and is applied to
+    /// the scene stack instead, e.g. to push a confirmation prompt.
+    fn on_exit_requested(&mut self, _world: &mut Self::World) -> Transition<Self> {
+        Transition::Exit
+    }
 }
 
 #[derive(Debug)]
 pub enum Transition<S: State> {
+    /// Leave the current scene
     Exit,
+    /// Leave all scenes and shut down the engine
+    Quit,
     Continue,
     Next(S),
     Replace(S),
+    /// Push a scene of another type that works on the same world
+    NextWith(Box<dyn Scene<S::World>>),
+    /// Replace the current scene with one of another type
+    ReplaceWith(Box<dyn Scene<S::World>>),
+}
+
+/// A scene on the engine's stack, whatever its type
+///
+/// Every `State` is a scene. The engine only sees this trait, so the stack
+/// can hold scenes of different types as long as they share the world.
+pub trait Scene<W>: std::fmt::Debug {
+    fn render(&self, con: &mut Offscreen, world: &W);
+
+    /// The action an event would be interpreted as, for the engine's trace
+    fn describe(&self, event: &Event) -> String;
+
+    /// Interpret an event and update the world with the result
+    fn handle(&mut self, event: &Event, world: &mut W) -> Step<W>;
+
+    fn animate(&mut self, con: &mut Offscreen, world: &mut W) -> bool;
+
+    fn on_exit_requested(&mut self, world: &mut W) -> Step<W>;
+}
+
+/// A `Transition` with the type of the scenes erased
+pub enum Step<W> {
+    Exit,
+    Quit,
+    Continue,
+    Next(Box<dyn Scene<W>>),
+    Replace(Box<dyn Scene<W>>),
+}
+
+impl<W> std::fmt::Debug for Step<W> {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Step::Exit => write!(f, "Exit"),
+            Step::Quit => write!(f, "Quit"),
+            Step::Continue => write!(f, "Continue"),
+            Step::Next(s) => f.debug_tuple("Next").field(s).finish(),
+            Step::Replace(s) => f.debug_tuple("Replace").field(s).finish(),
+        }
+    }
+}
+
+impl<S> From<Transition<S>> for Step<S::World>
+where
+    S: State + 'static,
+    S::Action: std::fmt::Debug,
+{
+    fn from(transition: Transition<S>) -> Self {
+        match transition {
+            Transition::Exit => Step::Exit,
+            Transition::Quit => Step::Quit,
+            Transition::Continue => Step::Continue,
+            Transition::Next(s) => Step::Next(Box::new(s)),
+            Transition::Replace(s) => Step::Replace(Box::new(s)),
+            Transition::NextWith(s) => Step::Next(s),
+            Transition::ReplaceWith(s) => Step::Replace(s),
+        }
+    }
+}
+
+impl<S> Scene<S::World> for S
+where
+    S: State + 'static,
+    S::Action: std::fmt::Debug,
+{
+    fn render(&self, con: &mut Offscreen, world: &S::World) {
+        State::render(self, con, world)
+    }
+
+    fn describe(&self, event: &Event) -> String {
+        format!("{:?}", self.interpret(event))
+    }
+
+    fn handle(&mut self, event: &Event, world: &mut S::World) -> Step<S::World> {
+        let action = self.interpret(event);
+        self.update(action, world).into()
+    }
+
+    fn animate(&mut self, con: &mut Offscreen, world: &mut S::World) -> bool {
+        State::animate(self, con, world)
+    }
+
+    fn on_exit_requested(&mut self, world: &mut S::World) -> Step<S::World> {
+        State::on_exit_requested(self, world).into()
+    }
 }
 
 #[derive(Debug)]
 pub enum Event {
     KeyEvent(input::Key),
+    /// The mouse moved to another cell, a button was pressed or the wheel
+    /// turned
+    Mouse(input::Mouse),
     Command(String),
     Nothing,
 }
@@ -56,65 +181,82 @@ pub enum Event {
 impl Engine {
     pub fn new(screen_width: i32, screen_height: i32, limit_fps: i32) -> Self {
         system::set_fps(limit_fps);
-        let mut root = Root::initializer()
-            .font("src/consolas12x12.png", FontLayout::Tcod)
-            .font_type(FontType::Greyscale)
-            .size(screen_width, screen_height)
-            .title("Rusty Roguelike")
-            .init();
-        root.set_fullscreen(false);
+        let root = init_root(screen_width, screen_height);
 
         Engine {
             running: true,
-            root: root,
+            exit_requested: false,
+            log: false,
+            root,
         }
     }
 
+    /// Trace scenes, events, actions and transitions to stdout
+    pub fn set_log(&mut self, log: bool) {
+        self.log = log;
+    }
+
     pub fn run<S, W, A>(&mut self, mut world: W, start: S) -> W
     where
         A: std::fmt::Debug,
         S: std::fmt::Debug,
-        S: State<World = W, Action = A>,
+        S: State<World = W, Action = A> + 'static,
     {
-        let mut scenes = vec![start];
+        let mut scenes: Vec<Box<dyn Scene<W>>> = vec![Box::new(start)];
         while self.running() {
-            println!("ENGINE: scenes: {:?}", scenes);
+            log!(self, "scenes: {:?}", scenes);
 
-            scenes
+            if let Some((scene, transition)) = scenes
                 .pop()
-                .map(|scene| {
-                    println!("ENGINE: scene = {:?}", scene);
-                    self.render(&scene, &world);
+                .map(|mut scene| {
+                    log!(self, "scene = {:?}", scene);
+                    self.render(scene.as_mut(), &mut world);
                     scene
                 })
-                .and_then(|scene| {
+                .map(|scene| {
                     let event = self.next_event();
-                    println!("ENGINE: event = {:?}", event);
-                    event.map(|e| (scene, e))
+                    log!(self, "event = {:?}", event);
+                    (scene, event)
                 })
-                .map(|(scene, event)| {
-                    let action = scene.interpret(&event);
-                    println!("ENGINE: action = {:?}", action);
-                    (scene, action)
+                .map(|(mut scene, event)| match event {
+                    Some(event) => {
+                        log!(self, "action = {}", scene.describe(&event));
+                        let step = scene.handle(&event, &mut world);
+                        (scene, step)
+                    }
+                    None if self.exit_requested => {
+                        self.exit_requested = false;
+                        let step = scene.on_exit_requested(&mut world);
+                        match step {
+                            Step::Exit => (scene, Step::Quit),
+                            step => (scene, step),
+                        }
+                    }
+                    None => (scene, Step::Continue),
                 })
-                .map(|(mut scene, action)| {
-                    let transition = scene.update(action, &mut world);
-                    println!("ENGINE: transition = {:?}", transition);
+                .map(|(scene, transition)| {
+                    log!(self, "transition = {:?}", transition);
                     (scene, transition)
                 })
-                .map(|(scene, transition)| match transition {
-                    Transition::Continue => {
+            {
+                match transition {
+                    Step::Continue => {
                         scenes.push(scene);
                     }
-                    Transition::Exit => {},
-                    Transition::Next(s) => {
+                    Step::Exit => {},
+                    Step::Quit => {
+                        scenes.clear();
+                        self.exit();
+                    },
+                    Step::Next(s) => {
                         scenes.push(scene);
                         scenes.push(s);
                     },
-                    Transition::Replace(s) => {
+                    Step::Replace(s) => {
                         scenes.push(s);
                     },
-                });
+                }
+            }
 
             if scenes.is_empty() {
                 break;
@@ -128,11 +270,38 @@ impl Engine {
     pub fn run_if<S, W, A>(&mut self, world: Option<W>, start: S) -> Option<W>
     where
         A: std::fmt::Debug,
-        S: State<World = W, Action = A> + std::fmt::Debug,
+        S: State<World = W, Action = A> + std::fmt::Debug + 'static,
     {
         world.map(|s| self.run(s, start))
     }
 
+    /// Ask for a line of text, `None` if the prompt was cancelled
+    pub fn prompt(
+        &mut self,
+        text: &str,
+        max_len: usize,
+        validate: prompt::Validator,
+    ) -> Option<String> {
+        self.run(None, prompt::Prompt::new(text, max_len, validate))
+    }
+
+    /// Make the screen the given number of cells wide and high
+    ///
+    /// Scenes render onto a console the size of the screen, so they are
+    /// laid out for the new size from the next frame on.
+    pub fn resize(&mut self, width: i32, height: i32) {
+        if (width, height) != (self.root.width(), self.root.height()) {
+            let fullscreen = self.root.is_fullscreen();
+            self.root = init_root(width, height);
+            self.root.set_fullscreen(fullscreen);
+        }
+    }
+
+    /// Run scenes again after the last ones quit
+    pub fn resume(&mut self) {
+        self.running = true;
+    }
+
     pub fn exit(&mut self) {
         // Toggle off fullscreen to avoid messing up the resolution
         self.root.set_fullscreen(false);
@@ -141,27 +310,32 @@ impl Engine {
 }
 
 impl Engine {
-    fn render<S, W>(&mut self, layer: &S, world: &W)
-    where
-        S: State<World = W>,
-    {
+    /// Render the scene, playing its animations before the final frame
+    fn render<W>(&mut self, layer: &mut dyn Scene<W>, world: &mut W) {
         self.root.set_default_background(colors::BLACK);
 
-        let mut con = Offscreen::new(self.root.width(), self.root.height());
+        loop {
+            let mut con = Offscreen::new(self.root.width(), self.root.height());
+
+            layer.render(&mut con, world);
+            let animating = layer.animate(&mut con, world);
 
-        layer.render(&mut con, world);
+            console::blit(
+                &con,
+                (0, 0),
+                (con.width(), con.height()),
+                &mut self.root,
+                (0, 0),
+                1.0,
+                1.0,
+            );
 
-        console::blit(
-            &con,
-            (0, 0),
-            (con.width(), con.height()),
-            &mut self.root,
-            (0, 0),
-            1.0,
-            1.0,
-        );
+            self.root.flush();
 
-        self.root.flush();
+            if !animating || !self.running() {
+                break;
+            }
+        }
     }
 
     fn running(&self) -> bool {
@@ -177,7 +351,26 @@ impl Engine {
         use input::{Key, KeyCode};
         use Event::*;
 
-        let key = self.root.wait_for_keypress(true);
+        let key = loop {
+            match input::check_for_event(input::MOUSE | input::KEY_PRESS) {
+                Some((_, input::Event::Key(key))) => break key,
+                Some((_, input::Event::Mouse(mouse)))
+                    if mouse.dcx != 0
+                        || mouse.dcy != 0
+                        || mouse.lbutton_pressed
+                        || mouse.rbutton_pressed
+                        || mouse.wheel_up
+                        || mouse.wheel_down =>
+                {
+                    return Some(Mouse(mouse));
+                }
+                // The pointer moved within the same cell
+                Some(_) => {}
+                None if self.root.window_closed() => return None,
+                // Keep the frame rate while waiting
+                None => self.root.flush(),
+            }
+        };
 
         match key {
             Key {
@@ -185,7 +378,7 @@ impl Engine {
                 alt: true,
                 ..
             } => {
-                println!("ENGINE: Toggle Fullscreen");
+                log!(self, "Toggle Fullscreen");
                 self.toggle_fullscreen();
                 None
             }
@@ -195,8 +388,8 @@ impl Engine {
                 printable: 'c',
                 ..
             } => {
-                println!("ENGINE: CTRL-C received -> Exit!");
-                self.exit();
+                log!(self, "CTRL-C received -> Exit requested!");
+                self.exit_requested = true;
                 None
             }
             Key {
@@ -206,18 +399,112 @@ impl Engine {
                 ..
             } => {
                 let command_string = self.run(String::new(), CommandLine {});
-                println!("ENGINE: $ {:?}", command_string);
-                Some(Command(command_string))
+                log!(self, "$ {:?}", command_string);
+                match resize_command(&command_string) {
+                    Some((width, height)) => {
+                        log!(self, "Resize to {}x{}", width, height);
+                        self.resize(width, height);
+                        None
+                    }
+                    None => Some(Command(command_string)),
+                }
             }
             _ => Some(KeyEvent(key)),
         }
     }
 }
 
+/// Open the window with a root console of the given size
+fn init_root(width: i32, height: i32) -> Root {
+    let mut root = Root::initializer()
+        .font("src/consolas12x12.png", FontLayout::Tcod)
+        .font_type(FontType::Greyscale)
+        .size(width, height)
+        .title("Rusty Roguelike")
+        .init();
+    root.set_fullscreen(false);
+    root
+}
+
+/// The size asked for by a `resize <width> <height>` command
+///
+/// The engine handles these itself, the scenes never see them.
+fn resize_command(command: &str) -> Option<(i32, i32)> {
+    let mut args = command.split_whitespace();
+    match (args.next(), args.next(), args.next(), args.next()) {
+        (Some("resize"), Some(width), Some(height), None) => {
+            match (width.parse(), height.parse()) {
+                (Ok(width), Ok(height)) if width > 0 && height > 0 => Some((width, height)),
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
 #[cfg(test)]
 mod tests {
+    use super::*;
+
+    #[derive(Debug)]
+    struct Menu;
+
+    #[derive(Debug)]
+    struct Popup;
+
+    impl State for Menu {
+        type World = Vec<&'static str>;
+        type Action = ();
+
+        fn render(&self, _con: &mut Offscreen, _world: &Self::World) {}
+
+        fn interpret(&self, _event: &Event) -> Self::Action {}
+
+        fn update(&mut self, _action: (), world: &mut Self::World) -> Transition<Self> {
+            world.push("menu");
+            Transition::NextWith(Box::new(Popup))
+        }
+    }
+
+    impl State for Popup {
+        type World = Vec<&'static str>;
+        type Action = ();
+
+        fn render(&self, _con: &mut Offscreen, _world: &Self::World) {}
+
+        fn interpret(&self, _event: &Event) -> Self::Action {}
+
+        fn update(&mut self, _action: (), world: &mut Self::World) -> Transition<Self> {
+            world.push("popup");
+            Transition::Exit
+        }
+    }
+
     #[test]
     fn it_works() {
         assert_eq!(1, 1);
     }
+
+    #[test]
+    fn resize_commands_name_a_size() {
+        assert_eq!(resize_command("resize 120 60"), Some((120, 60)));
+        assert_eq!(resize_command("resize 0 60"), None);
+        assert_eq!(resize_command("resize 120"), None);
+        assert_eq!(resize_command("spawn orc"), None);
+    }
+
+    #[test]
+    fn scenes_of_another_type_can_be_pushed() {
+        let mut world = vec![];
+        let mut menu: Box<dyn Scene<Vec<&str>>> = Box::new(Menu);
+
+        let mut popup = match menu.handle(&Event::Nothing, &mut world) {
+            Step::Next(popup) => popup,
+            step => panic!("Expected the popup to be pushed: {:?}", step),
+        };
+        let step = popup.handle(&Event::Nothing, &mut world);
+
+        assert!(matches!(step, Step::Exit));
+        assert_eq!(world, vec!["menu", "popup"]);
+    }
 }
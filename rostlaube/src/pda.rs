@@ -7,9 +7,7 @@ pub trait State: std::fmt::Debug {
     type Data;
     type Action;
 
-    fn render(&self) {
-        println!("STATE={:?}: rendering", self);
-    }
+    fn render(&self) {}
     fn interpret(&self, event: Event) -> Self::Action;
     fn update(
         &self,
@@ -29,6 +27,7 @@ pub enum Transition<D, A> {
 #[derive(Debug)]
 pub struct Engine<D, A> {
     stack: Vec<BoxedState<D, A>>,
+    log: bool,
 }
 
 impl<D, A> Engine<D, A>
@@ -39,22 +38,28 @@ where
     pub fn new(start: BoxedState<D, A>) -> Self {
         Engine {
             stack: vec![start],
+            log: false,
         }
     }
 
+    /// Trace the stack, actions and transitions to stdout
+    pub fn set_log(&mut self, log: bool) {
+        self.log = log;
+    }
+
     pub fn run(&mut self, mut data: D) -> D {
         while let Some(state) = self.stack.pop() {
-            println!("ENGINE: stack = {:?}", self.stack);
+            log!(self, "stack = {:?}", self.stack);
 
-            println!("ENGINE: state = {:?}", state);
+            log!(self, "state = {:?}", state);
 
             state.render();
 
             let action = state.interpret(self.next_event());
-            println!("ENGINE: action = {:?}", action);
+            log!(self, "action = {:?}", action);
 
             let transition = state.update(&mut data, action);
-            println!("ENGINE: transition = {:?}", transition);
+            log!(self, "transition = {:?}", transition);
 
             match transition {
                 Transition::Continue => {
@@ -71,7 +76,7 @@ where
             }
         }
 
-        println!("ENGINE: stack empty");
+        log!(self, "stack empty");
         data
     }
 
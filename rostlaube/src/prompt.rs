@@ -0,0 +1,194 @@
+use crate::{State, Event, Transition};
+use crate::colors;
+use crate::console::{Console, Offscreen, TextAlignment, BackgroundFlag};
+use crate::input::{Key, KeyCode};
+
+/// Check the input of a prompt, explaining what is wrong with it
+pub type Validator = fn(&str) -> Result<(), String>;
+
+/// Accept any input, including no input at all
+pub fn any(_input: &str) -> Result<(), String> {
+    Ok(())
+}
+
+/// Reject empty input
+pub fn non_empty(input: &str) -> Result<(), String> {
+    if input.trim().is_empty() {
+        Err(String::from("Please enter something."))
+    } else {
+        Ok(())
+    }
+}
+
+/// Modal asking for a line of text
+///
+/// The world is the answer: `None` until the input is confirmed with
+/// Enter, and left as `None` when the prompt is cancelled with Escape.
+#[derive(Debug)]
+pub struct Prompt {
+    text: String,
+    max_len: usize,
+    validate: Validator,
+    input: String,
+    error: Option<String>,
+}
+
+#[derive(Debug)]
+pub enum PromptAction {
+    Confirm,
+    Cancel,
+    Read(String),
+    Delete,
+    InvalidKey,
+}
+
+impl Prompt {
+    pub fn new<T: Into<String>>(text: T, max_len: usize, validate: Validator) -> Self {
+        Prompt {
+            text: text.into(),
+            max_len,
+            validate,
+            input: String::new(),
+            error: None,
+        }
+    }
+}
+
+impl State for Prompt {
+    type World = Option<String>;
+    type Action = PromptAction;
+
+    fn render(&self, con: &mut Offscreen, _world: &Self::World) {
+        let (w, h) = (con.width(), con.height());
+        con.set_default_background(colors::BLACK);
+        con.set_default_foreground(colors::WHITE);
+        con.print_ex(
+            w / 2,
+            h / 3,
+            BackgroundFlag::Set,
+            TextAlignment::Center,
+            &self.text,
+        );
+        con.print_ex(
+            w / 2,
+            h / 3 + 2,
+            BackgroundFlag::Set,
+            TextAlignment::Center,
+            format!("{}_", self.input),
+        );
+        if let Some(error) = &self.error {
+            con.set_default_foreground(colors::RED);
+            con.print_ex(
+                w / 2,
+                h / 3 + 4,
+                BackgroundFlag::Set,
+                TextAlignment::Center,
+                error,
+            );
+        }
+    }
+
+    fn interpret(&self, event: &Event) -> Self::Action {
+        use PromptAction::*;
+        use Event::*;
+        match event {
+            KeyEvent(Key {
+                code: KeyCode::Enter,
+                ..
+            }) => Confirm,
+            KeyEvent(Key {
+                code: KeyCode::Escape,
+                ..
+            }) => Cancel,
+            KeyEvent(Key {
+                code: KeyCode::Backspace,
+                ..
+            }) => Delete,
+            KeyEvent(Key {
+                code: KeyCode::Spacebar,
+                ..
+            }) => Read(String::from(" ")),
+            KeyEvent(Key {
+                code: KeyCode::Char,
+                printable,
+                shift,
+                ..
+            }) => {
+                if *shift {
+                    Read(printable.to_uppercase().to_string())
+                } else {
+                    Read(printable.to_string())
+                }
+            }
+            _ => InvalidKey,
+        }
+    }
+
+    fn update(&mut self, action: Self::Action, world: &mut Self::World) -> Transition<Self> {
+        use PromptAction::*;
+        match action {
+            Confirm => match (self.validate)(&self.input) {
+                Ok(()) => {
+                    world.replace(self.input.clone());
+                    Transition::Exit
+                }
+                Err(e) => {
+                    self.error.replace(e);
+                    Transition::Continue
+                }
+            },
+            Cancel => {
+                world.take();
+                Transition::Exit
+            }
+            Read(s) => {
+                if self.input.chars().count() + s.chars().count() <= self.max_len {
+                    self.input.push_str(&s);
+                }
+                Transition::Continue
+            }
+            Delete => {
+                self.input.pop();
+                Transition::Continue
+            }
+            InvalidKey => Transition::Continue,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn type_in(prompt: &mut Prompt, answer: &mut Option<String>, text: &str) {
+        for c in text.chars() {
+            prompt.update(PromptAction::Read(c.to_string()), answer);
+        }
+    }
+
+    #[test]
+    fn confirmed_input_is_the_answer() {
+        let mut prompt = Prompt::new("Name this save:", 4, non_empty);
+        let mut answer = None;
+
+        let transition = prompt.update(PromptAction::Confirm, &mut answer);
+        assert!(matches!(transition, Transition::Continue));
+        assert!(prompt.error.is_some());
+
+        type_in(&mut prompt, &mut answer, "save1");
+        let transition = prompt.update(PromptAction::Confirm, &mut answer);
+        assert!(matches!(transition, Transition::Exit));
+        assert_eq!(answer.as_deref(), Some("save"));
+    }
+
+    #[test]
+    fn cancelling_gives_no_answer() {
+        let mut prompt = Prompt::new("Enter seed:", 10, any);
+        let mut answer = None;
+
+        type_in(&mut prompt, &mut answer, "1234");
+        let transition = prompt.update(PromptAction::Cancel, &mut answer);
+        assert!(matches!(transition, Transition::Exit));
+        assert_eq!(answer, None);
+    }
+}
@@ -1,26 +1,60 @@
-pub use rand::random;
-use rand::Rng;
+use std::cell::RefCell;
+use std::error::Error;
+use std::fmt;
+use std::str::FromStr;
+
+use rand::{Rand, Rng, SeedableRng, StdRng};
+use serde::{Deserialize, Serialize};
+
+thread_local! {
+    /// The generator behind all rolls on this thread
+    static RNG: RefCell<StdRng> = RefCell::new(StdRng::new().expect("Could not seed the rng"));
+}
+
+/// Make all following rolls on this thread repeatable
+pub fn seed(seed: u64) {
+    RNG.with(|rng| *rng.borrow_mut() = StdRng::from_seed(&[seed as usize]));
+}
+
+fn with<T, F: FnOnce(&mut StdRng) -> T>(f: F) -> T {
+    RNG.with(|rng| f(&mut rng.borrow_mut()))
+}
+
+/// A random value of any type that can be generated
+pub fn random<T: Rand>() -> T {
+    with(|rng| rng.gen())
+}
 
 /// Random number within an inclusive [min:max] range
 pub fn within(min: i32, max: i32) -> i32 {
-    rand::thread_rng().gen_range(min, max + 1)
+    with(|rng| rng.gen_range(min, max + 1))
 }
 
 /// Return true with probability p
 pub fn chance(p: f32) -> bool {
-    rand::thread_rng().next_f32() <= p
+    with(|rng| rng.next_f32() <= p)
 }
 
 /// Choose a random value from the input slice
 pub fn choose<T>(values: &[T]) -> Option<&T> {
-    rand::thread_rng().choose(values)
+    with(|rng| rng.choose(values))
+}
+
+/// Put the values into a random order
+pub fn shuffle<T>(values: &mut [T]) {
+    with(|rng| {
+        // Fisher-Yates, swapping each value with one that comes before it
+        for i in (1..values.len()).rev() {
+            values.swap(i, rng.gen_range(0, i + 1));
+        }
+    })
 }
 
 /// Roll custom dice
 pub fn dx(x: i32) -> i32 {
     match x {
         0 => 0,
-        x => rand::thread_rng().gen_range(1, x + 1),
+        x => with(|rng| rng.gen_range(1, x + 1)),
     }
 }
 /// Roll n custom dice
@@ -29,7 +63,7 @@ pub fn ndx(n: i32, x: i32) -> i32 {
 }
 /// Roll 1d3
 pub fn d3() -> i32 {
-    rand::thread_rng().gen_range(1, 4)
+    with(|rng| rng.gen_range(1, 4))
 }
 /// Roll nd3
 pub fn nd3(n: i32) -> i32 {
@@ -37,7 +71,7 @@ pub fn nd3(n: i32) -> i32 {
 }
 /// Roll 1d6
 pub fn d6() -> i32 {
-    rand::thread_rng().gen_range(1, 7)
+    with(|rng| rng.gen_range(1, 7))
 }
 /// Roll nd6
 pub fn nd6(n: i32) -> i32 {
@@ -45,7 +79,7 @@ pub fn nd6(n: i32) -> i32 {
 }
 /// Roll 1d12
 pub fn d12() -> i32 {
-    rand::thread_rng().gen_range(1, 13)
+    with(|rng| rng.gen_range(1, 13))
 }
 /// Roll nd12
 pub fn nd12(n: i32) -> i32 {
@@ -53,9 +87,145 @@ pub fn nd12(n: i32) -> i32 {
 }
 /// Roll 1d20
 pub fn d20() -> i32 {
-    rand::thread_rng().gen_range(1, 21)
+    with(|rng| rng.gen_range(1, 21))
 }
 /// Roll 1d100
 pub fn d100() -> i32 {
-    rand::thread_rng().gen_range(1, 101)
+    with(|rng| rng.gen_range(1, 101))
+}
+
+/// Dice notation that could not be read
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseError {
+    pub notation: String,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Invalid dice notation: {:?}", self.notation)
+    }
+}
+
+impl Error for ParseError {}
+
+/// A number of dice with the same number of sides and a fixed modifier
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Dice {
+    pub n: i32,
+    pub x: i32,
+    pub modifier: i32,
+}
+
+impl Dice {
+    pub fn roll(&self) -> i32 {
+        ndx(self.n, self.x) + self.modifier
+    }
+}
+
+impl fmt::Display for Dice {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}d{}", self.n, self.x)?;
+        match self.modifier {
+            0 => Ok(()),
+            m if m > 0 => write!(f, "+{}", m),
+            m => write!(f, "{}", m),
+        }
+    }
+}
+
+impl FromStr for Dice {
+    type Err = ParseError;
+
+    /// Read dice notation, e.g. "2d6+1" or "1d20-2"
+    fn from_str(notation: &str) -> Result<Self, ParseError> {
+        let error = || ParseError { notation: notation.into() };
+        let notation = notation.trim();
+        let (dice, modifier) = match notation.find(['+', '-']) {
+            Some(i) => {
+                let modifier = number(&notation[i + 1..]).ok_or_else(error)?;
+                match &notation[i..i + 1] {
+                    "-" => (&notation[..i], -modifier),
+                    _ => (&notation[..i], modifier),
+                }
+            }
+            None => (notation, 0),
+        };
+        let (n, x) = dice.split_once('d').ok_or_else(error)?;
+        // "d6" is the same as "1d6"
+        let n = if n.is_empty() { Some(1) } else { number(n) };
+        match (n, number(x)) {
+            (Some(n), Some(x)) => Ok(Dice { n, x, modifier }),
+            _ => Err(error()),
+        }
+    }
+}
+
+/// Roll dice given in dice notation, e.g. "2d6+1" or "1d20-2"
+pub fn roll(notation: &str) -> Result<i32, ParseError> {
+    notation.parse::<Dice>().map(|dice| dice.roll())
+}
+
+/// Parse a plain, unsigned number
+fn number(s: &str) -> Option<i32> {
+    if !s.is_empty() && s.chars().all(|c| c.is_ascii_digit()) {
+        s.parse().ok()
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rolls_stay_within_the_dice() {
+        for _ in 0..100 {
+            let r = roll("3d8").unwrap();
+            assert!((3..=24).contains(&r), "3d8 rolled {}", r);
+            let r = roll("1d4+3").unwrap();
+            assert!((4..=7).contains(&r), "1d4+3 rolled {}", r);
+            let r = roll("1d20-2").unwrap();
+            assert!((-1..=18).contains(&r), "1d20-2 rolled {}", r);
+            let r = roll("d6").unwrap();
+            assert!((1..=6).contains(&r), "d6 rolled {}", r);
+        }
+    }
+
+    #[test]
+    fn malformed_dice_are_rejected() {
+        for notation in &["", "d", "3", "3x8", "2d6+", "2d6+1+1", "-1d6", "2d-6", "ad6", "2d6*2"] {
+            assert_eq!(
+                roll(notation),
+                Err(ParseError { notation: notation.to_string() }),
+            );
+        }
+    }
+
+    #[test]
+    fn dice_are_written_as_they_are_read() {
+        for notation in &["2d4+1", "1d20-2", "3d6"] {
+            let dice: Dice = notation.parse().unwrap();
+            assert_eq!(dice.to_string(), *notation);
+        }
+        assert_eq!("d6".parse(), Ok(Dice { n: 1, x: 6, modifier: 0 }));
+    }
+
+    #[test]
+    fn the_same_seed_rolls_the_same() {
+        seed(42);
+        let first: Vec<i32> = (0..20).map(|_| d100()).collect();
+        seed(42);
+        let second: Vec<i32> = (0..20).map(|_| d100()).collect();
+        assert_eq!(first, second);
+    }
+
+    #[test]
+    fn shuffling_keeps_every_value() {
+        let mut values: Vec<i32> = (0..50).collect();
+        shuffle(&mut values);
+        assert_ne!(values, (0..50).collect::<Vec<i32>>());
+        values.sort();
+        assert_eq!(values, (0..50).collect::<Vec<i32>>());
+    }
 }
@@ -48,7 +48,7 @@ impl Draw for Bar {
             0,
             BackgroundFlag::None,
             TextAlignment::Left,
-            &format!("{}: {}/{}", self.name, self.current, self.maximum),
+            format!("{}: {}/{}", self.name, self.current, self.maximum),
         );
 
         console::blit(&con, (0, 0), (width, 1), layer, (self.x, self.y), 1.0, 1.0);
@@ -1,72 +1,201 @@
-use crate::game::{self, Action, Game};
-use crate::{rng, Direction, PLAYER};
+use serde::{Deserialize, Serialize};
 
-#[derive(Debug)]
+use crate::game::{self, Action, Game, Spell};
+use crate::objects::Id;
+use crate::{rng, Direction, Location, PLAYER};
+
+/// Number of turns a monster searches for the player after losing sight
+pub const HUNT_PATIENCE: i32 = 10;
+/// Chance that an idle monster takes a step in a random direction
+const IDLE_WANDER_CHANCE: f32 = 0.25;
+/// Share of its health below which a monster runs from the player
+const FLEE_HEALTH: f32 = 0.25;
+/// Number of turns a wounded monster runs before it looks back
+const FLEE_TURNS: i32 = 5;
+/// Directions a confused or idle monster may stumble in
+const DIRECTIONS: [Direction; 8] = [
+    Direction(-1, -1),
+    Direction(0, -1),
+    Direction(1, -1),
+    Direction(-1, 0),
+    Direction(1, 0),
+    Direction(-1, 1),
+    Direction(0, 1),
+    Direction(1, 1),
+];
+
+#[derive(Debug, Serialize, Deserialize)]
 pub enum Ai {
     Basic,
     Idle,
-    Confused { previous: Box<Ai>, num_turns: i32 },
+    Caster {
+        cooldown: i32,
+    },
+    Hunting {
+        last_seen: Location,
+        patience: i32,
+    },
+    /// Running from the player for a number of turns
+    Fleeing {
+        turns: i32,
+    },
 }
 
 impl Ai {
     /// Calculate an Ai turn
-    pub fn turn(self, id: usize, game: &Game) -> (game::Turn, Self) {
+    pub fn turn(self, id: Id, game: &Game) -> (game::Turn, Self) {
         match self {
-            Ai::Basic => basic(id, &game),
-            Ai::Idle => idle(id, &game),
-            Ai::Confused {
-                previous,
-                num_turns,
-            } => confused(id, &game, previous, num_turns),
+            Ai::Basic => basic(id, game),
+            Ai::Idle => idle(id, game),
+            Ai::Caster { cooldown } => caster(id, game, cooldown),
+            Ai::Hunting {
+                last_seen,
+                patience,
+            } => hunting(id, game, last_seen, patience),
+            Ai::Fleeing { turns } => fleeing(id, game, turns),
         }
     }
 }
 
-/// When the monster is confused
-fn confused(id: usize, _game: &Game, previous: Box<Ai>, num_turns: i32) -> (game::Turn, Ai) {
-    let mut turn = vec![];
-    let ai = if num_turns >= 1 {
-        let num_turns = num_turns - 1;
-        turn.push(Action::Move(
-            id,
-            Direction(rng::within(-1, 1), rng::within(-1, 1)),
-        ));
-        Ai::Confused {
-            previous,
-            num_turns,
-        }
-    } else {
-        *previous
-    };
-    (turn, ai)
+/// When the monster is confused, whatever it would do otherwise
+pub fn stumble(id: Id) -> game::Turn {
+    let direction = *rng::choose(&DIRECTIONS).expect("Directions to choose from");
+    vec![Action::Move(id, direction)]
 }
 
 /// When the monster sees the player
-fn basic(id: usize, game: &Game) -> (game::Turn, Ai) {
+fn basic(id: Id, game: &Game) -> (game::Turn, Ai) {
     let mut turn = vec![];
     let object = &game.objects[id];
     let player = &game.objects[PLAYER];
 
     if game.visible(&object.loc) {
-        if game::distance(&object.loc, &player.loc) >= 2.0 {
+        if wounded(id, game) {
+            if let Some(direction) = away_from(id, &player.loc, game) {
+                turn.push(Action::Panic(id));
+                turn.push(Action::Move(id, direction));
+                return (turn, Ai::Fleeing { turns: FLEE_TURNS });
+            }
+        }
+        let in_range = object
+            .shooting_range()
+            .is_some_and(|range| game::chebyshev_distance(&object.loc, &player.loc) <= range);
+        let player_alive = player.fighter.is_some_and(|f| f.health > 0);
+        if game::distance(&object.loc, &player.loc) >= 2.0 && in_range && player_alive {
+            turn.push(Action::Shoot(id, player.loc));
+        } else if game::distance(&object.loc, &player.loc) >= 2.0 {
             if rng::d12() > 11 {
                 turn.push(Action::Bark(id));
             }
-            turn.push(Action::Move(id, game::direction(&object.loc, &player.loc)));
-            (turn, Ai::Basic)
-        } else if player.fighter.map_or(false, |f| f.health > 0) {
+            let direction = game::path_to(&object.loc, &player.loc, game)
+                .unwrap_or_else(|| game::direction(&object.loc, &player.loc));
+            turn.push(Action::Move(id, direction));
+        } else if player_alive {
             turn.push(Action::Attack(id, PLAYER));
-            (turn, Ai::Basic)
-        } else {
-            (turn, Ai::Basic)
         }
+        (
+            turn,
+            Ai::Hunting {
+                last_seen: player.loc,
+                patience: HUNT_PATIENCE,
+            },
+        )
     } else {
         (turn, Ai::Idle)
     }
 }
 
+/// When the monster lost sight of the player and searches for them
+fn hunting(id: Id, game: &Game, last_seen: Location, patience: i32) -> (game::Turn, Ai) {
+    let object = &game.objects[id];
+
+    if game.visible(&object.loc) {
+        basic(id, game)
+    } else if object.loc == last_seen || patience <= 0 {
+        (vec![], Ai::Idle)
+    } else {
+        let turn = vec![Action::Move(id, game::direction(&object.loc, &last_seen))];
+        (
+            turn,
+            Ai::Hunting {
+                last_seen,
+                patience: patience - 1,
+            },
+        )
+    }
+}
+
+/// When the monster is too wounded to fight and runs from the player
+fn fleeing(id: Id, game: &Game, turns: i32) -> (game::Turn, Ai) {
+    let player = &game.objects[PLAYER];
+
+    match away_from(id, &player.loc, game) {
+        Some(direction) if turns > 0 => (
+            vec![Action::Move(id, direction)],
+            Ai::Fleeing { turns: turns - 1 },
+        ),
+        // Cornered or out of breath, it fights for its life
+        _ => basic(id, game),
+    }
+}
+
+/// The monster's health is so low that it would rather run
+fn wounded(id: Id, game: &Game) -> bool {
+    game.objects[id]
+        .fighter
+        .is_some_and(|f| (f.health as f32) < f.max_health as f32 * FLEE_HEALTH)
+}
+
+/// A step that takes the monster further away from a location, straight
+/// away from it if that is open
+fn away_from(id: Id, from: &Location, game: &Game) -> Option<Direction> {
+    let loc = game.objects[id].loc;
+    let open = |d: &Direction| !game.is_blocked(&game::destination(&loc, d));
+    let straight = game::direction(from, &loc);
+    if straight != Direction(0, 0) && open(&straight) {
+        return Some(straight);
+    }
+    let current = game::distance(&loc, from);
+    DIRECTIONS
+        .iter()
+        .copied()
+        .filter(open)
+        .map(|d| (d, game::distance(&game::destination(&loc, &d), from)))
+        .filter(|&(_, distance)| distance > current)
+        .max_by(|(_, a), (_, b)| a.total_cmp(b))
+        .map(|(d, _)| d)
+}
+
+/// When the monster can cast spells at the player
+fn caster(id: Id, game: &Game, cooldown: i32) -> (game::Turn, Ai) {
+    let object = &game.objects[id];
+    let player = &game.objects[PLAYER];
+    let in_range = game::distance(&object.loc, &player.loc) <= game::SPELL_RANGE as f32;
+    let player_alive = player.fighter.is_some_and(|f| f.health > 0);
+
+    if cooldown <= 0 && in_range && player_alive && game.visible(&object.loc) {
+        let spell = *rng::choose(&[Spell::Confuse, Spell::Bolt]).expect("Spells to choose from");
+        let turn = vec![Action::Cast(id, PLAYER, spell)];
+        (
+            turn,
+            Ai::Caster {
+                cooldown: game::SPELL_COOLDOWN,
+            },
+        )
+    } else {
+        // Fight like any other monster while the spell is recharging
+        let (turn, _) = basic(id, game);
+        (
+            turn,
+            Ai::Caster {
+                cooldown: cooldown - 1,
+            },
+        )
+    }
+}
+
 /// When the monster does not see the player
-fn idle(id: usize, game: &Game) -> (game::Turn, Ai) {
+fn idle(id: Id, game: &Game) -> (game::Turn, Ai) {
     let mut turn = vec![];
     let object = &game.objects[id];
 
@@ -75,7 +204,232 @@ fn idle(id: usize, game: &Game) -> (game::Turn, Ai) {
     } else if rng::dx(1000) > 999 {
         turn.push(Action::Mumble(id));
         (turn, Ai::Idle)
+    } else if rng::chance(IDLE_WANDER_CHANCE) {
+        let open: Vec<Direction> = DIRECTIONS
+            .iter()
+            .copied()
+            .filter(|d| !game.is_blocked(&game::destination(&object.loc, d)))
+            .collect();
+        if let Some(direction) = rng::choose(&open) {
+            turn.push(Action::Move(id, *direction));
+        }
+        (turn, Ai::Idle)
     } else {
         (turn, Ai::Idle)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dungeon::Layout;
+    use crate::game::{Difficulty, Map, Object, StatusEffect, Tile};
+    use crate::Dimension;
+
+    #[test]
+    fn hunters_search_where_they_last_saw_the_player() {
+        let mut game = Game::new(
+            "Tester",
+            Layout::new(Dimension(40, 30), Dimension(6, 8), 10, 0, 0),
+            1,
+            Difficulty::Normal,
+        );
+        game.clear_objects();
+        let mut map = Map::new(Dimension(40, 30), Tile::empty());
+        for y in (0..30).filter(|&y| y != 10) {
+            map.set(&Location(20, y), Tile::wall());
+        }
+        game.set_map(map);
+        game.relocate(PLAYER, Location(19, 10));
+        let mut orc = Object::orc(Location(15, 10));
+        orc.movement = Some(game::Movement { speed: 100 });
+        let orc = game.spawn(orc);
+        game.refresh();
+
+        let (_, mut ai) = Ai::Basic.turn(orc, &game);
+        assert!(matches!(
+            ai,
+            Ai::Hunting {
+                last_seen: Location(19, 10),
+                ..
+            }
+        ));
+
+        // The player slips away behind the wall
+        game.relocate(PLAYER, Location(35, 25));
+        game.refresh();
+        assert!(!game.visible(&game.objects[orc].loc));
+
+        // Idle monsters wander off, so stop once it gave up
+        for _ in 0..10 {
+            if matches!(ai, Ai::Idle) {
+                break;
+            }
+            let (turn, next) = ai.turn(orc, &game);
+            game.play(&turn);
+            game.refresh();
+            ai = next;
+        }
+        assert_eq!(game.objects[orc].loc, Location(19, 10));
+        assert!(matches!(ai, Ai::Idle));
+    }
+
+    #[test]
+    fn wounded_monsters_run_until_cornered() {
+        let mut game = Game::new(
+            "Tester",
+            Layout::new(Dimension(40, 30), Dimension(6, 8), 0, 0, 0),
+            1,
+            Difficulty::Normal,
+        );
+        game.set_map(Map::new(Dimension(40, 30), Tile::empty()));
+        game.relocate(PLAYER, Location(10, 10));
+        let mut orc = Object::orc(Location(11, 10));
+        orc.movement = Some(game::Movement { speed: 100 });
+        if let Some(fighter) = orc.fighter.as_mut() {
+            fighter.health = 1;
+        }
+        let orc = game.spawn(orc);
+        game.refresh();
+
+        let (turn, mut ai) = Ai::Basic.turn(orc, &game);
+        assert!(matches!(
+            turn.as_slice(),
+            [Action::Panic(_), Action::Move(_, Direction(1, 0))]
+        ));
+        assert!(matches!(ai, Ai::Fleeing { turns: FLEE_TURNS }));
+        game.play(&turn);
+        for _ in 0..FLEE_TURNS {
+            let (turn, next) = ai.turn(orc, &game);
+            game.play(&turn);
+            ai = next;
+        }
+        assert_eq!(game.objects[orc].loc, Location(11 + FLEE_TURNS + 1, 10));
+
+        // With its back to the wall, it fights
+        game.relocate(PLAYER, Location(38, 28));
+        game.relocate(orc, Location(39, 29));
+        game.refresh();
+        let (turn, ai) = Ai::Fleeing { turns: 3 }.turn(orc, &game);
+        assert!(matches!(turn.as_slice(), [Action::Attack(_, PLAYER)]));
+        assert!(matches!(ai, Ai::Hunting { .. }));
+    }
+
+    #[test]
+    fn confused_monsters_stumble_around() {
+        let mut game = Game::new(
+            "Tester",
+            Layout::new(Dimension(40, 30), Dimension(6, 8), 10, 0, 0),
+            1,
+            Difficulty::Normal,
+        );
+        game.clear_objects();
+        game.set_map(Map::new(Dimension(40, 30), Tile::empty()));
+        game.relocate(PLAYER, Location(30, 20));
+        let orc = game.spawn(Object::orc(Location(10, 10)));
+        game.objects[orc].afflict(StatusEffect::Confused { turns: 20 });
+
+        for _ in 0..20 {
+            let turn = game.ai_turns();
+            match turn.as_slice() {
+                [Action::Move(id, direction)] => {
+                    assert_eq!(*id, orc);
+                    assert!(DIRECTIONS.contains(direction));
+                }
+                _ => panic!("Expected a single move: {:?}", turn),
+            }
+            assert!(matches!(game.objects[orc].ai, Some(Ai::Basic)));
+        }
+        for _ in 0..20 {
+            game.update(Action::Wait(PLAYER));
+        }
+        assert!(!game.objects[orc].is_confused());
+    }
+
+    #[test]
+    fn archers_shoot_from_a_distance() {
+        let mut game = Game::new(
+            "Tester",
+            Layout::new(Dimension(40, 30), Dimension(6, 8), 10, 0, 0),
+            1,
+            Difficulty::Normal,
+        );
+        game.clear_objects();
+        game.set_map(Map::new(Dimension(40, 30), Tile::empty()));
+        game.relocate(PLAYER, Location(10, 10));
+        let archer = game.spawn(Object::archer(Location(14, 10)));
+        game.refresh();
+
+        let (turn, _) = Ai::Basic.turn(archer, &game);
+        assert!(matches!(
+            turn.as_slice(),
+            [Action::Shoot(id, Location(10, 10))] if *id == archer
+        ));
+
+        // Out of range it comes closer instead
+        game.relocate(archer, Location(17, 10));
+        game.refresh();
+        let (turn, _) = Ai::Basic.turn(archer, &game);
+        assert!(turn.iter().all(|a| !matches!(a, Action::Shoot(..))));
+    }
+
+    #[test]
+    fn idle_monsters_wander_into_open_space() {
+        let mut game = Game::new(
+            "Tester",
+            Layout::new(Dimension(40, 30), Dimension(6, 8), 10, 0, 0),
+            1,
+            Difficulty::Normal,
+        );
+        game.clear_objects();
+        let mut map = Map::new(Dimension(40, 30), Tile::wall());
+        map.set(&Location(10, 10), Tile::empty());
+        map.set(&Location(11, 11), Tile::empty());
+        // Far enough away that the player cannot see the orc
+        game.relocate(PLAYER, Location(30, 20));
+        game.set_map(map);
+        let orc = game.spawn(Object::orc(Location(10, 10)));
+
+        let moves: Vec<game::Turn> = (0..200).map(|_| idle(orc, &game).0).collect();
+        assert!(moves.iter().any(|turn| !turn.is_empty()));
+        for turn in moves.iter().filter(|turn| !turn.is_empty()) {
+            match turn.as_slice() {
+                [Action::Move(_, direction)] => assert_eq!(*direction, Direction(1, 1)),
+                [Action::Mumble(_)] => {}
+                _ => panic!("Expected a move or a mumble: {:?}", turn),
+            }
+        }
+    }
+
+    #[test]
+    fn monsters_walk_around_pillars() {
+        let mut game = Game::new(
+            "Tester",
+            Layout::new(Dimension(40, 30), Dimension(6, 8), 10, 0, 0),
+            1,
+            Difficulty::Normal,
+        );
+        game.clear_objects();
+        let mut map = Map::new(Dimension(40, 30), Tile::empty());
+        // A low pillar that blocks the way but not the view
+        if let Some(tile) = map.get_mut(&Location(12, 10)) {
+            tile.blocked = true;
+        }
+        game.set_map(map);
+        game.relocate(PLAYER, Location(14, 10));
+        let mut orc = Object::orc(Location(10, 10));
+        orc.movement = Some(game::Movement { speed: 100 });
+        let orc = game.spawn(orc);
+        game.refresh();
+
+        for _ in 0..3 {
+            let (turn, _) = Ai::Basic.turn(orc, &game);
+            game.play(&turn);
+            game.refresh();
+        }
+        assert_eq!(
+            game::distance(&game.objects[orc].loc, &game.objects[PLAYER].loc).round(),
+            1.0
+        );
+    }
+}
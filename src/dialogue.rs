@@ -0,0 +1,165 @@
+//! Conversations with the non-hostile inhabitants of the caves
+//!
+//! A dialogue is plain data: a list of nodes, each with a line spoken by
+//! the NPC and the numbered responses the player can choose from. A
+//! response either leads to another node or ends the conversation, and may
+//! trigger an effect on the game.
+use crate::game::{Game, Messages};
+use crate::{colors, PLAYER};
+
+/// A line spoken by the NPC and the possible answers
+#[derive(Debug)]
+pub struct Node {
+    pub text: &'static str,
+    pub responses: &'static [Response],
+}
+
+/// An answer the player can give
+#[derive(Debug)]
+pub struct Response {
+    pub text: &'static str,
+    /// Index of the next node, `None` ends the conversation
+    pub next: Option<usize>,
+    pub effect: Option<Effect>,
+}
+
+/// What happens when a response is chosen
+#[derive(Debug, Clone, Copy)]
+pub enum Effect {
+    /// The NPC tends to the player's wounds
+    Heal(i32),
+    /// The NPC shares some advice
+    Tell(&'static str),
+}
+
+pub type Dialogue = &'static [Node];
+
+/// An old hermit who has made the caves his home
+pub static HERMIT: Dialogue = &[
+    Node {
+        text: "Another one, down here in the rust? What brings you to my caves?",
+        responses: &[
+            Response {
+                text: "I'm looking for a way through.",
+                next: Some(1),
+                effect: None,
+            },
+            Response {
+                text: "I'm hurt. Can you help me?",
+                next: Some(2),
+                effect: None,
+            },
+            Response {
+                text: "Nothing. Farewell.",
+                next: None,
+                effect: None,
+            },
+        ],
+    },
+    Node {
+        text: "Through? Ha! The deeper you go, the angrier they get. Mind the shamans.",
+        responses: &[
+            Response {
+                text: "Any advice?",
+                next: None,
+                effect: Some(Effect::Tell(
+                    "The hermit whispers: \"Read the word of recall before you are surrounded.\"",
+                )),
+            },
+            Response {
+                text: "Thanks.",
+                next: None,
+                effect: None,
+            },
+        ],
+    },
+    Node {
+        text: "Hold still. This will sting.",
+        responses: &[Response {
+            text: "Thank you.",
+            next: None,
+            effect: Some(Effect::Heal(10)),
+        }],
+    },
+];
+
+/// Every dialogue by the name it is saved under
+static DIALOGUES: &[(&str, Dialogue)] = &[("hermit", HERMIT)];
+
+/// Save dialogues by name, they are static data and not part of the game
+pub mod by_name {
+    use super::{Dialogue, DIALOGUES};
+    use serde::de::Error;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    pub fn serialize<S: Serializer>(dialogue: &Option<Dialogue>, s: S) -> Result<S::Ok, S::Error> {
+        let name = dialogue.and_then(|dialogue| {
+            DIALOGUES
+                .iter()
+                .find(|(_, d)| std::ptr::eq(*d, dialogue))
+                .map(|(name, _)| *name)
+        });
+        name.serialize(s)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<Option<Dialogue>, D::Error> {
+        match Option::<String>::deserialize(d)? {
+            Some(name) => DIALOGUES
+                .iter()
+                .find(|(n, _)| *n == name)
+                .map(|(_, dialogue)| Some(*dialogue))
+                .ok_or_else(|| D::Error::custom(format!("unknown dialogue: {}", name))),
+            None => Ok(None),
+        }
+    }
+}
+
+/// Apply the response chosen at a node and return the next node
+pub fn respond(game: &mut Game, dialogue: Dialogue, node: usize, choice: usize) -> Option<usize> {
+    let response = dialogue.get(node)?.responses.get(choice)?;
+    if let Some(effect) = response.effect {
+        let messages = apply(game, effect);
+        game.messages.append(messages);
+    }
+    response.next
+}
+
+fn apply(game: &mut Game, effect: Effect) -> Messages {
+    match effect {
+        Effect::Heal(amount) => match game.objects[PLAYER].fighter.as_mut() {
+            Some(fighter) => {
+                fighter.health = std::cmp::min(fighter.health + amount, fighter.max_health);
+                Messages::new("Your wounds are tended to.", colors::GREEN)
+            }
+            None => Messages::empty(),
+        },
+        Effect::Tell(advice) => Messages::new(advice, colors::LIGHT_BLUE),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dungeon::Layout;
+    use crate::game::Difficulty;
+    use crate::Dimension;
+
+    #[test]
+    fn asking_for_help_heals() {
+        let mut game = Game::new(
+            "Tester",
+            Layout::new(Dimension(40, 30), Dimension(6, 8), 10, 0, 0),
+            1,
+            Difficulty::Normal,
+        );
+        if let Some(fighter) = game.objects[PLAYER].fighter.as_mut() {
+            fighter.health = 1;
+        }
+
+        let node = respond(&mut game, HERMIT, 0, 1);
+        assert_eq!(node, Some(2));
+        assert_eq!(respond(&mut game, HERMIT, 2, 0), None);
+
+        assert!(game.objects[PLAYER].fighter.unwrap().health > 1);
+    }
+}
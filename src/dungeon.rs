@@ -1,24 +1,227 @@
 // Stdlib
 use std::cmp;
 
+// External
+use rostlaube::bsp::Bsp;
+use serde::{Deserialize, Serialize};
+
 // Internal
-use crate::game::{Item, Map, Object, Tile};
+use crate::game::{Item, Map, Object, Tile, TrapKind};
+use crate::objects::Objects;
 use crate::PLAYER;
 use crate::{game, rng};
 use crate::{Dimension, Location};
 
+/// Parameters for generating a level
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct Layout {
+    pub map_dimensions: Dimension,
+    pub room_dimensions: Dimension,
+    pub max_rooms: i32,
+    pub max_room_monsters: i32,
+    pub max_room_items: i32,
+    #[serde(default)]
+    pub algorithm: GenAlgorithm,
+}
+
+impl Layout {
+    /// Levels of rooms dropped wherever they fit
+    pub fn new(
+        map_dimensions: Dimension,
+        room_dimensions: Dimension,
+        max_rooms: i32,
+        max_room_monsters: i32,
+        max_room_items: i32,
+    ) -> Self {
+        Layout {
+            map_dimensions,
+            room_dimensions,
+            max_rooms,
+            max_room_monsters,
+            max_room_items,
+            algorithm: Default::default(),
+        }
+    }
+}
+
+/// How the rooms of a level are laid out
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum GenAlgorithm {
+    /// Rooms dropped wherever they fit, each joined to the one before
+    #[default]
+    Rooms,
+    /// The map split in halves over and over with a room in every part
+    Bsp,
+}
+
+/// Chance that a hermit lives in the caves
+const HERMIT_CHANCE: f32 = 0.3;
+/// Number of levels until another monster may be placed in each room
+const MONSTERS_PER_ROOM_INTERVAL: i32 = 3;
+/// Chance of a trap in a room on the first level
+const TRAP_CHANCE: f32 = 0.1;
+/// How much more likely a trap gets with every level
+const TRAP_CHANCE_PER_DEPTH: f32 = 0.05;
+/// Highest chance of a trap in a room
+const TRAP_CHANCE_MAX: f32 = 0.5;
+/// Chance that a piece of gear or a healing potion lying around is cursed
+const CURSE_CHANCE: f32 = 0.1;
+
+/// Kinds of monsters that live in the caves
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Monster {
+    Orc,
+    Troll,
+    Ogre,
+    Shaman,
+    Spider,
+    Archer,
+}
+
+impl Monster {
+    pub fn spawn(self, loc: Location) -> Object {
+        match self {
+            Monster::Orc => Object::orc(loc),
+            Monster::Troll => Object::troll(loc),
+            Monster::Ogre => Object::ogre(loc),
+            Monster::Shaman => Object::shaman(loc),
+            Monster::Spider => Object::spider(loc),
+            Monster::Archer => Object::archer(loc),
+        }
+    }
+}
+
+/// Weights of the monsters living at a depth
+///
+/// Orcs dominate the upper levels and make way for trolls and ogres deeper
+/// down.
+pub fn monster_table(depth: i32) -> Vec<(Monster, i32)> {
+    let depth = cmp::max(depth, 1) - 1;
+    vec![
+        (Monster::Orc, cmp::max(80 - 10 * depth, 10)),
+        (Monster::Troll, cmp::min(10 + 5 * depth, 40)),
+        (Monster::Ogre, 8 * cmp::max(depth - 1, 0)),
+        (Monster::Shaman, cmp::min(10 + 2 * depth, 25)),
+        (Monster::Spider, cmp::min(5 + 2 * depth, 15)),
+        (Monster::Archer, cmp::min(5 + 3 * depth, 20)),
+    ]
+}
+
+/// Kinds of items that lie around in the caves
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Loot {
+    HealingPotion,
+    LightningScroll,
+    ConfusionScroll,
+    PoisonGas,
+    LightningWand,
+    RecallScroll,
+    EnchantScroll,
+    RemoveCurseScroll,
+    Candle,
+    Lantern,
+    Sword,
+    Axe,
+    Dagger,
+    Shield,
+    Bow,
+    Arrows,
+    Armor,
+    Ration,
+}
+
+impl Loot {
+    pub fn spawn(self, loc: Location) -> Object {
+        match self {
+            Loot::HealingPotion => Object::potion(loc, Item::Heal, "healing potion"),
+            Loot::LightningScroll => Object::scroll(loc, Item::Lightning, "lightning bolt"),
+            Loot::ConfusionScroll => Object::scroll(loc, Item::Confusion, "confusion"),
+            Loot::PoisonGas => Object::potion(loc, Item::PoisonGas, "flask of poison gas"),
+            Loot::LightningWand => {
+                Object::wand(loc, Item::Lightning, "wand of lightning", rng::within(2, 5))
+            }
+            Loot::RecallScroll => Object::scroll(loc, Item::Recall, "word of recall"),
+            Loot::EnchantScroll => Object::scroll(loc, Item::Enchant, "enchantment"),
+            Loot::RemoveCurseScroll => Object::scroll(loc, Item::RemoveCurse, "remove curse"),
+            Loot::Candle => Object::lamp(loc, Item::Candle, "candle"),
+            Loot::Lantern => Object::lamp(loc, Item::Lantern, "brass lantern"),
+            Loot::Sword => Object::sword(loc),
+            Loot::Axe => Object::axe(loc),
+            Loot::Dagger => Object::dagger(loc),
+            Loot::Shield => Object::shield(loc),
+            Loot::Bow => Object::bow(loc),
+            Loot::Arrows => Object::arrows(loc, rng::within(5, 12)),
+            Loot::Armor => Object::armor(loc),
+            Loot::Ration => Object::ration(loc),
+        }
+    }
+}
+
+/// Weights of the items lying around at a depth
+///
+/// Healing potions are always the most common find. Offensive items and
+/// lanterns get more common deeper down, where the monsters are tougher and
+/// the caves darker.
+pub fn item_table(depth: i32) -> Vec<(Loot, i32)> {
+    let depth = cmp::max(depth, 1) - 1;
+    vec![
+        (Loot::HealingPotion, 40),
+        (Loot::LightningScroll, cmp::min(18 + 2 * depth, 30)),
+        (Loot::ConfusionScroll, cmp::min(14 + depth, 20)),
+        (Loot::PoisonGas, cmp::min(5 + 2 * depth, 15)),
+        (Loot::LightningWand, cmp::min(5 + depth, 10)),
+        (Loot::RecallScroll, 5),
+        (Loot::EnchantScroll, cmp::min(2 + depth, 6)),
+        (Loot::RemoveCurseScroll, 3),
+        (Loot::Candle, 3),
+        (Loot::Lantern, cmp::min(3 + depth, 8)),
+        (Loot::Sword, 2),
+        (Loot::Axe, 2),
+        (Loot::Dagger, 2),
+        (Loot::Shield, 3),
+        (Loot::Bow, 2),
+        (Loot::Arrows, 4),
+        (Loot::Armor, 3),
+        (Loot::Ration, 10),
+    ]
+}
+
+/// Create a new level with the algorithm of the layout
+pub fn generate(layout: &Layout, objects: &mut Objects, depth: i32) -> Map {
+    match layout.algorithm {
+        GenAlgorithm::Rooms => make_map(
+            objects,
+            layout.map_dimensions,
+            layout.room_dimensions,
+            layout.max_rooms,
+            layout.max_room_monsters,
+            layout.max_room_items,
+            depth,
+        ),
+        GenAlgorithm::Bsp => make_map_bsp(
+            objects,
+            layout.map_dimensions,
+            layout.room_dimensions,
+            layout.max_room_monsters,
+            layout.max_room_items,
+            depth,
+        ),
+    }
+}
+
 /// Create a new map
 pub fn make_map(
-    objects: &mut Vec<Object>,
+    objects: &mut Objects,
     map_dimension: Dimension,
     room_dimensions: Dimension,
     max_rooms: i32,
     max_room_monsters: i32,
     max_room_items: i32,
+    depth: i32,
 ) -> Map {
     // fill map with "unblocked" tiles
     let Dimension(width, height) = map_dimension;
-    let mut map = vec![vec![Tile::wall(); height as usize]; width as usize];
+    let mut map = Map::new(map_dimension, Tile::wall());
     let mut rooms: Vec<Rect> = vec![];
 
     let Dimension(min_room_size, max_room_size) = room_dimensions;
@@ -36,27 +239,9 @@ pub fn make_map(
 
         if !intersects {
             create_room(room, &mut map);
-
-            let (new_x, new_y) = room.center();
-            if rooms.is_empty() {
-                // put the player in the center of the first room
-                objects[PLAYER].loc = Location(new_x, new_y);
-            } else {
-                // populate with some monsters
-                place_objects(room, objects, max_room_monsters, max_room_items);
-                // connect to the previous room
-                let (prev_x, prev_y) = rooms[rooms.len() - 1].center();
-
-                // toss a coin
-                if rostlaube::rng::random() {
-                    // first move horizontally, then vertically
-                    create_h_tunnel(prev_x, new_x, prev_y, &mut map);
-                    create_v_tunnel(prev_y, new_y, new_x, &mut map);
-                } else {
-                    // first move vertically, then horizontally
-                    create_v_tunnel(prev_y, new_y, prev_x, &mut map);
-                    create_h_tunnel(prev_x, new_x, new_y, &mut map);
-                }
+            // connect to the previous room
+            if let Some(&previous) = rooms.last() {
+                connect(previous, room, &mut map);
             }
 
             // Add this room to the list
@@ -64,9 +249,118 @@ pub fn make_map(
         }
     }
 
+    populate(&rooms, objects, depth, max_room_monsters, max_room_items);
+    map
+}
+
+/// Create a new map by partitioning it into smaller and smaller parts
+///
+/// Every part that can't be split any further gets a room, and the rooms on
+/// either side of a split are joined with a tunnel.
+pub fn make_map_bsp(
+    objects: &mut Objects,
+    map_dimension: Dimension,
+    room_dimensions: Dimension,
+    max_room_monsters: i32,
+    max_room_items: i32,
+    depth: i32,
+) -> Map {
+    let Dimension(width, height) = map_dimension;
+    let mut map = Map::new(map_dimension, Tile::wall());
+    let mut rooms: Vec<Rect> = vec![];
+
+    let mut bsp = Bsp::new_with_size(0, 0, width, height);
+    // the largest room and its walls fit into every part
+    let Dimension(_, max_room_size) = room_dimensions;
+    split(&mut bsp, max_room_size + 1);
+    carve(&bsp, room_dimensions, &mut map, &mut rooms);
+
+    populate(&rooms, objects, depth, max_room_monsters, max_room_items);
     map
 }
 
+/// Split a part of the map in two at random until the parts get too small
+fn split(node: &mut Bsp, min_size: i32) {
+    // cut across the longer side
+    let horizontal = node.h > node.w;
+    let (start, size) = if horizontal {
+        (node.y, node.h)
+    } else {
+        (node.x, node.w)
+    };
+    if size < 2 * min_size {
+        return;
+    }
+    node.split_once(horizontal, start + rng::within(min_size, size - min_size));
+    for mut child in node.left().into_iter().chain(node.right()) {
+        split(&mut child, min_size);
+    }
+}
+
+/// Put a room into every leaf and connect the rooms across each split
+fn carve(node: &Bsp, room_dimensions: Dimension, map: &mut Map, rooms: &mut Vec<Rect>) {
+    match (node.left(), node.right()) {
+        (Some(left), Some(right)) => {
+            carve(&left, room_dimensions, map, rooms);
+            let last_left = rooms.last().copied();
+            let first_right = rooms.len();
+            carve(&right, room_dimensions, map, rooms);
+            if let (Some(a), Some(&b)) = (last_left, rooms.get(first_right)) {
+                connect(a, b, map);
+            }
+        }
+        _ => {
+            // the room's walls stay inside the leaf
+            let Dimension(min_room_size, max_room_size) = room_dimensions;
+            let w = rng::within(min_room_size, cmp::min(max_room_size, node.w - 1));
+            let h = rng::within(min_room_size, cmp::min(max_room_size, node.h - 1));
+            let x = node.x + rng::within(0, node.w - 1 - w);
+            let y = node.y + rng::within(0, node.h - 1 - h);
+            let room = Rect::new(x, y, w, h);
+            create_room(room, map);
+            rooms.push(room);
+        }
+    }
+}
+
+/// Put the player, the stairs and the inhabitants into the rooms of a level
+fn populate(
+    rooms: &[Rect],
+    objects: &mut Objects,
+    depth: i32,
+    max_room_monsters: i32,
+    max_room_items: i32,
+) {
+    // put the player in the center of the first room
+    if let Some(room) = rooms.first() {
+        let (x, y) = room.center();
+        objects[PLAYER].loc = Location(x, y);
+    }
+
+    // populate the others with some monsters
+    for &room in rooms.iter().skip(1) {
+        place_objects(room, objects, depth, max_room_monsters, max_room_items);
+    }
+
+    // the way down is in the last room
+    if let Some(room) = rooms.last() {
+        let (x, y) = room.center();
+        objects.insert(Object::stairs(Location(x, y)));
+    }
+
+    // the way back up is where the player arrives
+    objects.insert(Object::upstairs(objects[PLAYER].loc));
+
+    // Maybe someone lives down here
+    if rooms.len() > 1 && rng::chance(HERMIT_CHANCE) {
+        let (x, y) = rooms[rng::within(1, rooms.len() as i32 - 1) as usize].center();
+        let loc = Location(x, y);
+        if !game::object_blocks(&loc, objects) {
+            objects.insert(Object::hermit(loc));
+        }
+    }
+}
+
 /// A rectangle on the map, used to characterise a room
 #[derive(Clone, Copy, Debug)]
 struct Rect {
@@ -105,7 +399,7 @@ fn create_room(room: Rect, map: &mut Map) {
     // leave a one tile wide wall on the outside.
     for x in (room.x1 + 1)..room.x2 {
         for y in (room.y1 + 1)..room.y2 {
-            map[x as usize][y as usize] = Tile::empty();
+            map.set(&Location(x, y), Tile::empty());
         }
     }
 }
@@ -114,7 +408,24 @@ fn create_room(room: Rect, map: &mut Map) {
 fn create_h_tunnel(x1: i32, x2: i32, y: i32, map: &mut Map) {
     // horizontal tunnel. `min()` and `mac()` are used in case `x1 > x2`
     for x in cmp::min(x1, x2)..(cmp::max(x1, x2) + 1) {
-        map[x as usize][y as usize] = Tile::empty();
+        map.set(&Location(x, y), Tile::empty());
+    }
+}
+
+/// Join two rooms with a tunnel that turns once
+fn connect(from: Rect, to: Rect, map: &mut Map) {
+    let (prev_x, prev_y) = from.center();
+    let (new_x, new_y) = to.center();
+
+    // toss a coin
+    if rostlaube::rng::random() {
+        // first move horizontally, then vertically
+        create_h_tunnel(prev_x, new_x, prev_y, map);
+        create_v_tunnel(prev_y, new_y, new_x, map);
+    } else {
+        // first move vertically, then horizontally
+        create_v_tunnel(prev_y, new_y, prev_x, map);
+        create_h_tunnel(prev_x, new_x, new_y, map);
     }
 }
 
@@ -122,63 +433,215 @@ fn create_h_tunnel(x1: i32, x2: i32, y: i32, map: &mut Map) {
 fn create_v_tunnel(y1: i32, y2: i32, x: i32, map: &mut Map) {
     // vertical tunnel. `min()` and `max()` are used in case `y1 > y2`
     for y in cmp::min(y1, y2)..(cmp::max(y1, y2) + 1) {
-        map[x as usize][y as usize] = Tile::empty();
+        map.set(&Location(x, y), Tile::empty());
     }
 }
 
 // -------------------------------- Monsters ----------------------------------
 
-/// Return a random position inside a room
-fn loc_in_room(room: Rect) -> Location {
-    let x = rng::within(room.x1 + 1, room.x2 - 1);
-    let y = rng::within(room.y1 + 1, room.y2 - 1);
-    Location(x, y)
-}
-
-/// Create monster
-fn create_monster(room: Rect) -> Object {
-    let loc = loc_in_room(room);
-    let roll = rng::d100();
-    if roll < 50 {
-        game::Object::orc(loc)
-    } else if roll < 80 {
-        game::Object::troll(loc)
-    } else {
-        game::Object::ogre(loc)
-    }
+/// The unoccupied positions inside a room, in random order
+fn free_locs_in_room(room: Rect, objects: &Objects) -> Vec<Location> {
+    let mut locs: Vec<Location> = ((room.x1 + 1)..room.x2)
+        .flat_map(|x| ((room.y1 + 1)..room.y2).map(move |y| Location(x, y)))
+        .filter(|loc| objects.values().all(|o| o.loc != *loc))
+        .collect();
+    rng::shuffle(&mut locs);
+    locs
 }
 
-/// Create item
-fn create_item(room: Rect) -> Object {
-    let loc = loc_in_room(room);
-    let roll = rng::d100();
-    if roll < 50 {
-        game::Object::potion(loc, Item::Heal, "healing potion")
-    } else if roll < 75 {
-        game::Object::scroll(loc, Item::Lightning, "lightning bolt")
-    } else {
-        game::Object::scroll(loc, Item::Confusion, "confusion")
-    }
+/// Create a monster from the table of cave dwellers at the depth
+pub fn random_monster(depth: i32, loc: Location) -> Object {
+    let entries: Vec<Monster> = monster_table(depth)
+        .into_iter()
+        .flat_map(|(monster, weight)| std::iter::repeat_n(monster, weight as usize))
+        .collect();
+    rng::choose(&entries)
+        .copied()
+        .unwrap_or(Monster::Orc)
+        .spawn(loc)
+}
+
+/// Create an item from the table of things lying around at the depth
+///
+/// Gear and healing potions are sometimes cursed.
+pub fn random_item(depth: i32, loc: Location) -> Object {
+    let entries: Vec<Loot> = item_table(depth)
+        .into_iter()
+        .flat_map(|(loot, weight)| std::iter::repeat_n(loot, weight as usize))
+        .collect();
+    let mut item = rng::choose(&entries)
+        .copied()
+        .unwrap_or(Loot::HealingPotion)
+        .spawn(loc);
+    let cursable = item.equipment.is_some() || item.item == Some(Item::Heal);
+    item.cursed = cursable && rng::chance(CURSE_CHANCE);
+    item
 }
 
 /// Place some monsters in random locations in a room
+///
+/// Every object gets a tile of its own, so a crowded room gets fewer objects
+/// rather than objects on top of each other.
 fn place_objects(
     room: Rect,
-    objects: &mut Vec<Object>,
+    objects: &mut Objects,
+    depth: i32,
     max_room_monsters: i32,
     max_room_items: i32,
 ) {
+    // deeper levels are more crowded
+    let max_room_monsters = max_room_monsters + (depth - 1) / MONSTERS_PER_ROOM_INTERVAL;
+    let mut locs = free_locs_in_room(room, objects).into_iter();
     // choose a random number of monsters to place in this room
     for _ in 0..rng::within(0, max_room_monsters) {
-        let monster = create_monster(room);
-
-        // only place the monster, if the position isn't blocked yet
-        if !game::object_blocks(&monster.loc, objects) {
-            objects.push(monster);
+        if let Some(loc) = locs.next() {
+            objects.insert(random_monster(depth, loc));
         }
     }
     for _ in 0..rng::within(0, max_room_items) {
-        let item = create_item(room);
-        objects.push(item);
+        if let Some(loc) = locs.next() {
+            objects.insert(random_item(depth, loc));
+        }
+    }
+    // deeper levels are more treacherous
+    let trap_chance = TRAP_CHANCE + TRAP_CHANCE_PER_DEPTH * (depth - 1) as f32;
+    if rng::chance(trap_chance.min(TRAP_CHANCE_MAX)) {
+        if let Some(loc) = locs.next() {
+            objects.insert(create_trap(loc));
+        }
+    }
+}
+
+/// Create a hidden trap
+fn create_trap(loc: Location) -> Object {
+    let kind = if rng::chance(0.7) {
+        TrapKind::Spikes
+    } else {
+        TrapKind::Confusion
+    };
+    Object::trap(loc, kind)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rooms_without_inside_get_no_objects() {
+        let mut objects = Objects::new();
+        for room in [
+            Rect::new(3, 3, 1, 5),
+            Rect::new(3, 3, 5, 1),
+            Rect::new(3, 3, 0, 0),
+        ] {
+            assert!(free_locs_in_room(room, &objects).is_empty());
+            place_objects(room, &mut objects, 1, 5, 5);
+        }
+        assert!(objects.is_empty());
+        assert_eq!(
+            free_locs_in_room(Rect::new(3, 3, 2, 2), &objects),
+            vec![Location(4, 4)]
+        );
+    }
+
+    #[test]
+    fn crowded_rooms_never_stack_objects() {
+        let room = Rect::new(0, 0, 4, 4);
+        for _ in 0..20 {
+            let mut objects = Objects::new();
+            objects.insert(Object::player(Location(2, 2), "Tester"));
+            place_objects(room, &mut objects, 10, 20, 20);
+            let mut locs: Vec<Location> = objects.values().map(|o| o.loc).collect();
+            locs.sort_by_key(|&Location(x, y)| (x, y));
+            locs.dedup();
+            assert_eq!(locs.len(), objects.len());
+            assert!(objects.len() <= 9);
+        }
+    }
+
+    #[test]
+    fn ogres_live_deep_down() {
+        let share = |depth: i32, kind: Monster| {
+            let table = monster_table(depth);
+            let total: i32 = table.iter().map(|(_, w)| w).sum();
+            let weight: i32 = table
+                .iter()
+                .filter(|(m, _)| *m == kind)
+                .map(|(_, w)| w)
+                .sum();
+            weight as f32 / total as f32
+        };
+
+        assert!(share(1, Monster::Ogre) < 0.01);
+        assert!(share(1, Monster::Orc) > 0.5);
+        assert!(share(8, Monster::Ogre) > 0.3);
+        assert!(share(8, Monster::Orc) < share(8, Monster::Troll));
+    }
+
+    #[test]
+    fn healing_potions_are_the_most_common_loot() {
+        let weight = |depth: i32, kind: Loot| {
+            item_table(depth)
+                .iter()
+                .filter(|(l, _)| *l == kind)
+                .map(|(_, w)| *w)
+                .sum::<i32>()
+        };
+
+        for depth in 1..=20 {
+            let table = item_table(depth);
+            assert!(table.iter().all(|(_, w)| *w > 0));
+            assert!(table
+                .iter()
+                .all(|(_, w)| *w <= weight(depth, Loot::HealingPotion)));
+            assert!(weight(depth, Loot::ConfusionScroll) < weight(depth, Loot::HealingPotion));
+        }
+        assert!(weight(10, Loot::Lantern) > weight(1, Loot::Lantern));
+    }
+
+    #[test]
+    fn both_generators_build_connected_levels() {
+        for &algorithm in &[GenAlgorithm::Rooms, GenAlgorithm::Bsp] {
+            let layout = Layout {
+                map_dimensions: Dimension(60, 40),
+                room_dimensions: Dimension(6, 10),
+                max_rooms: 30,
+                max_room_monsters: 3,
+                max_room_items: 2,
+                algorithm,
+            };
+            for _ in 0..10 {
+                let mut objects = Objects::new();
+                objects.insert(Object::player(Location(0, 0), "Tester"));
+                let map = generate(&layout, &mut objects, 1);
+
+                let floor = |loc: &Location| map.get(loc).is_some_and(|t| !t.blocked);
+                let start = objects[PLAYER].loc;
+                assert!(floor(&start), "{:?}", algorithm);
+                let mut reached = vec![start];
+                let mut frontier = vec![start];
+                while let Some(Location(x, y)) = frontier.pop() {
+                    for next in [
+                        Location(x + 1, y),
+                        Location(x - 1, y),
+                        Location(x, y + 1),
+                        Location(x, y - 1),
+                    ] {
+                        if floor(&next) && !reached.contains(&next) {
+                            reached.push(next);
+                            frontier.push(next);
+                        }
+                    }
+                }
+
+                let floors = map.iter_coords().filter(|loc| floor(loc)).count();
+                assert_eq!(reached.len(), floors, "{:?}", algorithm);
+                for object in objects.values() {
+                    assert!(reached.contains(&object.loc), "{:?}", algorithm);
+                }
+                assert!(objects.values().any(|o| o.stairs));
+                assert!(objects.values().any(|o| o.upstairs && o.loc == start));
+            }
+        }
     }
 }
@@ -0,0 +1,110 @@
+//! Bounded record of what happened in the game
+use std::collections::VecDeque;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, Write};
+use std::path::Path;
+
+/// Maximum number of entries kept in the log
+pub const EVENT_LOG_CAPACITY: usize = 1000;
+
+/// What kind of thing happened
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EntryKind {
+    Action,
+    Transition,
+    Event,
+}
+
+/// A single entry of the log
+#[derive(Debug, Clone)]
+pub struct Entry {
+    pub turn: i32,
+    pub kind: EntryKind,
+    pub text: String,
+}
+
+impl fmt::Display for Entry {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "[{:>5}] {:<10} {}",
+            self.turn,
+            format!("{:?}", self.kind),
+            self.text
+        )
+    }
+}
+
+/// Ring buffer of the most recent entries
+#[derive(Debug)]
+pub struct EventLog {
+    entries: VecDeque<Entry>,
+    capacity: usize,
+}
+
+impl EventLog {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            entries: VecDeque::with_capacity(capacity),
+            capacity,
+        }
+    }
+
+    /// Record an entry, dropping the oldest one if the log is full
+    pub fn record<T: Into<String>>(&mut self, turn: i32, kind: EntryKind, text: T) {
+        if self.capacity == 0 {
+            return;
+        }
+        if self.entries.len() == self.capacity {
+            self.entries.pop_front();
+        }
+        self.entries.push_back(Entry {
+            turn,
+            kind,
+            text: text.into(),
+        });
+    }
+
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = &Entry> {
+        self.entries.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Write all entries to a file, one per line
+    pub fn write_to<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let mut file = File::create(path)?;
+        for entry in self.iter() {
+            writeln!(file, "{}", entry)?;
+        }
+        Ok(())
+    }
+}
+
+impl Default for EventLog {
+    fn default() -> Self {
+        Self::new(EVENT_LOG_CAPACITY)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn oldest_entries_are_dropped() {
+        let mut log = EventLog::new(3);
+        for turn in 0..5 {
+            log.record(turn, EntryKind::Action, format!("action {}", turn));
+        }
+        let turns: Vec<i32> = log.iter().map(|e| e.turn).collect();
+        assert_eq!(turns, vec![2, 3, 4]);
+    }
+}
@@ -1,12 +1,26 @@
 use std::cmp;
+use std::collections::{HashMap, VecDeque};
+use std::fs::File;
+use std::hash::{Hash, Hasher};
+use std::io::{self, BufReader, BufWriter};
+use std::path::Path;
 
-use crate::ai::Ai;
+use serde::{Deserialize, Serialize};
+
+use crate::ai::{self, Ai};
+use crate::dialogue::{self, Dialogue};
+use crate::event_log::{EntryKind, EventLog};
+use crate::hooks::TurnHook;
+use crate::keys::KeyBindings;
+use crate::objects::{Id, Objects};
+use crate::sound::{Silence, SoundEvent, SoundKind, SoundSink};
+use crate::spatial::SpatialIndex;
 use crate::ui::{self, Bar};
-use crate::{colors, Color, FovAlgorithm, FovMap};
+use crate::{colors, AStar, Color, FovAlgorithm, FovMap};
+use crate::{console, BackgroundFlag, Console, Offscreen, TextAlignment};
 use crate::{dungeon, rng, Dimension, Direction, Location, PLAYER};
-use crate::{BackgroundFlag, Console, Offscreen, TextAlignment};
 
-/// Field of view algorithm
+/// Default field of view algorithm
 const FOV_ALGO: FovAlgorithm = FovAlgorithm::Basic;
 /// FOV lights walls or not
 const FOV_LIGHT_WALLS: bool = true;
@@ -18,23 +32,129 @@ const HEAL_AMOUNT: i32 = 10;
 const LIGHTNING_RANGE: i32 = 3;
 /// Damage of the lightning bolt scroll
 const LIGHTNING_DAMAGE: i32 = 10;
+/// Number of frames the flash of a lightning bolt is shown
+const LIGHTNING_FRAMES: i32 = 12;
 /// Range of the consuse scroll
 const CONFUSE_RANGE: i32 = 5;
 /// The number of turns a monster is confused
 const CONFUSE_NUM_TURNS: i32 = 5;
+/// Number of turns the poison of a bite lasts
+const POISON_NUM_TURNS: i32 = 5;
+/// Damage dealt by poison each turn
+const POISON_DAMAGE: i32 = 1;
+/// How far a bow shoots
+const BOW_RANGE: i32 = 8;
+/// Chance that a fired arrow can be picked up again where it landed
+const ARROW_RECOVERY_CHANCE: f32 = 0.5;
+/// What a battle axe deals, before armor
+const AXE_DAMAGE: rng::Dice = rng::Dice {
+    n: 2,
+    x: 4,
+    modifier: 0,
+};
+/// How far a goblin archer shoots
+const ARCHER_RANGE: i32 = 6;
+/// How far the noise of a fight carries
+const COMBAT_NOISE_RADIUS: i32 = 8;
+/// How far a monster's shout carries
+const BARK_NOISE_RADIUS: i32 = 12;
+/// Range of monster spells
+pub const SPELL_RANGE: i32 = 6;
+/// Number of turns a monster has to wait between spells
+pub const SPELL_COOLDOWN: i32 = 5;
+/// Damage of the magic bolt spell
+const MAGIC_BOLT_DAMAGE: i32 = 4;
+/// Number of full turns until the remains of a monster rot away
+pub const CORPSE_LIFETIME: i32 = 100;
+/// Health restored by eating remains
+const CORPSE_HEAL_AMOUNT: i32 = 4;
+/// Chance that the remains have gone bad
+const CORPSE_POISON_CHANCE: f32 = 0.25;
+/// Damage dealt by bad remains
+const CORPSE_POISON_DAMAGE: i32 = 3;
+/// Hunger stilled by eating remains
+const CORPSE_NUTRITION: i32 = 300;
+/// Number of full turns a well fed player can go without food
+const HUNGER_MAX: i32 = 1500;
+/// Food left when the player starts feeling hungry
+const HUNGER_HUNGRY: i32 = 300;
+/// Food left when the player starts starving
+const HUNGER_STARVING: i32 = 100;
+/// Damage dealt by starvation each turn
+const STARVATION_DAMAGE: i32 = 1;
+/// Hunger stilled by a ration
+const RATION_NUTRITION: i32 = 1000;
+/// Most turns the player rests in one go
+const REST_MAX_TURNS: i32 = 200;
+/// Most steps the player explores in one go
+const EXPLORE_MAX_STEPS: i32 = 500;
+/// Where the game is saved to
+pub const SAVE_FILE: &str = "savegame.json";
+/// Longest path a monster will follow to reach its target
+const PATH_MAX_LENGTH: i32 = 25;
+/// Damage dealt by a spike trap
+const TRAP_DAMAGE: i32 = 5;
+/// Experience needed for the first level up
+const LEVEL_UP_BASE: i32 = 200;
+/// Additional experience needed for each further level up
+const LEVEL_UP_FACTOR: i32 = 150;
+/// Health gained by choosing constitution on level up
+const LEVEL_UP_HEALTH: i32 = 20;
+/// Number of tiles a lantern adds to the light radius
+const LANTERN_BONUS: i32 = 3;
+/// Light radius while burning a candle
+const CANDLE_RADIUS: i32 = 4;
+/// Number of levels until the light radius shrinks by one tile
+const DARKNESS_INTERVAL: i32 = 2;
+/// The light radius does not shrink below this, however deep the player goes
+const MIN_LIGHT_RADIUS: i32 = 4;
+/// Number of turns a candle burns
+const CANDLE_DURATION: i32 = 20;
+/// Range at which a flask of poison gas can be thrown
+const GAS_RANGE: i32 = 5;
+/// Radius of a cloud of poison gas
+const GAS_RADIUS: i32 = 1;
+/// Number of turns a cloud of poison gas lingers
+const GAS_DURATION: i32 = 5;
+/// Damage dealt by poison gas to each fighter inside it per turn
+const GAS_DAMAGE: i32 = 2;
+/// Number of turns until word of recall takes effect
+const RECALL_DELAY: i32 = 5;
+/// Damage a cursed healing potion deals instead of healing
+const CURSED_POTION_DAMAGE: i32 = 6;
+/// Share of the power an off-hand weapon strikes with, in percent
+const OFF_HAND_POWER: i32 = 50;
+/// Highest enchantment a piece of gear can be raised to
+const ENCHANT_CAP: i32 = 3;
+/// Chance of an ambient message on a quiet turn
+const AMBIENT_CHANCE: f32 = 0.05;
+/// Minimum number of turns between two ambient messages
+const AMBIENT_COOLDOWN: i32 = 30;
+/// Number of turns between checks for a wandering monster
+const WANDER_INTERVAL: i32 = 50;
+/// Chance that a monster wanders in when checked
+const WANDER_CHANCE: f32 = 0.3;
+/// Sounds and sights of the caves
+const AMBIENT_MESSAGES: &[&str] = &[
+    "Water drips somewhere nearby.",
+    "You hear scuttling.",
+    "A cold draft brushes past you.",
+    "Something creaks in the distance.",
+    "The walls groan under the weight of the earth.",
+    "Flakes of rust drift down from the ceiling.",
+];
+/// How far dropped items spill over when their tile is taken
+const SPILL_RADIUS: i32 = 3;
+/// Default number of items the player can carry, one per menu letter
+pub const INVENTORY_CAPACITY: usize = 26;
+
+/// How far the player can get from the center of the view before it scrolls
+const CAMERA_DEAD_ZONE: Dimension = Dimension(8, 5);
+
+/// Number of levels it takes for the caves to change from one theme to the
+/// next
+const THEME_DEPTHS: i32 = 4;
 
-/// Color used for unexplored areas
-const COLOR_UNEXPLORED: Color = colors::BLACK;
-/// Color used for dark walls
-const COLOR_DARK_WALL: Color = colors::DARKEST_GREY;
-/// Color used for light walls
-const COLOR_LIGHT_WALL: Color = colors::DARKER_GREY;
-/// Color used for dark ground
-const COLOR_DARK_GROUND: Color = colors::DARKER_GREY;
-/// Color used for light ground
-const COLOR_LIGHT_GROUND: Color = colors::DARK_GREY;
-
-pub type Map = Vec<Vec<Tile>>;
 pub type Turn = Vec<Action>;
 pub type Message = (String, Color);
 pub type Inventory = Vec<Object>;
@@ -42,16 +162,114 @@ pub type Inventory = Vec<Object>;
 /// Struct for tracking the game state
 ///
 /// The game contains the `Map` and all objects.
+///
+/// State that can be rebuilt from the rest, like the field of view, and the
+/// sinks and hooks registered by the caller are not saved.
+#[derive(Serialize, Deserialize)]
 pub struct Game {
     pub map: Map,
-    pub objects: Vec<Object>,
+    pub objects: Objects,
     pub turn: i32,
     pub turns: Vec<(Turn, Turn)>,
     pub messages: Messages,
     pub inventory: Inventory,
+    pub inventory_capacity: usize,
+    #[serde(skip, default = "empty_fov")]
     pub fov: FovMap,
-    pub map_dimensions: Dimension,
     pub player_turn: Turn,
+    /// Area of the map covered by the last visibility update
+    pub fov_bounds: (Location, Location),
+    /// Debug commands are available
+    pub wizard: bool,
+    /// The player cannot die
+    pub godmode: bool,
+    /// Visible tiles fade out towards the edge of the torch light
+    pub light_falloff: bool,
+    /// How the field of view is computed
+    #[serde(skip, default = "default_fov_algorithm")]
+    pub fov_algorithm: FovAlgorithm,
+    /// Tall creatures block the view like walls
+    pub creatures_block_sight: bool,
+    /// Locations that creatures made opaque in the last visibility update
+    #[serde(skip)]
+    sight_blockers: Vec<Location>,
+    /// What the keys do, not saved with the game
+    #[serde(skip)]
+    pub key_bindings: KeyBindings,
+    pub stats: Statistics,
+    /// Number of full turns until remains rot away, -1 keeps them forever
+    pub corpse_lifetime: i32,
+    /// Ask before the player steps onto a trap they know about
+    pub confirm_traps: bool,
+    /// Where the player entered the caves
+    pub entrance: Location,
+    /// Number of turns until the player is recalled to the entrance
+    pub recall: Option<i32>,
+    /// Record of actions, transitions and events for debugging
+    #[serde(skip)]
+    pub event_log: EventLog,
+    /// Objects by location, kept in sync with `objects`
+    #[serde(skip)]
+    index: SpatialIndex,
+    /// Occasional flavor messages on quiet turns
+    pub ambient: bool,
+    /// Turn of the last ambient message
+    pub last_ambient: i32,
+    /// Receiver of the sounds made in the game
+    #[serde(skip, default = "silence")]
+    sound: Box<dyn SoundSink>,
+    /// Number of turns between checks for a wandering monster, 0 disables them
+    pub wander_interval: i32,
+    /// Chance that a monster wanders in when checked
+    pub wander_chance: f32,
+    /// Monsters stand still, for inspecting the layout of a level
+    pub peaceful: bool,
+    /// Custom logic run at fixed points of every turn
+    #[serde(skip)]
+    hooks: Vec<Box<dyn TurnHook>>,
+    /// The player's last action that can be repeated
+    pub last_action: Option<Action>,
+    /// How far the player's torch lights up the caves
+    pub torch_radius: i32,
+    /// Number of turns the player's light is reduced to a candle
+    pub candle: i32,
+    /// Lingering hazards on the map
+    pub effects: Vec<AreaEffect>,
+    /// Effects queued during play, drawn before the next key press
+    #[serde(skip)]
+    pub animations: Vec<Animation>,
+    /// How many levels deep the player is, starting at 1
+    pub depth: i32,
+    /// The levels the player left, by depth starting at 1
+    pub levels: Vec<Option<Level>>,
+    /// The player made it out of the caves alive
+    pub escaped: bool,
+    /// The player wants to start over once this game is over
+    #[serde(skip)]
+    pub restart: bool,
+    /// Cell of the screen under the mouse pointer
+    #[serde(skip)]
+    pub pointer: Option<Location>,
+    /// How tough the monsters are
+    pub difficulty: Difficulty,
+    /// Number of messages before the player's last action, the alert line
+    /// only shows what came after
+    #[serde(skip)]
+    alert_mark: usize,
+    /// Where the view is centered, trails the player within a dead zone
+    pub camera: Location,
+    /// How new levels are generated
+    pub layout: dungeon::Layout,
+}
+
+/// A level the player left, kept for when they come back
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Level {
+    pub map: Map,
+    /// Everything on the level, except for the player
+    pub objects: Vec<Object>,
+    pub effects: Vec<AreaEffect>,
+    pub entrance: Location,
 }
 
 impl std::fmt::Debug for Game {
@@ -63,41 +281,99 @@ impl std::fmt::Debug for Game {
              player: {:?} \
              inventory: {:?} \
              ",
-            self.turn, self.objects[0], self.inventory,
+            self.turn, self.objects[PLAYER], self.inventory,
         )
     }
 }
 
+/// Placeholder until the map of a loaded game is known
+fn empty_fov() -> FovMap {
+    FovMap::new(1, 1)
+}
+
+fn default_fov_algorithm() -> FovAlgorithm {
+    FOV_ALGO
+}
+
+fn silence() -> Box<dyn SoundSink> {
+    Box::new(Silence)
+}
+
 impl Game {
+    /// A game on the given depth, with its first level laid out
+    ///
+    /// The difficulty is set for the whole game: it makes the monsters of
+    /// this level and of all levels to come tougher or weaker.
     pub fn new(
         player_name: &str,
-        map_dimensions: Dimension,
-        room_dimensions: Dimension,
-        max_rooms: i32,
-        max_room_monsters: i32,
-        max_room_items: i32,
+        layout: dungeon::Layout,
+        depth: i32,
+        difficulty: Difficulty,
     ) -> Self {
-        let player = Object::player(Location(0, 0), player_name);
-        let mut objects = vec![player];
-        let Dimension(map_width, map_height) = map_dimensions;
+        assert!(depth >= 1, "The caves start at depth 1");
+        let mut player = Object::player(Location(0, 0), player_name);
+        if let Some(fighter) = player.fighter.as_mut() {
+            fighter.health_regen *= difficulty.player_regen();
+        }
+        let mut objects = Objects::new();
+        objects.insert(player);
+        let map = dungeon::generate(&layout, &mut objects, depth);
+        for (id, object) in objects.iter_mut() {
+            if id != PLAYER {
+                difficulty.adjust(object);
+            }
+        }
+        let Dimension(map_width, map_height) = layout.map_dimensions;
         let mut game = Game {
-            map: dungeon::make_map(
-                &mut objects,
-                map_dimensions,
-                room_dimensions,
-                max_rooms,
-                max_room_monsters,
-                max_room_items,
-            ),
-            objects: objects,
+            map,
+            objects,
             turn: 0,
             turns: vec![],
             messages: Messages::empty(),
             inventory: vec![],
+            inventory_capacity: INVENTORY_CAPACITY,
             fov: FovMap::new(map_width, map_height),
-            map_dimensions: map_dimensions,
             player_turn: vec![],
+            fov_bounds: (Location(0, 0), Location(map_width - 1, map_height - 1)),
+            wizard: false,
+            godmode: false,
+            light_falloff: true,
+            fov_algorithm: FOV_ALGO,
+            creatures_block_sight: false,
+            sight_blockers: vec![],
+            key_bindings: Default::default(),
+            stats: Default::default(),
+            corpse_lifetime: CORPSE_LIFETIME,
+            confirm_traps: true,
+            entrance: Location(0, 0),
+            recall: None,
+            event_log: Default::default(),
+            index: Default::default(),
+            ambient: true,
+            last_ambient: 0,
+            sound: Box::new(Silence),
+            wander_interval: WANDER_INTERVAL,
+            wander_chance: WANDER_CHANCE,
+            peaceful: false,
+            hooks: vec![],
+            last_action: None,
+            torch_radius: TORCH_RADIUS,
+            candle: 0,
+            effects: vec![],
+            animations: vec![],
+            depth,
+            levels: vec![],
+            escaped: false,
+            restart: false,
+            pointer: None,
+            difficulty,
+            alert_mark: 0,
+            camera: Location(0, 0),
+            layout,
         };
+        game.reindex();
+        game.entrance = game.objects[PLAYER].loc;
+        game.camera = game.entrance;
         game.init_fov();
         game.refresh();
 
@@ -109,65 +385,200 @@ impl Game {
         game
     }
 
+    /// A game with every level down to `depth_count` laid out up front
+    ///
+    /// Nothing the player does changes how the other levels look, so
+    /// everybody playing the same seed and settings finds the same caves.
+    pub fn new_full(
+        seed: u64,
+        player_name: &str,
+        layout: dungeon::Layout,
+        depth: i32,
+        difficulty: Difficulty,
+        depth_count: i32,
+    ) -> Self {
+        rng::seed(seed);
+        let mut game = Game::new(player_name, layout, depth, difficulty);
+        game.levels = (1..=depth_count)
+            .map(|d| match d {
+                d if d == depth => None,
+                d => Some(game.build_level(d)),
+            })
+            .collect();
+        game
+    }
+
+    /// A game with repeatable dice and the default dungeon layout
+    ///
+    /// Nothing here touches the window, so the game can be driven through
+    /// `update`, `play` and `ai_turns` in tests and simulations.
+    pub fn new_headless(seed: u64, map_dimensions: Dimension) -> Self {
+        rng::seed(seed);
+        let layout = dungeon::Layout::new(
+            map_dimensions,
+            Dimension(crate::ROOM_MIN_SIZE, crate::ROOM_MAX_SIZE),
+            crate::MAX_ROOMS,
+            crate::MAX_ROOM_MONSTERS,
+            crate::MAX_ROOM_ITEMS,
+        );
+        Game::new("Tester", layout, 1, Difficulty::Normal)
+    }
+
+    /// Write the game to a file
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let file = File::create(path)?;
+        serde_json::to_writer(BufWriter::new(file), self)?;
+        Ok(())
+    }
+
+    /// Read a game from a file written by `save`
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let file = File::open(path)?;
+        let mut game: Game = serde_json::from_reader(BufReader::new(file))?;
+        game.reindex();
+        game.init_fov();
+        game.refresh();
+        Ok(game)
+    }
+
     pub fn turn(&mut self, player: Turn, ai: Turn) {
         self.turns.push((player, ai));
         self.turn += 1;
         self.player_turn.clear();
     }
 
-    pub fn play(&mut self, turn: &Turn) {
+    /// Resolve the actions and report what happened for each of them
+    pub fn play(&mut self, turn: &Turn) -> Vec<ActionOutcome> {
+        let mut outcomes = vec![];
         for action in turn {
-            let msgs = match *action {
-                Action::Move(id, direction) => {
-                    move_object(id, direction, &self.map, &mut self.objects)
+            self.event_log
+                .record(self.turn, EntryKind::Action, format!("{:?}", action));
+            let outcome = match *action {
+                Action::Move(id, direction) => move_object(id, direction, self),
+                Action::Attack(id, target) => melee(id, target, self),
+                Action::AttackDirection(id, direction) => attack_direction(id, direction, self),
+                Action::PickUp(id, target) => pickup_item(id, target, self).into(),
+                Action::Bark(id) => bark(id, &self.objects).into(),
+                Action::Mumble(id) => mumble(id, &self.objects).into(),
+                Action::Panic(id) => panic(id, &self.objects).into(),
+                Action::Wait(_) => Messages::empty().into(),
+                Action::UseItem(id, item) => use_item(id, item, None, self).into(),
+                Action::UseItemOn(id, item, target) => use_item(id, item, target, self).into(),
+                Action::Drop(id, item) => drop_item(id, item, self).into(),
+                Action::Enchant(id, scroll, gear) => enchant(id, scroll, gear, self).into(),
+                Action::Shoot(id, target) => shoot(id, target, self),
+                Action::Equip(id, item) => equip(id, item, self).into(),
+                Action::Unequip(id, item) => unequip(id, item, self).into(),
+                Action::Cast(id, target, spell) => cast_spell(id, target, spell, self),
+                Action::Descend(id) => take_stairs(id, self).into(),
+                Action::Ascend(id) => climb_stairs(id, self).into(),
+                Action::Save => match self.save(SAVE_FILE) {
+                    Ok(()) => Messages::new(format!("Game saved to {}.", SAVE_FILE), colors::WHITE),
+                    Err(e) => Messages::new(format!("Could not save the game: {}", e), colors::RED),
                 }
-                Action::Attack(id, target) => attack(id, target, &mut self.objects),
-                Action::PickUp(id, target) => {
-                    pickup_item(id, target, &mut self.objects, &mut self.inventory)
+                .into(),
+                _ => Messages::empty().into(),
+            };
+            // Fighting and shouting carry through the caves
+            let noise = match *action {
+                Action::Attack(id, _) | Action::AttackDirection(id, _) => {
+                    self.objects.get(id).map(|o| (o.loc, COMBAT_NOISE_RADIUS))
                 }
-                Action::Bark(id) => bark(id, &self.objects),
-                Action::Mumble(id) => mumble(id, &self.objects),
-                Action::Wait(_) => Messages::empty(),
-                Action::UseItem(id, item) => use_item(id, item, self),
-                _ => Messages::empty(),
+                Action::Bark(id) => self.objects.get(id).map(|o| (o.loc, BARK_NOISE_RADIUS)),
+                Action::Shoot(_, target) => Some((target, COMBAT_NOISE_RADIUS)),
+                _ => None,
             };
-            self.messages.append(msgs);
+            if let Some((origin, radius)) = noise {
+                propagate_noise(origin, radius, self);
+            }
+            for (msg, color) in outcome.messages.iter() {
+                self.messages.add_counted(msg.as_str(), *color);
+            }
+            outcomes.push(outcome);
         }
+        outcomes
     }
 
     /// Monster turn
     pub fn ai_turns(&mut self) -> Turn {
         let mut actions = vec![];
-        for id in PLAYER + 1..self.objects.len() {
-            self.objects[id].ai.take().map(|ai| {
-                let (mut turn, new_ai) = ai.turn(id, self);
+        if self.peaceful {
+            return actions;
+        }
+        for id in self.objects.ids().into_iter().filter(|&id| id != PLAYER) {
+            if let Some(ai) = self.objects[id].ai.take() {
+                let (mut turn, new_ai) = if self.objects[id].is_confused() {
+                    (ai::stumble(id), ai)
+                } else {
+                    ai.turn(id, self)
+                };
                 actions.append(&mut turn);
                 self.objects[id].ai = Some(new_ai);
-            });
+            }
         }
         actions
     }
 
+    /// Switch to another field of view algorithm and look around again
+    pub fn set_fov_algorithm(&mut self, algorithm: FovAlgorithm) {
+        self.fov_algorithm = algorithm;
+        self.refresh();
+    }
+
     pub fn refresh(&mut self) {
+        self.follow_player();
         self.update_fov();
         self.update_map();
         self.update_objects(false);
     }
 
     pub fn rollover(&mut self, player: Turn, ai: Turn) {
+        if let Some(turns) = self.recall.take() {
+            let msgs = self.countdown_recall(turns - 1);
+            self.messages.append(msgs);
+        }
+        let effects = self.tick_effects();
+        self.messages.append(effects);
+        if self.candle > 0 {
+            self.candle -= 1;
+            if self.candle == 0 {
+                self.messages.add(
+                    "The candle burns out and your torch flares up.",
+                    colors::WHITE,
+                );
+            }
+        }
         self.update_fov();
         self.update_map();
         self.update_objects(true);
+        let ambient = self.ambient_tick();
+        self.messages.append(ambient);
+        self.wander_tick();
         self.turn(player, ai);
     }
 
+    /// Update the visibility of the tiles around the player
+    ///
+    /// Only tiles within the torch radius can change visibility, so the
+    /// update is restricted to the bounding box of the current field of
+    /// view combined with that of the previous update, which clears the
+    /// tiles that went out of view.
     fn update_map(&mut self) -> Messages {
-        let Dimension(width, height) = self.map_dimensions;
-        for y in 0..height {
-            for x in 0..width {
+        let bounds = self.current_fov_bounds();
+        let (Location(x_min, y_min), Location(x_max, y_max)) = bounds;
+        let (Location(prev_x_min, prev_y_min), Location(prev_x_max, prev_y_max)) = self.fov_bounds;
+
+        for y in cmp::min(y_min, prev_y_min)..=cmp::max(y_max, prev_y_max) {
+            for x in cmp::min(x_min, prev_x_min)..=cmp::max(x_max, prev_x_max) {
                 let visible = self.visible(&Location(x, y));
-                let tile = &mut self.map[x as usize][y as usize];
+                let tile = match self.map.get_mut(&Location(x, y)) {
+                    Some(tile) => tile,
+                    None => continue,
+                };
                 if visible {
+                    if !tile.explored {
+                        self.stats.tiles_explored += 1;
+                    }
                     tile.explored = true;
                     tile.visible = true;
                 } else {
@@ -175,13 +586,29 @@ impl Game {
                 }
             }
         }
+        self.fov_bounds = bounds;
         Messages::empty()
     }
 
+    /// Bounding box of the field of view, clamped to the map edges
+    fn current_fov_bounds(&self) -> (Location, Location) {
+        let Location(x, y) = self.objects[PLAYER].loc;
+        let Dimension(width, height) = self.map.dimensions();
+        let radius = self.light_radius();
+        (
+            Location(cmp::max(x - radius, 0), cmp::max(y - radius, 0)),
+            Location(
+                cmp::min(x + radius, width - 1),
+                cmp::min(y + radius, height - 1),
+            ),
+        )
+    }
+
     fn update_objects(&mut self, full_turn: bool) {
         let mut messages = Messages::empty();
-        for id in 0..self.objects.len() {
-            if self.visible(&self.objects[id].loc) {
+        for id in self.objects.ids() {
+            let hidden = self.objects[id].trap.is_some_and(|t| t.hidden);
+            if self.visible(&self.objects[id].loc) && !hidden {
                 self.objects[id].visible = true;
                 if !self.objects[id].seen {
                     messages.add(
@@ -194,533 +621,2928 @@ impl Game {
                 self.objects[id].visible = false;
             }
 
-            self.objects[id].fighter.map(|fighter| {
-                if fighter.health <= 0 {
-                    let death_messages = fighter.on_death.call(&mut self.objects[id]);
-                    messages.append(death_messages);
+            if full_turn && self.objects[id].alive {
+                messages.append(self.tick_status(id));
+                messages.append(self.tick_hunger(id));
+            }
+
+            if id == PLAYER && self.godmode {
+                if let Some(fighter) = self.objects[id].fighter.as_mut() {
+                    fighter.health = cmp::max(fighter.health, 1);
                 }
-            });
+            }
 
-            if full_turn && self.objects[id].alive {
+            let dead = self.objects[id].fighter.is_some_and(|f| !f.is_alive());
+            if dead && self.objects[id].alive {
+                messages.append(self.die(id));
+            }
+
+            // The starving don't heal
+            if full_turn && self.objects[id].alive && self.objects[id].hunger != Some(0) {
                 let _ = regenerate(&mut self.objects[id]);
             }
+
+            if full_turn {
+                if let Some(decay) = self.objects[id].decay.as_mut() {
+                    *decay -= 1;
+                    if *decay <= 0 && self.objects[id].visible {
+                        messages.add(
+                            format!("{} rot away.", direct(&self.objects[id].name, true)),
+                            colors::WHITE,
+                        );
+                    }
+                }
+            }
+        }
+        let count = self.objects.len();
+        self.objects
+            .retain(|_, o| o.decay.is_none_or(|turns| turns > 0));
+        if self.objects.len() != count {
+            self.reindex();
         }
         self.messages.append(messages)
     }
 
-    fn init_fov(&mut self) {
-        let Dimension(width, height) = self.map_dimensions;
-        for x in 0..width {
-            for y in 0..height {
-                self.fov.set(
-                    x,
-                    y,
-                    !self.map[x as usize][y as usize].block_sight,
-                    !self.map[x as usize][y as usize].blocked,
-                )
+    /// Describe the surroundings once in a while when nothing else is going on
+    pub fn ambient_tick(&mut self) -> Messages {
+        let quiet = self
+            .visible_fighters_by_distance(PLAYER, self.light_radius())
+            .is_empty();
+        let rested = self.turn - self.last_ambient >= AMBIENT_COOLDOWN;
+        if self.ambient && quiet && rested && rng::chance(AMBIENT_CHANCE) {
+            self.last_ambient = self.turn;
+            rng::choose(AMBIENT_MESSAGES)
+                .map(|msg| Messages::new(*msg, colors::GREY))
+                .unwrap_or_else(Messages::empty)
+        } else {
+            Messages::empty()
+        }
+    }
+
+    /// Hurt every fighter inside an area effect and let the effects fade
+    fn tick_effects(&mut self) -> Messages {
+        let mut messages = Messages::empty();
+        for effect in self.effects.iter_mut() {
+            for (id, object) in self.objects.iter_mut() {
+                if !object.alive || !effect.covers(&object.loc) {
+                    continue;
+                }
+                if let Some(fighter) = object.fighter.as_mut() {
+                    fighter.take_damage(effect.damage);
+                    if id == PLAYER {
+                        self.stats.damage_taken += effect.damage;
+                        messages.add(format!("You choke in the {}.", effect.name), effect.color);
+                    } else if object.visible {
+                        messages.add(
+                            format!(
+                                "{} chokes in the {}.",
+                                direct(&object.name, true),
+                                effect.name
+                            ),
+                            effect.color,
+                        );
+                    }
+                }
+            }
+            effect.turns -= 1;
+            if effect.turns <= 0 {
+                messages.add(format!("The {} dissipates.", effect.name), colors::WHITE);
             }
         }
+        self.effects.retain(|e| e.turns > 0);
+        messages
     }
 
-    fn update_fov(&mut self) -> Messages {
-        let Location(x, y) = self.objects[PLAYER].loc;
-        self.fov
-            .compute_fov(x, y, TORCH_RADIUS, FOV_LIGHT_WALLS, FOV_ALGO);
-        Messages::empty()
+    /// Let a monster wander in every so often while the player lingers
+    fn wander_tick(&mut self) {
+        let due = self.wander_interval > 0 && self.turn % self.wander_interval == 0;
+        if due && rng::chance(self.wander_chance) {
+            self.spawn_wanderer();
+        }
     }
 
-    pub fn visible(&self, loc: &Location) -> bool {
-        let Location(x, y) = *loc;
-        self.fov.is_in_fov(x, y)
+    /// Spawn a monster on a free floor tile out of view of the player
+    fn spawn_wanderer(&mut self) -> Option<Id> {
+        let Dimension(width, height) = self.map.dimensions();
+        let hidden: Vec<Location> = (0..width)
+            .flat_map(|x| (0..height).map(move |y| Location(x, y)))
+            .filter(|loc| !self.visible(loc) && !self.is_blocked(loc))
+            .collect();
+        rng::choose(&hidden).copied().map(|loc| {
+            let mut monster = dungeon::random_monster(self.depth, loc);
+            self.difficulty.adjust(&mut monster);
+            let id = self.spawn(monster);
+            self.event_log.record(
+                self.turn,
+                EntryKind::Event,
+                format!(
+                    "{} ({}) wandered in at {:?}",
+                    self.objects[id].name, id, loc
+                ),
+            );
+            id
+        })
     }
 
-    pub fn render_game_world(&self, con: &mut Offscreen) {
-        let focus = &self.objects[PLAYER].loc;
-
-        let source = &self.map_dimensions;
-        let target = &Dimension(con.width(), con.height());
-
-        let Dimension(map_width, map_height) = self.map_dimensions;
-        for y_map in 0..map_height {
-            for x_map in 0..map_width {
-                let loc = &Location(x_map, y_map);
-                let view_loc = rostlaube::geometry::translate(source, target, loc, focus);
-                if let Some(Location(x, y)) = view_loc {
-                    let tile = &self.map[x_map as usize][y_map as usize];
-                    let (color, char) = match (tile.explored, tile.visible, tile) {
-                        (
-                            true,
-                            true,
-                            Tile {
-                                blocked: true,
-                                char: c,
-                                ..
-                            },
-                        ) => (COLOR_LIGHT_WALL, Some(c)),
-                        (true, false, Tile { blocked: true, .. }) => (COLOR_DARK_WALL, None),
-                        (
-                            true,
-                            true,
-                            Tile {
-                                blocked: false,
-                                char: c,
-                                ..
-                            },
-                        ) => (COLOR_LIGHT_GROUND, Some(c)),
-                        (true, false, Tile { blocked: false, .. }) => (COLOR_DARK_GROUND, None),
-                        (false, _, _) => (COLOR_UNEXPLORED, None),
+    /// Count down word of recall and return to the entrance once it expires
+    fn countdown_recall(&mut self, turns: i32) -> Messages {
+        if !self.objects[PLAYER].alive {
+            Messages::empty()
+        } else if turns > 0 {
+            self.recall = Some(turns);
+            Messages::new("The air hums around you...", colors::LIGHT_BLUE)
+        } else {
+            if self.depth > 1 {
+                self.change_level(1);
+            }
+            let Location(x, y) = self.entrance;
+            let destination = (0..=SPILL_RADIUS)
+                .flat_map(|radius| {
+                    (-radius..=radius)
+                        .flat_map(move |dy| (-radius..=radius).map(move |dx| (dx, dy)))
+                })
+                .map(|(dx, dy)| Location(x + dx, y + dy))
+                .find(|l| self.map.contains(l) && !self.is_blocked(l));
+            match destination {
+                Some(loc) => {
+                    self.relocate(PLAYER, loc);
+                    Messages::new(
+                        "You are pulled back to the entrance of the caves!",
+                        colors::LIGHT_BLUE,
+                    )
+                }
+                None => Messages::new("The humming fades away.", colors::LIGHT_BLUE),
+            }
+        }
+    }
+
+    /// Let the status effects of an object act and wear off
+    ///
+    /// Only the player's own effects and those of visible objects are
+    /// reported.
+    fn tick_status(&mut self, id: Id) -> Messages {
+        let mut messages = Messages::empty();
+        let reported = id == PLAYER || self.objects[id].visible;
+        let name = direct(&self.objects[id].name, true);
+        let mut status = std::mem::take(&mut self.objects[id].status);
+        for effect in status.iter_mut() {
+            match *effect {
+                StatusEffect::Poisoned { per_turn, .. } => {
+                    if let Some(fighter) = self.objects[id].fighter.as_mut() {
+                        fighter.take_damage(per_turn);
+                    }
+                    let text = match id {
+                        PLAYER => {
+                            self.stats.damage_taken += per_turn;
+                            format!("You suffer {} damage from poison.", per_turn)
+                        }
+                        _ => format!("{} suffers {} damage from poison.", name, per_turn),
                     };
-                    con.set_char_background(x, y, color, BackgroundFlag::Set);
-                    if let Some(c) = char {
-                        con.set_default_foreground(colors::LIGHT_GREY);
-                        con.put_char(x, y, *c, BackgroundFlag::None);
+                    if reported {
+                        messages.push(MessageKind::harm_to(id), text);
+                    }
+                }
+                StatusEffect::Regenerating { per_turn, .. } => {
+                    if let Some(fighter) = self.objects[id].fighter.as_mut() {
+                        fighter.heal(per_turn);
                     }
                 }
+                StatusEffect::Confused { .. } => {}
+            }
+            *effect.turns_mut() -= 1;
+            if effect.turns() <= 0 && reported {
+                let text = match id {
+                    PLAYER => format!("You are no longer {}.", effect.adjective()),
+                    _ => format!("{} is no longer {}.", name, effect.adjective()),
+                };
+                messages.add(text, colors::WHITE);
             }
         }
 
-        // Sort the object to draw such that non-blocking objects are
-        // drawn first to avoid drawing them over other objects standing
-        // on top of them.
-        let mut to_draw: Vec<_> = self.objects.iter().filter(|o| o.visible).collect();
+        let dying = self.objects[id].fighter.is_some_and(|f| !f.is_alive());
+        let poisoned = status
+            .iter()
+            .any(|e| matches!(e, StatusEffect::Poisoned { .. }));
+        if dying && poisoned && !(id == PLAYER && self.godmode) {
+            let text = match id {
+                PLAYER => String::from("You succumb to poison."),
+                _ => format!("{} succumbs to poison.", name),
+            };
+            messages.push(MessageKind::Danger, text);
+        }
 
-        to_draw.sort_by(|a, b| a.blocks.cmp(&b.blocks));
-        for object in to_draw {
-            if let Some(loc) = rostlaube::geometry::translate(source, target, &object.loc, focus) {
-                ui::draw(object, con, &loc);
+        status.retain(|e| e.turns() > 0);
+        self.objects[id].status = status;
+        messages
+    }
+
+    /// Use up some food and let the starving waste away
+    fn tick_hunger(&mut self, id: Id) -> Messages {
+        let mut messages = Messages::empty();
+        let hunger = match self.objects[id].hunger.as_mut() {
+            Some(hunger) => hunger,
+            None => return messages,
+        };
+        *hunger = cmp::max(*hunger - 1, 0);
+        match *hunger {
+            HUNGER_HUNGRY => messages.push(MessageKind::Warning, "You are hungry."),
+            HUNGER_STARVING => messages.push(MessageKind::Danger, "You are starving!"),
+            0 => {
+                if let Some(fighter) = self.objects[id].fighter.as_mut() {
+                    fighter.take_damage(STARVATION_DAMAGE);
+                    if id == PLAYER {
+                        self.stats.damage_taken += STARVATION_DAMAGE;
+                    }
+                    if !fighter.is_alive() && !self.godmode {
+                        messages.push(MessageKind::Danger, "You starve to death.");
+                    }
+                }
             }
+            _ => {}
         }
+        messages
     }
 
-    fn render_ui(&self, con: &mut Offscreen) {
-        let player = &self.objects[PLAYER];
-        con.set_default_background(colors::BLACK);
-        con.clear();
-
-        if let Some(fighter) = player.fighter {
-            let health_bar = Bar {
-                x: 0,
-                y: 0,
-                color: colors::GREEN,
-                background: colors::RED,
-                current: fighter.health,
-                maximum: fighter.max_health,
-                width: con.width(),
-                name: String::from("HP"),
-            };
-            ui::draw(&health_bar, con, &Location(0, 0));
+    /// Let a fighter without health left die
+    fn die(&mut self, id: Id) -> Messages {
+        let mut messages = Messages::empty();
+        let fighter = match self.objects[id].fighter {
+            Some(fighter) => fighter,
+            None => return messages,
+        };
+        self.event_log.record(
+            self.turn,
+            EntryKind::Event,
+            format!("{} ({}) died", self.objects[id].name, id),
+        );
+        self.emit_sound(SoundKind::Death, self.objects[id].loc);
+        messages.append(fighter.on_death.call(&mut self.objects[id]));
+        if id == PLAYER {
+            messages.append(self.leave_grave(id));
+        } else if self.corpse_lifetime >= 0 {
+            self.objects[id].decay = Some(self.corpse_lifetime);
         }
+        messages
+    }
 
-        con.set_default_background(colors::BLACK);
-        con.set_default_foreground(colors::WHITE);
-        let y = 2;
-        let opponents = fighters_by_distance(PLAYER, &self.objects, TORCH_RADIUS);
-        for (i, &id) in opponents
-            .iter()
-            .rev()
-            .enumerate()
-            .take(con.height() as usize - y as usize - 1)
-        // Only as many as there is space for
-        {
-            let o = &self.objects[id];
-            if self.visible(&o.loc) {
-                con.put_char_ex(1, i as i32 + 1 + 1, o.char, o.color, colors::BLACK);
-                con.print_ex(
-                    2,
-                    i as i32 + y,
-                    BackgroundFlag::None,
-                    TextAlignment::Left,
-                    format!(" {}", o.name),
-                )
+    /// Scatter the inventory around the place of death and mark it with a grave
+    fn leave_grave(&mut self, id: Id) -> Messages {
+        let loc = self.objects[id].loc;
+        let grave = Object::grave(loc, &self.objects[id].name);
+
+        let mut messages = Messages::empty();
+        if !self.inventory.is_empty() {
+            for item in std::mem::take(&mut self.inventory) {
+                place_item(item, &loc, self);
             }
+            messages.add("Your belongings scatter across the floor.", colors::WHITE);
         }
+        self.spawn(grave);
+        messages
     }
 
-    pub fn render_messages(&self, con: &mut Offscreen) {
-        let messages = &self.messages;
-        con.set_default_background(colors::BLACK);
-        // self.window.con.clear();
+    /// The gear a fighter has equipped in a slot
+    ///
+    /// Only the player carries an inventory, so monsters have no gear.
+    pub fn equipped_bonus(&self, id: Id, slot: Slot) -> Option<&Equipment> {
+        if id != PLAYER {
+            return None;
+        }
+        self.inventory
+            .iter()
+            .filter_map(|o| o.equipment.as_ref())
+            .find(|e| e.equipped && e.slot == slot)
+    }
 
-        ui::draw(messages, con, &Location(0, 0));
+    /// The weapon a fighter shoots with, if it can shoot at all
+    pub fn ranged_weapon(&self, id: Id) -> Option<&Equipment> {
+        self.equipped_bonus(id, Slot::Weapon)
+            .filter(|e| e.range > 0)
     }
 
-    pub fn update(&mut self, action: Action) {
-        self.player_turn.push(action);
-        self.play(&vec![action]);
-        self.refresh();
+    /// Arrows the player has left to shoot
+    pub fn arrows(&self) -> i32 {
+        self.inventory
+            .iter()
+            .filter(|o| o.item == Some(Item::Arrow))
+            .map(|o| o.count.unwrap_or(1))
+            .sum()
+    }
 
-        // Some actions don't consume a turn
-        if action.took_turn() {
-            // Calculate the reaction of the AI and play
-            // the AI turn.
-            let ai_turns = self.ai_turns();
-            self.play(&ai_turns);
+    fn equipped(&self, id: Id) -> impl Iterator<Item = &Equipment> {
+        [Slot::Weapon, Slot::OffHand, Slot::Armor, Slot::Ring]
+            .iter()
+            .filter_map(move |&slot| self.equipped_bonus(id, slot))
+    }
 
-            self.rollover(self.player_turn.clone(), ai_turns);
-        }
+    /// Attack power of a fighter including its gear
+    ///
+    /// An off-hand weapon strikes on its own and does not add to it.
+    pub fn power(&self, id: Id) -> i32 {
+        let base = self.objects[id].fighter.map_or(0, |f| f.power);
+        let bonus = self
+            .equipped(id)
+            .filter(|e| e.slot != Slot::OffHand)
+            .map(|e| e.power_bonus)
+            .sum::<i32>();
+        base + bonus
     }
 
-    // fn open_inventory(&self, engine: &mut Engine, title: &str) -> Option<usize> {
-    //     let mut items: Vec<&str> = vec![];
-    //     for item in &self.inventory {
-    //         items.push(&item.name);
-    //     }
-    //     engine.menu(title, &items, 25)
-    // }
-}
+    /// Power of the follow-up strike with a weapon in the off hand, `None`
+    /// for an empty hand or a shield
+    pub fn off_hand_power(&self, id: Id) -> Option<i32> {
+        let base = self.objects[id].fighter.map_or(0, |f| f.power);
+        self.equipped_bonus(id, Slot::OffHand)
+            .filter(|e| e.power_bonus > 0)
+            .map(|e| (base + e.power_bonus) * OFF_HAND_POWER / 100)
+    }
 
-#[derive(Debug)]
-pub struct Messages {
-    messages: Vec<Message>,
-}
+    /// Defense of a fighter including its gear
+    pub fn defense(&self, id: Id) -> i32 {
+        let base = self.objects[id].fighter.map_or(0, |f| f.defense);
+        base + self.equipped(id).map(|e| e.defense_bonus).sum::<i32>()
+    }
 
-impl Messages {
-    pub fn empty() -> Self {
-        Self { messages: vec![] }
+    /// Damage dice of the fighter's weapon, `None` when it fights with its
+    /// power alone
+    pub fn damage_dice(&self, id: Id) -> Option<rng::Dice> {
+        self.equipped_bonus(id, Slot::Weapon).and_then(|e| {
+            e.damage.map(|dice| rng::Dice {
+                modifier: dice.modifier + e.enchantment,
+                ..dice
+            })
+        })
     }
 
-    pub fn new<T: Into<String>>(message: T, color: Color) -> Self {
-        let mut messages = Self::empty();
-        messages.add(message, color);
-        messages
+    /// Pack indices of the equipped gear a scroll of enchantment works on
+    pub fn enchantable(&self) -> Vec<usize> {
+        self.inventory
+            .iter()
+            .enumerate()
+            .filter(|(_, o)| {
+                o.equipment
+                    .is_some_and(|e| e.equipped && matches!(e.slot, Slot::Weapon | Slot::Armor))
+            })
+            .map(|(i, _)| i)
+            .collect()
     }
 
-    pub fn add<T: Into<String>>(&mut self, message: T, color: Color) {
-        self.messages.push((message.into(), color));
+    /// The player has gathered enough experience for the next level
+    pub fn level_up_pending(&self) -> bool {
+        self.objects[PLAYER]
+            .fighter
+            .is_some_and(|f| f.xp >= level_up_threshold(f.level))
     }
 
-    pub fn append(&mut self, other: Self) {
-        for (msg, color) in other.iter() {
-            self.messages.push((msg.into(), *color));
+    /// Advance the player to the next level with the chosen bonus
+    pub fn level_up(&mut self, bonus: Bonus) {
+        if !self.level_up_pending() {
+            return;
+        }
+        if let Some(fighter) = self.objects[PLAYER].fighter.as_mut() {
+            fighter.level += 1;
+            let msg = match bonus {
+                Bonus::Constitution => {
+                    fighter.max_health += LEVEL_UP_HEALTH;
+                    fighter.health += LEVEL_UP_HEALTH;
+                    "You feel tougher!"
+                }
+                Bonus::Strength => {
+                    fighter.power += 1;
+                    "You feel stronger!"
+                }
+                Bonus::Agility => {
+                    fighter.defense += 1;
+                    "You feel quicker!"
+                }
+            };
+            let level = fighter.level;
+            self.messages.push(
+                MessageKind::Good,
+                format!(
+                    "Your battle skills grow stronger! You reached level {}.",
+                    level
+                ),
+            );
+            self.messages.push(MessageKind::Good, msg);
         }
     }
 
-    pub fn iter(&self) -> impl DoubleEndedIterator<Item = &(String, Color)> {
-        self.messages.iter()
+    /// Move the player one level deeper
+    ///
+    /// Everything but the player is left behind, the inventory is kept. A
+    /// level is generated the first time the player gets there.
+    pub fn descend(&mut self) -> Messages {
+        let light_radius = self.light_radius();
+        self.change_level(self.depth + 1);
+        let mut messages = Messages::new(
+            format!(
+                "You descend deeper into the rusty caves (level {}).",
+                self.depth
+            ),
+            colors::GREEN,
+        );
+        if self.light_radius() < light_radius {
+            messages.add("The darkness closes in around your light.", colors::GREY);
+        }
+        messages
     }
-}
 
-// --------------------------------- Objects ----------------------------------
+    /// Move the player back up to the level above, as it was left
+    ///
+    /// Climbing up from the first level leaves the caves, which wins the game.
+    pub fn ascend(&mut self) -> Messages {
+        if self.depth <= 1 {
+            self.escaped = true;
+            self.event_log
+                .record(self.turn, EntryKind::Event, "Escaped the caves");
+            return Messages::new(
+                "You escape the rusty caves and see daylight again. You win!",
+                colors::YELLOW,
+            );
+        }
+        self.change_level(self.depth - 1);
+        Messages::new(
+            format!("You climb back up to level {}.", self.depth),
+            colors::GREEN,
+        )
+    }
 
-/// A tile of the map and its properties
-#[derive(Debug, Clone, Copy)]
-pub struct Tile {
-    pub blocked: bool,
-    pub block_sight: bool,
-    pub char: char,
-    pub explored: bool,
-    pub visible: bool,
-}
+    /// The player stands on the stairs leading out of the caves
+    pub fn at_exit(&self) -> bool {
+        self.depth == 1
+            && self
+                .objects_at(&self.objects[PLAYER].loc)
+                .any(|(_, o)| o.upstairs)
+    }
 
-impl Tile {
-    pub fn empty() -> Self {
-        Tile {
-            blocked: false,
-            block_sight: false,
-            char: '.',
-            explored: false,
-            visible: false,
+    /// Put the current level away and swap in the one at another depth
+    ///
+    /// The player arrives on the stairs leading back to where they came from.
+    fn change_level(&mut self, depth: i32) {
+        let going_up = depth < self.depth;
+        let objects = self
+            .objects
+            .ids()
+            .into_iter()
+            .filter(|&id| id != PLAYER)
+            .filter_map(|id| self.objects.remove(id))
+            .collect();
+        let level = Level {
+            map: std::mem::replace(&mut self.map, Map::new(Dimension(0, 0), Tile::wall())),
+            objects,
+            effects: std::mem::take(&mut self.effects),
+            entrance: self.entrance,
+        };
+        let index = (self.depth - 1) as usize;
+        if self.levels.len() <= index {
+            self.levels.resize_with(index + 1, || None);
         }
-    }
+        self.levels[index] = Some(level);
+        self.depth = depth;
 
-    pub fn wall() -> Self {
-        Tile {
-            blocked: true,
-            block_sight: true,
-            char: '#',
-            explored: false,
-            visible: false,
+        match self
+            .levels
+            .get_mut(depth as usize - 1)
+            .and_then(Option::take)
+        {
+            Some(level) => {
+                self.map = level.map;
+                for object in level.objects {
+                    self.objects.insert(object);
+                }
+                self.effects = level.effects;
+                self.entrance = level.entrance;
+                let arrival = self
+                    .objects
+                    .values()
+                    .find(|o| if going_up { o.stairs } else { o.upstairs })
+                    .map_or(self.entrance, |o| o.loc);
+                self.objects[PLAYER].loc = arrival;
+            }
+            None => self.generate_level(),
         }
+
+        let player = self.objects[PLAYER].loc;
+        self.camera = player;
+        self.reindex();
+        self.init_fov();
+        self.refresh();
+        self.emit_sound(SoundKind::LevelChange, player);
+        self.event_log.record(
+            self.turn,
+            EntryKind::Event,
+            format!("Moved to level {}", self.depth),
+        );
     }
-}
 
-/// Generic object: the player, a monster, an item, the stairs...
-/// It's always represented by a character on screen.
-#[derive(Debug, Default)]
-pub struct Object {
-    pub loc: Location,
-    pub char: char,
-    pub color: Color,
-    pub name: String,
+    /// Lay out a fresh level at the current depth
+    fn generate_level(&mut self) {
+        let level = self.build_level(self.depth);
+        self.map = level.map;
+        for object in level.objects {
+            self.objects.insert(object);
+        }
+        self.entrance = level.entrance;
+        self.objects[PLAYER].loc = level.entrance;
+    }
 
-    // Flags
-    pub blocks: bool,
-    pub visible: bool,
-    pub seen: bool,
-    pub alive: bool,
+    /// Lay out a level at a depth without entering it
+    fn build_level(&self, depth: i32) -> Level {
+        // The generator places the player, a stand-in marks the entrance
+        let mut objects = Objects::new();
+        let stand_in = objects.insert(Object::player(Location(0, 0), ""));
+        let map = dungeon::generate(&self.layout, &mut objects, depth);
+        let entrance = objects[stand_in].loc;
+        objects.remove(stand_in);
 
-    // Components
-    pub movement: Option<Movement>,
-    pub fighter: Option<Fighter>,
-    pub ai: Option<Ai>,
-    pub noise: Option<Noise>,
-    pub item: Option<Item>,
-}
+        let mut objects: Vec<Object> = objects
+            .ids()
+            .into_iter()
+            .filter_map(|id| objects.remove(id))
+            .collect();
+        for object in objects.iter_mut() {
+            self.difficulty.adjust(object);
+        }
+        Level {
+            map,
+            objects,
+            effects: vec![],
+            entrance,
+        }
+    }
 
-impl Object {
-    pub fn new() -> Self {
-        let mut o: Object = Default::default();
-        o.char = '`';
-        o.name = "it".into();
-        o
+    /// Remove everything but the player, e.g. to set up a level by hand
+    pub fn clear_objects(&mut self) {
+        self.objects.retain(|id, _| id == PLAYER);
+        self.reindex();
     }
-    pub fn player(loc: Location, name: &str) -> Self {
-        let mut this = Object::new();
-        this.loc = loc;
-        this.name = String::from(name);
-        this.char = '@';
-        this.color = colors::YELLOW;
 
-        this.blocks = true;
-        this.alive = true;
-        this.visible = true;
-        this.seen = true;
+    /// Replace the map, e.g. with a hand-made one of any size
+    pub fn set_map(&mut self, map: Map) {
+        self.map = map;
+        self.init_fov();
+        self.refresh();
+    }
 
-        this.movement = Some(Movement { speed: 100 });
-        this.fighter = Some(Fighter {
-            max_health: 30,
-            health: 30,
-            defense: 2,
-            power: 5,
-            on_death: DeathCallback::Player,
-            health_regen: 0.5,
-        });
+    /// Size the field of view to the map and let it know what blocks
+    fn init_fov(&mut self) {
+        let Dimension(width, height) = self.map.dimensions();
+        self.fov = FovMap::new(width, height);
+        self.fov_bounds = (Location(0, 0), Location(width - 1, height - 1));
+        for loc in self.map.iter_coords() {
+            if let Some(tile) = self.map.get(&loc) {
+                let Location(x, y) = loc;
+                self.fov.set(x, y, !tile.block_sight, !tile.blocked);
+            }
+        }
+    }
 
-        this
+    fn update_fov(&mut self) -> Messages {
+        self.update_sight_blockers();
+        let Location(x, y) = self.objects[PLAYER].loc;
+        let radius = self.light_radius();
+        self.fov
+            .compute_fov(x, y, radius, FOV_LIGHT_WALLS, self.fov_algorithm);
+        Messages::empty()
     }
-    pub fn orc(loc: Location) -> Self {
-        let mut this = Object::new();
-        this.loc = loc;
-        this.name = String::from("orc");
-        this.char = 'o';
-        this.color = colors::GREEN;
-        this.blocks = true;
-        this.alive = true;
 
-        this.ai = Some(Ai::Basic);
-        this.movement = Some(Movement { speed: 90 });
-        this.fighter = Some(Fighter {
-            max_health: 10,
-            health: 10,
-            defense: 0,
-            power: 3,
-            on_death: DeathCallback::Monster,
-            health_regen: 0.1,
-        });
-        this.noise = Some(Noise {
-            bark: String::from("shout"),
-            mumble: String::from("mumble"),
-        });
+    /// Let tall creatures cast a shadow in the field of view
+    ///
+    /// The tiles they stood on last time are cleared first, so the shadows
+    /// follow them around.
+    fn update_sight_blockers(&mut self) {
+        for loc in std::mem::take(&mut self.sight_blockers) {
+            if let Some(tile) = self.map.get(&loc) {
+                let Location(x, y) = loc;
+                self.fov.set(x, y, !tile.block_sight, !tile.blocked);
+            }
+        }
+        if !self.creatures_block_sight {
+            return;
+        }
+        self.sight_blockers = self
+            .objects
+            .iter()
+            .filter(|&(id, o)| id != PLAYER && o.blocks && o.tall)
+            .map(|(_, o)| o.loc)
+            .collect();
+        for loc in &self.sight_blockers {
+            if let Some(tile) = self.map.get(loc) {
+                let Location(x, y) = *loc;
+                self.fov.set(x, y, false, !tile.blocked);
+            }
+        }
+    }
 
-        this
+    /// Switch whether tall creatures block the view and look around again
+    pub fn set_creatures_block_sight(&mut self, block: bool) {
+        self.creatures_block_sight = block;
+        self.refresh();
     }
-    pub fn troll(loc: Location) -> Self {
-        let mut this = Object::new();
-        this.loc = loc;
-        this.name = String::from("troll");
-        this.char = 'T';
-        this.color = colors::GREEN;
-        this.blocks = true;
-        this.alive = true;
 
-        this.ai = Some(Ai::Basic);
-        this.movement = Some(Movement { speed: 80 });
-        this.fighter = Some(Fighter {
-            max_health: 16,
-            health: 16,
-            defense: 1,
-            power: 4,
-            on_death: DeathCallback::Monster,
-            health_regen: 0.5,
-        });
-        this.noise = Some(Noise {
-            bark: String::from("roar"),
-            mumble: String::from("growl"),
-        });
+    /// How far the player can see right now
+    pub fn light_radius(&self) -> i32 {
+        let radius = cmp::max(self.torch_radius - self.darkness(), MIN_LIGHT_RADIUS);
+        if self.candle > 0 {
+            cmp::min(CANDLE_RADIUS, radius)
+        } else {
+            radius
+        }
+    }
 
-        this
+    /// How many tiles the gloom of the deeper levels takes off the light
+    fn darkness(&self) -> i32 {
+        (self.depth - 1) / DARKNESS_INTERVAL
     }
-    pub fn ogre(loc: Location) -> Self {
-        let mut this = Object::new();
-        this.loc = loc;
-        this.name = String::from("ogre");
-        this.char = 'O';
-        this.color = colors::YELLOW;
-        this.blocks = true;
-        this.alive = true;
 
-        this.ai = Some(Ai::Basic);
-        this.movement = Some(Movement { speed: 70 });
-        this.fighter = Some(Fighter {
-            max_health: 25,
-            health: 25,
-            defense: 2,
-            power: 8,
-            on_death: DeathCallback::Monster,
-            health_regen: 0.2,
-        });
-        this.noise = Some(Noise {
-            bark: String::from("bellow"),
-            mumble: String::from("burp"),
-        });
+    /// Hash of the essential game state to detect diverging replays
+    ///
+    /// Only state that the rules act on is included. Visibility and what has
+    /// been explored are left out because they follow from the field of view,
+    /// which is recomputed on every refresh. The hasher is fixed, so the same
+    /// state has the same fingerprint across runs and builds.
+    pub fn fingerprint(&self) -> u64 {
+        let mut hasher = Fnv::default();
+        self.turn.hash(&mut hasher);
+        self.depth.hash(&mut hasher);
+        for tile in self.map.tiles() {
+            tile.blocked.hash(&mut hasher);
+            tile.block_sight.hash(&mut hasher);
+            tile.char.hash(&mut hasher);
+        }
+        for object in self.objects.values().chain(self.inventory.iter()) {
+            object.name.hash(&mut hasher);
+            object.loc.hash(&mut hasher);
+            object.alive.hash(&mut hasher);
+            if let Some(fighter) = object.fighter {
+                fighter.health.hash(&mut hasher);
+                fighter.max_health.hash(&mut hasher);
+            }
+        }
+        self.inventory.len().hash(&mut hasher);
+        hasher.finish()
+    }
 
-        this
+    /// Register the receiver of the sounds made in the game
+    pub fn set_sound_sink(&mut self, sink: Box<dyn SoundSink>) {
+        self.sound = sink;
     }
-    pub fn potion<T: Into<String>>(loc: Location, item: Item, name: T) -> Self {
-        let mut this = Object::new();
-        this.loc = loc;
-        this.name = name.into();
-        this.char = '!';
-        this.color = colors::BLUE;
-        this.item = Some(item);
 
-        this
+    /// Report a sound at a location to the sound sink
+    pub fn emit_sound(&mut self, kind: SoundKind, loc: Location) {
+        self.sound.emit(SoundEvent { kind, loc });
     }
-    pub fn scroll<T: Into<String>>(loc: Location, item: Item, name: T) -> Self {
-        let mut this = Object::new();
-        this.loc = loc;
-        this.name = name.into();
-        this.char = '?';
-        this.color = colors::BLUE;
-        this.item = Some(item);
 
-        this
+    /// Add an object to the map and return its id
+    pub fn spawn(&mut self, object: Object) -> Id {
+        let loc = object.loc;
+        let id = self.objects.insert(object);
+        self.index.insert(id, loc);
+        id
     }
-}
 
-#[derive(Clone, Copy, Debug)]
-pub struct Fighter {
-    pub max_health: i32,
-    pub health: i32,
-    pub defense: i32,
-    pub power: i32,
-    pub on_death: DeathCallback,
-    pub health_regen: f32,
-}
+    /// Remove an object from the map, its id becomes invalid
+    fn despawn(&mut self, id: Id) -> Object {
+        let object = self
+            .objects
+            .remove(id)
+            .unwrap_or_else(|| panic!("Object {} does not exist anymore", id));
+        self.index.remove(id, &object.loc);
+        object
+    }
 
-impl Fighter {
-    fn take_damage(&mut self, damage: i32) {
-        self.health -= damage;
+    /// Put an object at a new location
+    pub fn relocate(&mut self, id: Id, loc: Location) {
+        self.index.relocate(id, &self.objects[id].loc, loc);
+        self.objects[id].loc = loc;
     }
-    fn heal(&mut self, amount: i32) {
-        self.health = cmp::min(self.health + amount, self.max_health);
+
+    /// Rebuild the index of objects by location
+    fn reindex(&mut self) {
+        self.index = SpatialIndex::new(&self.objects);
     }
-}
 
-#[derive(Debug, Clone, Copy)]
-pub enum DeathCallback {
-    Player,
-    Monster,
-}
+    /// All objects at a location
+    ///
+    /// Objects removed without rebuilding the index are skipped.
+    pub fn objects_at(&self, loc: &Location) -> impl Iterator<Item = (Id, &Object)> {
+        self.index
+            .at(loc)
+            .iter()
+            .filter_map(move |&id| self.objects.get(id).map(|o| (id, o)))
+    }
 
-impl DeathCallback {
-    fn call(&self, object: &mut Object) -> Messages {
-        use DeathCallback::*;
-        match self {
-            Player => kill_player(object),
-            Monster => kill_monster(object),
-        }
+    /// A living fighter at the location, whatever else lies there
+    pub fn first_fighter_at(&self, loc: &Location) -> Option<Id> {
+        self.objects_at(loc)
+            .find(|(_, o)| o.alive && o.fighter.is_some())
+            .map(|(id, _)| id)
     }
-}
 
-#[derive(Debug)]
-pub struct Noise {
-    pub bark: String,
-    pub mumble: String,
-}
+    /// Something at the location that can be picked up
+    pub fn first_item_at(&self, loc: &Location) -> Option<Id> {
+        self.objects_at(loc)
+            .find(|(_, o)| o.item.is_some() || o.equipment.is_some())
+            .map(|(id, _)| id)
+    }
 
-#[derive(Debug)]
-pub struct Movement {
-    pub speed: i32,
-}
+    /// Check if an object blocks at this position
+    pub fn object_blocks(&self, loc: &Location) -> bool {
+        self.objects_at(loc).any(|(_, o)| o.blocks)
+    }
 
-#[derive(Debug)]
-pub enum Item {
-    Heal,
-    Lightning,
-    Confusion,
-}
+    /// A trap at the location that has already been sprung or spotted
+    pub fn known_trap_at(&self, loc: &Location) -> Option<Id> {
+        self.objects_at(loc)
+            .find(|(_, o)| o.trap.is_some_and(|t| !t.hidden))
+            .map(|(id, _)| id)
+    }
 
-// --------------------------------- Actions ----------------------------------
+    /// Check if a place on the map is blocked
+    pub fn is_blocked(&self, loc: &Location) -> bool {
+        structure_blocks(loc, &self.map) || self.object_blocks(loc)
+    }
 
-#[derive(Debug, Clone, Copy)]
-pub enum Action {
-    Move(usize, Direction),
-    Attack(usize, usize),
-    PickUp(usize, usize),
-    UseItem(usize, usize),
-    Bark(usize),
-    Mumble(usize),
-    Wait(usize),
-    Nothing,
-}
+    /// A visible fighter at the location that items can be used on
+    pub fn target_at(&self, loc: &Location) -> Option<Id> {
+        if !self.visible(loc) {
+            return None;
+        }
+        self.objects_at(loc)
+            .find(|(id, o)| *id != PLAYER && o.fighter.is_some() && o.ai.is_some())
+            .map(|(id, _)| id)
+    }
 
-impl Action {
-    pub fn took_turn(&self) -> bool {
-        use Action::*;
-        match self {
-            Move(_, _) => true,
-            Attack(_, _) => true,
-            PickUp(_, _) => true,
-            Bark(_) => true,
-            Mumble(_) => true,
-            Wait(_) => true,
-            UseItem(_, _) => false,
-            Nothing => false,
+    /// What is at a location, naming the topmost visible object or the tile
+    pub fn describe(&self, loc: &Location) -> String {
+        let topmost = self
+            .objects_at(loc)
+            .filter(|(_, o)| o.visible)
+            .max_by_key(|(_, o)| o.render_priority);
+        match topmost {
+            Some((PLAYER, _)) => String::from("yourself"),
+            Some((_, o)) => some(o, false),
+            None => String::from(self.map.get(loc).map_or("nothing", |t| t.name())),
         }
     }
-}
 
-/// Pick a move or attack action
-pub fn move_or_attack(
-    id: usize,
-    direction: Direction,
-    map: &Map,
-    objects: &[Object],
-) -> (Option<Action>, Messages) {
-    let destination = destination(&objects[id].loc, &direction);
-    if object_blocks(&destination, objects) {
-        objects
-            .iter()
-            .position(|o| o.loc == destination && o.fighter.is_some())
-            .map_or_else(
-                || (None, Messages::new("Cannot attack that.", colors::WHITE)),
-                |defender| (Some(Action::Attack(id, defender)), Messages::empty()),
-            )
-    } else if structure_blocks(&destination, map) {
-        (None, Messages::new("It's blocked.", colors::WHITE))
-    } else {
-        (Some(Action::Move(id, direction)), Messages::empty())
+    /// Estimate how the attacks of one fighter on another would go
+    pub fn odds(&self, attacker: Id, defender: Id) -> Option<Odds> {
+        self.objects[attacker].fighter?;
+        let health = self.objects[defender].fighter?.health;
+        let defense = self.defense(defender);
+        // The same rolls as in an attack, at their lowest and highest
+        let (min, max) = match self.damage_dice(attacker) {
+            Some(dice) => (
+                dice.n + dice.modifier - defense,
+                dice.n * dice.x + dice.modifier - defense,
+            ),
+            None => {
+                let power = self.power(attacker);
+                (power.clamp(0, 1) - defense, power - defense.clamp(0, 1))
+            }
+        };
+        let damage = (min.max(0), max.max(0));
+        let average = (damage.0 + damage.1) as f32 / 2.0;
+        let hits_to_kill = Some(average)
+            .filter(|&average| average > 0.0)
+            .map(|average| (health as f32 / average).ceil() as i32);
+        Some(Odds {
+            damage,
+            hits_to_kill,
+        })
     }
-}
 
-/// Grab an object
-pub fn grab(id: usize, objects: &[Object]) -> (Option<Action>, Messages) {
-    objects
-        .iter()
-        .position(|o| o.loc == objects[id].loc && o.item.is_some())
-        .map_or_else(
-            || {
-                (
-                    None,
-                    Messages::new("There is nothing here to pick up.", colors::WHITE),
-                )
-            },
-            |item_id| (Some(Action::PickUp(id, item_id)), Messages::empty()),
-        )
-}
+    /// How far away a location is and whether the player could shoot there
+    pub fn describe_sight(&self, loc: &Location) -> String {
+        let player = &self.objects[PLAYER].loc;
+        let distance = chebyshev_distance(player, loc);
+        let sight = if has_line_of_sight(player, loc, &self.map) {
+            "clear"
+        } else {
+            "blocked"
+        };
+        match distance {
+            1 => format!("1 tile away, line of sight {}.", sight),
+            d => format!("{} tiles away, line of sight {}.", d, sight),
+        }
+    }
 
-// ------------------------------- Resolution ---------------------------------
+    /// How a fight with the visible monster at a location would go, both ways
+    pub fn describe_odds(&self, loc: &Location) -> Option<String> {
+        let monster = self.target_at(loc)?;
+        let yours = self.odds(PLAYER, monster)?;
+        let theirs = self.odds(monster, PLAYER)?;
+        Some(format!("You deal {}, it deals {}", yours, theirs))
+    }
 
-enum UseResult {
-    UsedUp,
-    Cancelled,
-}
+    /// Someone to talk to in the given direction
+    pub fn talk_target(&self, id: Id, direction: Direction) -> Option<Id> {
+        let destination = destination(&self.objects[id].loc, &direction);
+        self.objects_at(&destination)
+            .find(|(_, o)| o.dialogue.is_some())
+            .map(|(id, _)| id)
+    }
 
-/// Attack resolution
-fn attack(attacker: usize, defender: usize, objects: &mut [Object]) -> Messages {
-    let msg = match (attacker, defender) {
-        (PLAYER, d) => format!("You attack {}", direct(&objects[d].name, false)),
+    pub fn visible(&self, loc: &Location) -> bool {
+        let Location(x, y) = *loc;
+        self.fov.is_in_fov(x, y)
+    }
+
+    /// Fighters within range that are in the field of view, sorted by
+    /// descending distance
+    pub fn visible_fighters_by_distance(&self, id: Id, range: i32) -> Vec<Id> {
+        fighters_by_distance(id, &self.objects, range)
+            .into_iter()
+            .filter(|&i| self.visible(&self.objects[i].loc))
+            .collect()
+    }
+
+    /// Draw the map and the visible objects onto the console
+    pub fn render_game_world(&self, con: &mut Offscreen) {
+        let layer = self.build_world_layer(Dimension(con.width(), con.height()));
+        let (width, height) = (layer.width(), layer.height());
+        console::blit(&layer, (0, 0), (width, height), con, (0, 0), 1.0, 1.0);
+    }
+
+    /// Move the camera just enough to keep the player inside the dead zone
+    fn follow_player(&mut self) {
+        let Location(x, y) = self.objects[PLAYER].loc;
+        let Location(cx, cy) = self.camera;
+        let Dimension(dx, dy) = CAMERA_DEAD_ZONE;
+        self.camera = Location(cx.clamp(x - dx, x + dx), cy.clamp(y - dy, y + dy));
+    }
+
+    /// Where a view of the given size is centered
+    ///
+    /// This is the camera, held back from the edges of the map so that the
+    /// view does not show anything beyond them.
+    pub fn view_focus(&self, size: &Dimension) -> Location {
+        let Location(x, y) = self.camera;
+        let Dimension(width, height) = *size;
+        let Dimension(map_width, map_height) = self.map.dimensions();
+        Location(
+            clamp_view(x, width, map_width),
+            clamp_view(y, height, map_height),
+        )
+    }
+
+    /// The explored tile under the mouse pointer in a view of the given size
+    pub fn pointed_at(&self, size: &Dimension) -> Option<Location> {
+        let cell = self.pointer?;
+        let loc = rostlaube::geometry::translate_back(size, &cell, &self.view_focus(size));
+        self.map.get(&loc).filter(|tile| tile.explored).map(|_| loc)
+    }
+
+    /// The colors the current level is drawn in
+    pub fn theme(&self) -> Theme {
+        Theme::at_depth(self.depth)
+    }
+
+    /// Build a layer of the given size with the map and the visible objects,
+    /// centered on the camera
+    ///
+    /// Overlays like cursors or effects can be drawn on top after blitting it.
+    pub fn build_world_layer(&self, size: Dimension) -> Offscreen {
+        let Dimension(width, height) = size;
+        let mut layer = Offscreen::new(width, height);
+        let con = &mut layer;
+        let focus = &self.view_focus(&size);
+
+        let source = &self.map.dimensions();
+        let target = &size;
+        let theme = self.theme();
+
+        for loc in self.map.iter_coords() {
+            let loc = &loc;
+            let view_loc = rostlaube::geometry::translate(source, target, loc, focus);
+            if let (Some(Location(x, y)), Some(tile)) = (view_loc, self.map.get(loc)) {
+                let (color, char) = match (tile.explored, tile.visible, tile) {
+                    (
+                        true,
+                        true,
+                        Tile {
+                            blocked: true,
+                            char: c,
+                            ..
+                        },
+                    ) => (self.lit(theme.light_wall, theme.dark_wall, loc), Some(c)),
+                    (true, false, Tile { blocked: true, .. }) => (theme.dark_wall, None),
+                    (
+                        true,
+                        true,
+                        Tile {
+                            blocked: false,
+                            char: c,
+                            ..
+                        },
+                    ) => (
+                        self.lit(theme.light_ground, theme.dark_ground, loc),
+                        Some(c),
+                    ),
+                    (true, false, Tile { blocked: false, .. }) => (theme.dark_ground, None),
+                    (false, _, _) => (theme.unexplored, None),
+                };
+                let color = match self.effects.iter().find(|e| e.covers(loc)) {
+                    Some(effect) if tile.visible => colors::lerp(color, effect.color, 0.5),
+                    _ => color,
+                };
+                con.set_char_background(x, y, color, BackgroundFlag::Set);
+                if let Some(c) = char {
+                    con.set_default_foreground(colors::LIGHT_GREY);
+                    con.put_char(x, y, *c, BackgroundFlag::None);
+                }
+            }
+        }
+
+        // Draw the objects on the floor first and the creatures last, so that
+        // the most relevant one on a tile is on top.
+        let mut to_draw: Vec<_> = self.objects.values().filter(|o| o.visible).collect();
+
+        to_draw.sort_by_key(|o| o.render_priority);
+        for object in to_draw {
+            if let Some(loc) = rostlaube::geometry::translate(source, target, &object.loc, focus) {
+                ui::draw(object, con, &loc);
+            }
+        }
+        layer
+    }
+
+    /// Draw the next frame of the queued animations on top of the world
+    ///
+    /// The animations play one after the other. Returns `false` once all of
+    /// them are done.
+    pub fn animate(&mut self, con: &mut Offscreen) -> bool {
+        let size = Dimension(con.width(), con.height());
+        let focus = self.view_focus(&size);
+        let animation = match self.animations.first_mut() {
+            Some(animation) => animation,
+            None => return false,
+        };
+        for loc in &animation.tiles {
+            let view_loc =
+                rostlaube::geometry::translate(&self.map.dimensions(), &size, loc, &focus);
+            if let Some(Location(x, y)) = view_loc {
+                con.set_default_foreground(animation.color);
+                con.put_char(x, y, animation.char, BackgroundFlag::None);
+            }
+        }
+        animation.frames -= 1;
+        if animation.frames <= 0 {
+            self.animations.remove(0);
+        }
+        true
+    }
+
+    /// Color of a visible tile, fading from light to dark with the distance
+    /// to the player
+    fn lit(&self, light: Color, dark: Color, loc: &Location) -> Color {
+        if self.light_falloff {
+            let falloff = distance(&self.objects[PLAYER].loc, loc) / self.light_radius() as f32;
+            colors::lerp(light, dark, falloff.powi(2).min(1.0))
+        } else {
+            light
+        }
+    }
+
+    fn render_ui(&self, con: &mut Offscreen) {
+        let player = &self.objects[PLAYER];
+        con.set_default_background(colors::BLACK);
+        con.clear();
+
+        if let Some(fighter) = player.fighter {
+            let health_bar = Bar {
+                x: 0,
+                y: 0,
+                color: colors::GREEN,
+                background: colors::RED,
+                current: fighter.health,
+                maximum: fighter.max_health,
+                width: con.width(),
+                name: String::from("HP"),
+            };
+            ui::draw(&health_bar, con, &Location(0, 0));
+        }
+
+        con.set_default_background(colors::BLACK);
+        con.set_default_foreground(colors::WHITE);
+        con.print_ex(
+            con.width() - 1,
+            1,
+            BackgroundFlag::None,
+            TextAlignment::Right,
+            format!("Turn {}", self.turn),
+        );
+        let hunger = match player.hunger {
+            Some(h) if h <= HUNGER_STARVING => Some(("Starving", colors::RED)),
+            Some(h) if h <= HUNGER_HUNGRY => Some(("Hungry", colors::YELLOW)),
+            _ => None,
+        };
+        if let Some((label, color)) = hunger {
+            con.set_default_foreground(color);
+            con.print_ex(0, 1, BackgroundFlag::None, TextAlignment::Left, label);
+        }
+        let y = 2;
+        let opponents = self.visible_fighters_by_distance(PLAYER, self.light_radius());
+        for (i, (o, fighter)) in opponents
+            .iter()
+            .rev()
+            .filter_map(|&id| {
+                let o = &self.objects[id];
+                o.fighter.map(|f| (o, f))
+            })
+            .enumerate()
+            .take(con.height() as usize - y as usize - 1)
+        // Only as many as there is space for
+        {
+            con.put_char_ex(1, i as i32 + 1 + 1, o.char, o.color, colors::BLACK);
+            con.set_default_foreground(fighter.health_color());
+            con.print_ex(
+                2,
+                i as i32 + y,
+                BackgroundFlag::None,
+                TextAlignment::Left,
+                format!(" {}", o.name),
+            )
+        }
+    }
+
+    pub fn render_character(&self, con: &mut Offscreen) {
+        let player = &self.objects[PLAYER];
+        con.set_default_background(colors::BLACK);
+        con.set_default_foreground(colors::WHITE);
+        con.clear();
+
+        let mut lines = vec![player.name.clone(), String::new()];
+        lines.append(&mut self.stats.summary());
+        if let Some(fighter) = player.fighter {
+            lines.push(format!("Attack:         {}", self.power(PLAYER)));
+            lines.push(format!("Defense:        {}", self.defense(PLAYER)));
+            if let Some(dice) = self.damage_dice(PLAYER) {
+                lines.push(format!("Damage:         {}", dice));
+            }
+            lines.push(format!("Level:          {}", fighter.level));
+            lines.push(format!(
+                "Experience:     {} / {}",
+                fighter.xp,
+                level_up_threshold(fighter.level)
+            ));
+        }
+        lines.push(format!("Turns:          {}", self.turn));
+        lines.push(format!("Light radius:   {}", self.light_radius()));
+        for (y, line) in lines.iter().enumerate() {
+            con.print_ex(
+                2,
+                2 + y as i32,
+                BackgroundFlag::None,
+                TextAlignment::Left,
+                line,
+            );
+        }
+    }
+
+    /// Render the message history, scrolled back by `offset` messages
+    pub fn render_message_log(&self, con: &mut Offscreen, offset: usize) {
+        con.set_default_background(colors::BLACK);
+        con.set_default_foreground(colors::WHITE);
+        con.clear();
+        con.print_ex(
+            2,
+            1,
+            BackgroundFlag::None,
+            TextAlignment::Left,
+            "Message log (j/k or PageUp/PageDown to scroll, Escape to close)",
+        );
+
+        let rows = (con.height() - 4).max(0) as usize;
+        let end = self.messages.len().saturating_sub(offset);
+        let start = end.saturating_sub(rows);
+        for (y, (msg, color)) in self.messages.slice(start, end).iter().enumerate() {
+            con.set_default_foreground(*color);
+            con.print_ex(
+                2,
+                3 + y as i32,
+                BackgroundFlag::None,
+                TextAlignment::Left,
+                msg,
+            );
+        }
+    }
+
+    /// Render ongoing effects on the player
+    pub fn render_status(&self, con: &mut Offscreen) {
+        // Drawn last, so the log can't bury it
+        if let Some((text, color)) = self.alert() {
+            con.set_default_background(colors::BLACK);
+            con.set_default_foreground(*color);
+            con.print_ex(0, 0, BackgroundFlag::Set, TextAlignment::Left, text);
+        }
+        if let Some(turns) = self.recall {
+            con.set_default_foreground(colors::LIGHT_BLUE);
+            con.print_ex(
+                con.width() - 1,
+                0,
+                BackgroundFlag::None,
+                TextAlignment::Right,
+                format!("Recall in {}", turns),
+            );
+        }
+        if let Some(loc) = self.pointed_at(&Dimension(con.width(), con.height())) {
+            con.set_default_foreground(colors::LIGHT_GREY);
+            con.print_ex(
+                con.width() - 1,
+                1,
+                BackgroundFlag::None,
+                TextAlignment::Right,
+                self.describe(&loc),
+            );
+            if let Some(odds) = self.describe_odds(&loc) {
+                con.print_ex(
+                    con.width() - 1,
+                    2,
+                    BackgroundFlag::None,
+                    TextAlignment::Right,
+                    odds,
+                );
+            }
+        }
+    }
+
+    pub fn render_messages(&self, con: &mut Offscreen) {
+        let messages = &self.messages;
+        con.set_default_background(colors::BLACK);
+        // self.window.con.clear();
+
+        ui::draw(messages, con, &Location(0, 0));
+    }
+
+    /// The latest message if the player's last action caused it
+    pub fn alert(&self) -> Option<&Message> {
+        self.messages
+            .latest()
+            .filter(|_| self.messages.len() > self.alert_mark)
+    }
+
+    pub fn update(&mut self, action: Action) {
+        self.alert_mark = self.messages.len();
+        if let Action::Move(PLAYER, _)
+        | Action::Attack(PLAYER, _)
+        | Action::AttackDirection(PLAYER, _)
+        | Action::Wait(PLAYER) = action
+        {
+            self.last_action = Some(action);
+        }
+
+        // A confused player stumbles around
+        let action = match action {
+            Action::Move(PLAYER, _) if self.objects[PLAYER].is_confused() => ai::stumble(PLAYER)[0],
+            action => action,
+        };
+        self.player_turn.push(action);
+        self.play(&vec![action]);
+        self.refresh();
+
+        // Some actions don't consume a turn
+        if action.took_turn() {
+            self.run_hooks(|hook, game| hook.on_player_turn_end(game, game.turn));
+
+            // Calculate the reaction of the AI and play
+            // the AI turn.
+            let ai_turns = self.ai_turns();
+            self.play(&ai_turns);
+
+            self.rollover(self.player_turn.clone(), ai_turns);
+            self.run_hooks(|hook, game| hook.on_round_end(game, game.turn));
+        }
+    }
+
+    /// Walk towards unexplored areas until something interesting happens
+    pub fn explore(&mut self) {
+        self.walk("explore", |game| {
+            game.explore_path()
+                .and_then(|path| path.first().copied())
+                .ok_or_else(|| String::from("There is nothing left to explore."))
+        });
+    }
+
+    /// Walk to the closest stairs the player has seen on this level
+    pub fn travel_to_stairs(&mut self) {
+        let stairs: Vec<Location> = self
+            .objects
+            .values()
+            .filter(|o| (o.stairs || o.upstairs) && o.seen)
+            .map(|o| o.loc)
+            .collect();
+        if stairs.is_empty() {
+            self.messages.add("No known stairs.", colors::WHITE);
+            return;
+        }
+        self.walk("travel", |game| {
+            match game.bfs_path(|loc| stairs.contains(loc)) {
+                Some(path) => path
+                    .first()
+                    .copied()
+                    .ok_or_else(|| String::from("You reach the stairs.")),
+                None => Err(String::from("You can't find a way to the stairs.")),
+            }
+        });
+    }
+
+    /// Take one step after the other until something interesting happens
+    ///
+    /// `next_step` gives the tile to walk to, or the reason to stop.
+    fn walk<F>(&mut self, activity: &str, next_step: F)
+    where
+        F: Fn(&Game) -> Result<Location, String>,
+    {
+        let mut steps = 0;
+        let reason = loop {
+            if !self.objects[PLAYER].alive {
+                return;
+            }
+            let threatened = self
+                .visible_fighters_by_distance(PLAYER, self.light_radius())
+                .iter()
+                .any(|&id| self.objects[id].ai.is_some());
+            let loc = self.objects[PLAYER].loc;
+            let found = self
+                .first_item_at(&loc)
+                .filter(|_| steps > 0)
+                .map(|id| some(&self.objects[id], false));
+            if threatened {
+                break String::from("You spot a monster.");
+            } else if let Some(item) = found {
+                break format!("You find {}.", item);
+            } else if self.objects[PLAYER].is_confused() {
+                break format!("You are too confused to {}.", activity);
+            } else if steps >= EXPLORE_MAX_STEPS {
+                break String::from("You stop to get your bearings.");
+            }
+            let step = match next_step(self) {
+                Ok(step) => step,
+                Err(reason) => break reason,
+            };
+            self.update(Action::Move(PLAYER, direction(&loc, &step)));
+            if self.objects[PLAYER].loc == loc {
+                break String::from("Your way is blocked.");
+            }
+            steps += 1;
+        };
+        let summary = match steps {
+            0 => reason,
+            1 => format!("You {} for 1 turn. {}", activity, reason),
+            n => format!("You {} for {} turns. {}", activity, n, reason),
+        };
+        self.messages.add(summary, colors::WHITE);
+    }
+
+    /// The closest tile the player has not seen yet and can walk to
+    pub fn nearest_unexplored(&self) -> Option<Location> {
+        self.explore_path().and_then(|path| path.last().copied())
+    }
+
+    /// The way to the closest unexplored tile, without the player's location
+    fn explore_path(&self) -> Option<Vec<Location>> {
+        self.bfs_path(|loc| self.map.get(loc).is_some_and(|tile| !tile.explored))
+    }
+
+    /// The way to the closest tile that is a goal, without the player's
+    /// location, so it is empty when the player already stands on one
+    ///
+    /// Searches outwards from the player over the explored tiles that can be
+    /// walked on, so the first goal found is the closest. Unexplored tiles are
+    /// only entered if they are a goal, and known traps are walked around.
+    fn bfs_path<F: Fn(&Location) -> bool>(&self, goal: F) -> Option<Vec<Location>> {
+        let start = self.objects[PLAYER].loc;
+        if goal(&start) {
+            return Some(vec![]);
+        }
+        let mut came_from = HashMap::new();
+        let mut queue = VecDeque::from(vec![start]);
+        came_from.insert(start, start);
+        while let Some(loc) = queue.pop_front() {
+            if goal(&loc) {
+                let mut path = vec![loc];
+                let mut current = loc;
+                while came_from[&current] != start {
+                    current = came_from[&current];
+                    path.push(current);
+                }
+                path.reverse();
+                return Some(path);
+            }
+            for dy in -1..=1 {
+                for dx in -1..=1 {
+                    let next = destination(&loc, &Direction(dx, dy));
+                    let known = self.map.get(&next).is_some_and(|tile| tile.explored);
+                    let walkable = !structure_blocks(&next, &self.map)
+                        && self.known_trap_at(&next).is_none()
+                        && (known || goal(&next));
+                    if walkable && !came_from.contains_key(&next) {
+                        came_from.insert(next, loc);
+                        queue.push_back(next);
+                    }
+                }
+            }
+        }
+        None
+    }
+
+    /// Wait until the player is fully healed or a monster shows up
+    pub fn rest(&mut self) {
+        let mut turns = 0;
+        let reason = loop {
+            let healthy = match self.objects[PLAYER].fighter {
+                Some(f) if !f.is_alive() => return,
+                Some(f) => f.health >= f.max_health,
+                None => return,
+            };
+            let threatened = self
+                .visible_fighters_by_distance(PLAYER, self.light_radius())
+                .iter()
+                .any(|&id| self.objects[id].ai.is_some());
+            if threatened {
+                break "You are interrupted.";
+            } else if healthy && turns == 0 {
+                break "You are already at full health.";
+            } else if healthy {
+                break "You feel rested.";
+            } else if turns >= REST_MAX_TURNS {
+                break "You can't find any more rest.";
+            }
+            self.update(Action::Wait(PLAYER));
+            turns += 1;
+        };
+        let summary = match turns {
+            0 => String::from(reason),
+            1 => format!("You rest for 1 turn. {}", reason),
+            n => format!("You rest for {} turns. {}", n, reason),
+        };
+        self.messages.add(summary, colors::WHITE);
+    }
+
+    /// Play the player's last action again
+    pub fn repeat_last_action(&mut self) {
+        match self.last_action {
+            Some(Action::Attack(_, target))
+                if !self
+                    .objects
+                    .get(target)
+                    .is_some_and(|o| o.alive && o.fighter.is_some()) =>
+            {
+                self.last_action = None;
+                self.messages
+                    .add("There is nothing left to attack.", colors::WHITE);
+            }
+            Some(action) => self.update(action),
+            None => self.messages.add("Nothing to repeat.", colors::WHITE),
+        }
+    }
+
+    /// Register custom logic to run at fixed points of every turn
+    pub fn add_hook(&mut self, hook: Box<dyn TurnHook>) {
+        self.hooks.push(hook);
+    }
+
+    /// Run all hooks, which may modify the game and register further hooks
+    fn run_hooks<F: FnMut(&mut dyn TurnHook, &mut Game)>(&mut self, mut f: F) {
+        let mut hooks = std::mem::take(&mut self.hooks);
+        for hook in hooks.iter_mut() {
+            f(hook.as_mut(), self);
+        }
+        hooks.append(&mut self.hooks);
+        self.hooks = hooks;
+    }
+
+    /// Unlock the debug commands and reveal the map
+    pub fn enable_wizard_mode(&mut self) {
+        self.wizard = true;
+        self.messages
+            .add("Wizard mode active.", colors::LIGHT_VIOLET);
+        self.cheat(Cheat::Reveal);
+    }
+
+    /// Execute a debug command
+    pub fn cheat(&mut self, cheat: Cheat) {
+        if !self.wizard {
+            self.messages
+                .add("Only wizards may do that.", colors::LIGHT_VIOLET);
+            return;
+        }
+
+        self.event_log
+            .record(self.turn, EntryKind::Event, format!("Cheat: {:?}", cheat));
+        let msgs = match cheat {
+            Cheat::Reveal => {
+                for tile in self.map.tiles_mut() {
+                    tile.explored = true;
+                }
+                Messages::new("The map is revealed.", colors::LIGHT_VIOLET)
+            }
+            Cheat::Godmode => {
+                self.godmode = !self.godmode;
+                let state = if self.godmode { "on" } else { "off" };
+                Messages::new(format!("Godmode {}.", state), colors::LIGHT_VIOLET)
+            }
+            Cheat::Teleport(loc) => {
+                if self.map.contains(&loc) && !self.is_blocked(&loc) {
+                    self.relocate(PLAYER, loc);
+                    Messages::new("You teleport.", colors::LIGHT_VIOLET)
+                } else {
+                    Messages::new("You cannot teleport there.", colors::LIGHT_VIOLET)
+                }
+            }
+            Cheat::Spawn(kind) => {
+                let Location(x, y) = self.objects[PLAYER].loc;
+                let free = (-1..=1)
+                    .flat_map(|dy| (-1..=1).map(move |dx| Location(x + dx, y + dy)))
+                    .find(|l| self.map.contains(l) && !self.is_blocked(l));
+                let monster = match kind.as_str() {
+                    "orc" => free.map(Object::orc),
+                    "troll" => free.map(Object::troll),
+                    "ogre" => free.map(Object::ogre),
+                    "shaman" => free.map(Object::shaman),
+                    "spider" => free.map(Object::spider),
+                    "archer" => free.map(Object::archer),
+                    "hermit" => free.map(Object::hermit),
+                    _ => None,
+                };
+                match (free, monster) {
+                    (None, _) => Messages::new("There is no room.", colors::LIGHT_VIOLET),
+                    (_, None) => {
+                        Messages::new(format!("Unknown monster: {}", kind), colors::LIGHT_VIOLET)
+                    }
+                    (_, Some(monster)) => {
+                        let msg = format!("{} appears.", indirect(&monster.name, true));
+                        self.spawn(monster);
+                        Messages::new(msg, colors::LIGHT_VIOLET)
+                    }
+                }
+            }
+            Cheat::Give(kind) => {
+                let loc = self.objects[PLAYER].loc;
+                let item = match kind.as_str() {
+                    "heal" => Some(Object::potion(loc, Item::Heal, "healing potion")),
+                    "lightning" => Some(Object::scroll(loc, Item::Lightning, "lightning bolt")),
+                    "confusion" => Some(Object::scroll(loc, Item::Confusion, "confusion")),
+                    "enchant" => Some(Object::scroll(loc, Item::Enchant, "enchantment")),
+                    "uncurse" => Some(Object::scroll(loc, Item::RemoveCurse, "remove curse")),
+                    "ration" => Some(Object::ration(loc)),
+                    "axe" => Some(Object::axe(loc)),
+                    "bow" => Some(Object::bow(loc)),
+                    "dagger" => Some(Object::dagger(loc)),
+                    "shield" => Some(Object::shield(loc)),
+                    "arrows" => Some(Object::arrows(loc, 10)),
+                    _ => None,
+                };
+                match item {
+                    Some(item) => {
+                        let id = self.spawn(item);
+                        pickup_item(PLAYER, id, self)
+                    }
+                    None => Messages::new(format!("Unknown item: {}", kind), colors::LIGHT_VIOLET),
+                }
+            }
+            Cheat::Heal => match self.objects[PLAYER].fighter.as_mut() {
+                Some(fighter) => {
+                    fighter.health = fighter.max_health;
+                    Messages::new("You are fully healed.", colors::LIGHT_VIOLET)
+                }
+                None => Messages::empty(),
+            },
+            Cheat::LightFalloff => {
+                self.light_falloff = !self.light_falloff;
+                Messages::empty()
+            }
+            Cheat::Fov(algorithm) => {
+                self.set_fov_algorithm(algorithm);
+                Messages::empty()
+            }
+            Cheat::Ambient => {
+                self.ambient = !self.ambient;
+                Messages::empty()
+            }
+            Cheat::CreaturesBlockSight => {
+                self.set_creatures_block_sight(!self.creatures_block_sight);
+                Messages::empty()
+            }
+            Cheat::Peaceful => {
+                self.peaceful = !self.peaceful;
+                let state = if self.peaceful { "on" } else { "off" };
+                Messages::new(format!("Peaceful {}.", state), colors::LIGHT_VIOLET)
+            }
+            Cheat::Explore => {
+                self.explore();
+                Messages::empty()
+            }
+            Cheat::Travel => {
+                self.travel_to_stairs();
+                Messages::empty()
+            }
+        };
+        self.messages.append(msgs);
+        self.refresh();
+    }
+}
+
+/// How serious a message is, which decides its color in the log
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MessageKind {
+    Info,
+    Combat,
+    Warning,
+    Danger,
+    Good,
+}
+
+impl MessageKind {
+    /// Harm is a danger to the player and just part of the fight for others
+    fn harm_to(id: Id) -> Self {
+        match id {
+            PLAYER => MessageKind::Danger,
+            _ => MessageKind::Combat,
+        }
+    }
+
+    pub fn color(self) -> Color {
+        match self {
+            MessageKind::Info => colors::WHITE,
+            MessageKind::Combat => colors::LIGHT_RED,
+            MessageKind::Warning => colors::YELLOW,
+            MessageKind::Danger => colors::RED,
+            MessageKind::Good => colors::GREEN,
+        }
+    }
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Messages {
+    messages: Vec<Message>,
+}
+
+impl Messages {
+    pub fn empty() -> Self {
+        Self { messages: vec![] }
+    }
+
+    pub fn new<T: Into<String>>(message: T, color: Color) -> Self {
+        let mut messages = Self::empty();
+        messages.add(message, color);
+        messages
+    }
+
+    /// A single message colored by its kind
+    pub fn log<T: Into<String>>(kind: MessageKind, message: T) -> Self {
+        Self::new(message, kind.color())
+    }
+
+    pub fn add<T: Into<String>>(&mut self, message: T, color: Color) {
+        self.messages.push((message.into(), color));
+    }
+
+    /// Add a message colored by its kind
+    pub fn push<T: Into<String>>(&mut self, kind: MessageKind, message: T) {
+        self.add(message, kind.color());
+    }
+
+    /// Add a message, or count it up if it repeats the last one, e.g. "It's blocked. (x3)"
+    pub fn add_counted<T: Into<String>>(&mut self, message: T, color: Color) {
+        let message = message.into();
+        if let Some((last, _)) = self.messages.last_mut() {
+            let count = if *last == message {
+                Some(1)
+            } else {
+                last.strip_prefix(message.as_str())
+                    .and_then(|rest| rest.strip_prefix(" (x"))
+                    .and_then(|rest| rest.strip_suffix(')'))
+                    .and_then(|n| n.parse::<i32>().ok())
+            };
+            if let Some(count) = count {
+                *last = format!("{} (x{})", message, count + 1);
+                return;
+            }
+        }
+        self.messages.push((message, color));
+    }
+
+    pub fn append(&mut self, other: Self) {
+        for (msg, color) in other.iter() {
+            self.messages.push((msg.into(), *color));
+        }
+    }
+
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = &(String, Color)> {
+        self.messages.iter()
+    }
+
+    /// The most recent message
+    pub fn latest(&self) -> Option<&Message> {
+        self.messages.last()
+    }
+
+    pub fn len(&self) -> usize {
+        self.messages.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.messages.is_empty()
+    }
+
+    /// The messages in a range, cut off at the end of the log
+    pub fn slice(&self, start: usize, end: usize) -> &[Message] {
+        let end = cmp::min(end, self.messages.len());
+        &self.messages[cmp::min(start, end)..end]
+    }
+}
+
+/// What happened when an action was resolved
+#[derive(Debug)]
+pub struct ActionOutcome {
+    pub messages: Messages,
+    /// Ids of the fighters that took fatal damage
+    pub killed: Vec<Id>,
+    /// The actor changed its location
+    pub moved: bool,
+    /// Damage dealt to the target
+    pub damage: i32,
+}
+
+impl From<Messages> for ActionOutcome {
+    fn from(messages: Messages) -> Self {
+        ActionOutcome {
+            messages,
+            killed: vec![],
+            moved: false,
+            damage: 0,
+        }
+    }
+}
+
+/// A hazard lingering over an area of the map
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AreaEffect {
+    pub name: String,
+    pub center: Location,
+    pub radius: i32,
+    /// Number of turns until the effect dissipates
+    pub turns: i32,
+    /// Damage dealt to each fighter inside per turn
+    pub damage: i32,
+    pub color: Color,
+}
+
+impl AreaEffect {
+    pub fn poison_gas(center: Location) -> Self {
+        AreaEffect {
+            name: String::from("poison gas"),
+            center,
+            radius: GAS_RADIUS,
+            turns: GAS_DURATION,
+            damage: GAS_DAMAGE,
+            color: colors::DARK_GREEN,
+        }
+    }
+
+    pub fn covers(&self, loc: &Location) -> bool {
+        distance(&self.center, loc) <= self.radius as f32
+    }
+}
+
+/// A short effect that is drawn for a few frames
+#[derive(Debug, Clone)]
+pub struct Animation {
+    pub tiles: Vec<Location>,
+    pub char: char,
+    pub color: Color,
+    /// Number of frames left to draw
+    pub frames: i32,
+}
+
+impl Animation {
+    /// A flash along the path of a lightning bolt
+    pub fn lightning(from: &Location, to: &Location) -> Self {
+        Animation {
+            tiles: line(from, to),
+            char: '*',
+            color: colors::LIGHT_BLUE,
+            frames: LIGHTNING_FRAMES,
+        }
+    }
+}
+
+/// Statistics of the current run
+#[derive(Debug, Default, Clone, Copy, Serialize, Deserialize)]
+pub struct Statistics {
+    pub damage_dealt: i32,
+    pub damage_taken: i32,
+    pub items_used: i32,
+    pub tiles_explored: i32,
+    pub steps_taken: i32,
+}
+
+impl Statistics {
+    /// Lines of text summarizing the run
+    pub fn summary(&self) -> Vec<String> {
+        vec![
+            format!("Damage dealt:   {}", self.damage_dealt),
+            format!("Damage taken:   {}", self.damage_taken),
+            format!("Items used:     {}", self.items_used),
+            format!("Tiles explored: {}", self.tiles_explored),
+            format!("Steps taken:    {}", self.steps_taken),
+        ]
+    }
+}
+
+/// Rough outcome of one fighter attacking another, leaving luck aside
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Odds {
+    /// Least and most damage a single hit deals
+    pub damage: (i32, i32),
+    /// Hits it takes to kill at average damage, `None` if no hit can hurt
+    pub hits_to_kill: Option<i32>,
+}
+
+impl std::fmt::Display for Odds {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let (min, max) = self.damage;
+        match self.hits_to_kill {
+            Some(hits) => write!(f, "{}-{} damage, ~{} hits", min, max, hits),
+            None => write!(f, "no damage"),
+        }
+    }
+}
+
+// --------------------------------- Objects ----------------------------------
+
+/// The 64 bit FNV-1a hash, which unlike the standard library's hasher is the
+/// same in every run
+struct Fnv(u64);
+
+impl Default for Fnv {
+    fn default() -> Self {
+        Fnv(0xcbf2_9ce4_8422_2325)
+    }
+}
+
+impl Hasher for Fnv {
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 ^= u64::from(*byte);
+            self.0 = self.0.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+
+    fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+/// A tile of the map and its properties
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct Tile {
+    pub blocked: bool,
+    pub block_sight: bool,
+    pub char: char,
+    pub explored: bool,
+    pub visible: bool,
+}
+
+impl Tile {
+    pub fn empty() -> Self {
+        Tile {
+            blocked: false,
+            block_sight: false,
+            char: '.',
+            explored: false,
+            visible: false,
+        }
+    }
+
+    pub fn wall() -> Self {
+        Tile {
+            blocked: true,
+            block_sight: true,
+            char: '#',
+            explored: false,
+            visible: false,
+        }
+    }
+
+    /// What the player sees when looking at the tile
+    pub fn name(&self) -> &'static str {
+        if self.blocked {
+            "a wall"
+        } else {
+            "the floor"
+        }
+    }
+}
+
+/// The tiles of a level, stored row by row
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Map {
+    tiles: Vec<Tile>,
+    width: i32,
+    height: i32,
+}
+
+impl Map {
+    /// A map of the given size, filled with one kind of tile
+    pub fn new(dimensions: Dimension, tile: Tile) -> Self {
+        let Dimension(width, height) = dimensions;
+        let (width, height) = (cmp::max(width, 0), cmp::max(height, 0));
+        Map {
+            tiles: vec![tile; (width * height) as usize],
+            width,
+            height,
+        }
+    }
+
+    pub fn dimensions(&self) -> Dimension {
+        Dimension(self.width, self.height)
+    }
+
+    /// The location lies on the map
+    pub fn contains(&self, loc: &Location) -> bool {
+        in_bounds(loc, &self.dimensions())
+    }
+
+    fn index(&self, loc: &Location) -> Option<usize> {
+        let Location(x, y) = *loc;
+        if self.contains(loc) {
+            Some((y * self.width + x) as usize)
+        } else {
+            None
+        }
+    }
+
+    /// The tile at a location, if it is on the map
+    pub fn get(&self, loc: &Location) -> Option<&Tile> {
+        self.index(loc).map(|i| &self.tiles[i])
+    }
+
+    /// The tile at a location for changing it, if it is on the map
+    pub fn get_mut(&mut self, loc: &Location) -> Option<&mut Tile> {
+        self.index(loc).map(move |i| &mut self.tiles[i])
+    }
+
+    /// Replace the tile at a location, nothing happens off the map
+    pub fn set(&mut self, loc: &Location, tile: Tile) {
+        if let Some(t) = self.get_mut(loc) {
+            *t = tile;
+        }
+    }
+
+    /// Locations of all tiles, row by row
+    pub fn iter_coords(&self) -> impl Iterator<Item = Location> {
+        let (width, height) = (self.width, self.height);
+        (0..height).flat_map(move |y| (0..width).map(move |x| Location(x, y)))
+    }
+
+    pub fn tiles(&self) -> impl Iterator<Item = &Tile> {
+        self.tiles.iter()
+    }
+
+    pub fn tiles_mut(&mut self) -> impl Iterator<Item = &mut Tile> {
+        self.tiles.iter_mut()
+    }
+}
+
+/// Generic object: the player, a monster, an item, the stairs...
+/// It's always represented by a character on screen.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Object {
+    pub loc: Location,
+    pub char: char,
+    pub color: Color,
+    pub name: String,
+
+    // Flags
+    pub blocks: bool,
+    pub visible: bool,
+    pub seen: bool,
+    pub alive: bool,
+    pub stairs: bool,
+    /// Leads up to the previous level
+    pub upstairs: bool,
+    /// Blocks the view when creatures block sight
+    pub tall: bool,
+    /// Gear that can't be taken off once worn, or a potion that harms
+    pub cursed: bool,
+    /// Objects on the same tile are drawn from the lowest to the highest
+    pub render_priority: RenderPriority,
+
+    // Components
+    pub movement: Option<Movement>,
+    pub fighter: Option<Fighter>,
+    pub ai: Option<Ai>,
+    pub noise: Option<Noise>,
+    pub item: Option<Item>,
+
+    /// Number of full turns until the object rots away
+    pub decay: Option<i32>,
+    /// Number of uses left of a rechargeable item
+    pub charges: Option<i32>,
+    /// Number of identical items in a stack, if the item stacks at all
+    pub count: Option<i32>,
+    /// What the object has to say when the player bumps into it
+    #[serde(with = "dialogue::by_name")]
+    pub dialogue: Option<Dialogue>,
+    pub equipment: Option<Equipment>,
+    pub trap: Option<Trap>,
+    /// Lasting conditions like poison or confusion
+    pub status: Vec<StatusEffect>,
+    /// Condition inflicted on whatever the object hits
+    pub venom: Option<StatusEffect>,
+    /// Full turns until the object starts starving, only the player gets hungry
+    pub hunger: Option<i32>,
+    /// What sets a monster apart from the others
+    pub abilities: Vec<Ability>,
+}
+
+impl Object {
+    pub fn new() -> Self {
+        Object {
+            char: '`',
+            name: "it".into(),
+            ..Default::default()
+        }
+    }
+    pub fn player(loc: Location, name: &str) -> Self {
+        let mut this = Object::new();
+        this.loc = loc;
+        this.name = String::from(name);
+        this.char = '@';
+        this.color = colors::YELLOW;
+
+        this.blocks = true;
+        this.alive = true;
+        this.render_priority = RenderPriority::Player;
+        this.visible = true;
+        this.seen = true;
+
+        this.movement = Some(Movement { speed: 100 });
+        this.fighter = Some(Fighter {
+            max_health: 30,
+            health: 30,
+            defense: 2,
+            power: 5,
+            on_death: DeathCallback::Player,
+            health_regen: 0.5,
+            xp: 0,
+            level: 1,
+        });
+        this.hunger = Some(HUNGER_MAX);
+
+        this
+    }
+    pub fn orc(loc: Location) -> Self {
+        let mut this = Object::new();
+        this.loc = loc;
+        this.name = String::from("orc");
+        this.char = 'o';
+        this.color = colors::GREEN;
+        this.blocks = true;
+        this.alive = true;
+        this.render_priority = RenderPriority::Creature;
+
+        this.ai = Some(Ai::Basic);
+        this.movement = Some(Movement { speed: 90 });
+        this.fighter = Some(Fighter {
+            max_health: 10,
+            health: 10,
+            defense: 0,
+            power: 3,
+            on_death: DeathCallback::Monster,
+            health_regen: 0.1,
+            xp: 0,
+            level: 1,
+        });
+        this.noise = Some(Noise {
+            bark: String::from("shout"),
+            mumble: String::from("mumble"),
+        });
+
+        this
+    }
+    pub fn troll(loc: Location) -> Self {
+        let mut this = Object::new();
+        this.loc = loc;
+        this.name = String::from("troll");
+        this.char = 'T';
+        this.color = colors::GREEN;
+        this.blocks = true;
+        this.alive = true;
+        this.render_priority = RenderPriority::Creature;
+        this.tall = true;
+
+        this.ai = Some(Ai::Basic);
+        this.movement = Some(Movement { speed: 80 });
+        this.fighter = Some(Fighter {
+            max_health: 16,
+            health: 16,
+            defense: 1,
+            power: 4,
+            on_death: DeathCallback::Monster,
+            health_regen: 0.1,
+            xp: 0,
+            level: 1,
+        });
+        this.noise = Some(Noise {
+            bark: String::from("roar"),
+            mumble: String::from("growl"),
+        });
+        this.abilities = vec![Ability::Regenerate { per_turn: 1 }];
+
+        this
+    }
+    pub fn ogre(loc: Location) -> Self {
+        let mut this = Object::new();
+        this.loc = loc;
+        this.name = String::from("ogre");
+        this.char = 'O';
+        this.color = colors::YELLOW;
+        this.blocks = true;
+        this.alive = true;
+        this.render_priority = RenderPriority::Creature;
+        this.tall = true;
+
+        this.ai = Some(Ai::Basic);
+        this.movement = Some(Movement { speed: 70 });
+        this.fighter = Some(Fighter {
+            max_health: 25,
+            health: 25,
+            defense: 2,
+            power: 8,
+            on_death: DeathCallback::Monster,
+            health_regen: 0.2,
+            xp: 0,
+            level: 1,
+        });
+        this.noise = Some(Noise {
+            bark: String::from("bellow"),
+            mumble: String::from("burp"),
+        });
+
+        this
+    }
+    pub fn potion<T: Into<String>>(loc: Location, item: Item, name: T) -> Self {
+        let mut this = Object::new();
+        this.loc = loc;
+        this.name = name.into();
+        this.char = '!';
+        this.color = colors::BLUE;
+        this.item = Some(item);
+        this.count = Some(1);
+        this.render_priority = RenderPriority::Item;
+
+        this
+    }
+    pub fn scroll<T: Into<String>>(loc: Location, item: Item, name: T) -> Self {
+        let mut this = Object::new();
+        this.loc = loc;
+        this.name = name.into();
+        this.char = '?';
+        this.color = colors::BLUE;
+        this.item = Some(item);
+        this.count = Some(1);
+        this.render_priority = RenderPriority::Item;
+
+        this
+    }
+    pub fn lamp<T: Into<String>>(loc: Location, item: Item, name: T) -> Self {
+        let mut this = Object::new();
+        this.loc = loc;
+        this.name = name.into();
+        this.char = '(';
+        this.color = colors::YELLOW;
+        this.item = Some(item);
+        this.render_priority = RenderPriority::Item;
+
+        this
+    }
+    pub fn ration(loc: Location) -> Self {
+        let mut this = Object::new();
+        this.loc = loc;
+        this.name = String::from("ration");
+        this.char = '%';
+        this.color = colors::DARK_ORANGE;
+        this.item = Some(Item::Food);
+        this.count = Some(1);
+        this.render_priority = RenderPriority::Item;
+
+        this
+    }
+    pub fn arrows(loc: Location, count: i32) -> Self {
+        let mut this = Object::new();
+        this.loc = loc;
+        this.name = String::from("arrow");
+        this.char = '|';
+        this.color = colors::LIGHT_SEPIA;
+        this.item = Some(Item::Arrow);
+        this.count = Some(count);
+        this.render_priority = RenderPriority::Item;
+
+        this
+    }
+    pub fn wand<T: Into<String>>(loc: Location, item: Item, name: T, charges: i32) -> Self {
+        let mut this = Object::new();
+        this.loc = loc;
+        this.name = with_charges(&name.into(), charges);
+        this.char = '/';
+        this.color = colors::LIGHT_BLUE;
+        this.item = Some(item);
+        this.charges = Some(charges);
+        this.render_priority = RenderPriority::Item;
+
+        this
+    }
+    /// Suffer from an effect, one of the same kind starts over
+    pub fn afflict(&mut self, effect: StatusEffect) {
+        self.status.retain(|e| !e.same_kind(&effect));
+        self.status.push(effect);
+    }
+    /// Stumbles around instead of going where it wants to
+    pub fn is_confused(&self) -> bool {
+        self.status
+            .iter()
+            .any(|e| matches!(e, StatusEffect::Confused { .. }))
+    }
+    /// How far the object shoots without a weapon, if it can shoot at all
+    pub fn shooting_range(&self) -> Option<i32> {
+        self.abilities.iter().find_map(|ability| match ability {
+            Ability::Ranged { range } => Some(*range),
+            _ => None,
+        })
+    }
+    /// Both are the same kind of item and go into one stack
+    pub fn stacks_with(&self, other: &Object) -> bool {
+        self.count.is_some()
+            && other.count.is_some()
+            && self.item == other.item
+            && self.name == other.name
+            && self.cursed == other.cursed
+    }
+    /// Name with the size of the stack, e.g. "healing potion (x3)"
+    pub fn stack_name(&self) -> String {
+        match self.count {
+            Some(count) if count > 1 => format!("{} (x{})", self.name, count),
+            _ => self.name.clone(),
+        }
+    }
+    pub fn shaman(loc: Location) -> Self {
+        let mut this = Object::new();
+        this.loc = loc;
+        this.name = String::from("shaman");
+        this.char = 's';
+        this.color = colors::LIGHT_VIOLET;
+        this.blocks = true;
+        this.alive = true;
+        this.render_priority = RenderPriority::Creature;
+
+        this.ai = Some(Ai::Caster { cooldown: 0 });
+        this.movement = Some(Movement { speed: 80 });
+        this.fighter = Some(Fighter {
+            max_health: 8,
+            health: 8,
+            defense: 0,
+            power: 2,
+            on_death: DeathCallback::Monster,
+            health_regen: 0.1,
+            xp: 0,
+            level: 1,
+        });
+        this.noise = Some(Noise {
+            bark: String::from("chant"),
+            mumble: String::from("hum"),
+        });
+
+        this
+    }
+    pub fn spider(loc: Location) -> Self {
+        let mut this = Object::new();
+        this.loc = loc;
+        this.name = String::from("giant spider");
+        this.char = 'S';
+        this.color = colors::DARK_AMBER;
+        this.blocks = true;
+        this.alive = true;
+        this.render_priority = RenderPriority::Creature;
+
+        this.ai = Some(Ai::Basic);
+        this.movement = Some(Movement { speed: 100 });
+        this.fighter = Some(Fighter {
+            max_health: 6,
+            health: 6,
+            defense: 0,
+            power: 2,
+            on_death: DeathCallback::Monster,
+            health_regen: 0.1,
+            xp: 0,
+            level: 1,
+        });
+        this.noise = Some(Noise {
+            bark: String::from("hisse"),
+            mumble: String::from("click"),
+        });
+        this.venom = Some(StatusEffect::Poisoned {
+            turns: POISON_NUM_TURNS,
+            per_turn: POISON_DAMAGE,
+        });
+
+        this
+    }
+    pub fn archer(loc: Location) -> Self {
+        let mut this = Object::new();
+        this.loc = loc;
+        this.name = String::from("goblin archer");
+        this.char = 'g';
+        this.color = colors::LIME;
+        this.blocks = true;
+        this.alive = true;
+        this.render_priority = RenderPriority::Creature;
+
+        this.ai = Some(Ai::Basic);
+        this.movement = Some(Movement { speed: 90 });
+        this.fighter = Some(Fighter {
+            max_health: 6,
+            health: 6,
+            defense: 0,
+            power: 3,
+            on_death: DeathCallback::Monster,
+            health_regen: 0.1,
+            xp: 0,
+            level: 1,
+        });
+        this.noise = Some(Noise {
+            bark: String::from("cackle"),
+            mumble: String::from("snicker"),
+        });
+        this.abilities = vec![Ability::Ranged {
+            range: ARCHER_RANGE,
+        }];
+
+        this
+    }
+    pub fn grave(loc: Location, name: &str) -> Self {
+        let mut this = Object::new();
+        this.loc = loc;
+        this.name = format!("grave of {}", name);
+        this.char = '+';
+        this.color = colors::WHITE;
+
+        this
+    }
+    pub fn stairs(loc: Location) -> Self {
+        let mut this = Object::new();
+        this.loc = loc;
+        this.name = String::from("staircase");
+        this.char = '>';
+        this.color = colors::WHITE;
+        this.stairs = true;
+
+        this
+    }
+    pub fn upstairs(loc: Location) -> Self {
+        let mut this = Object::new();
+        this.loc = loc;
+        this.name = String::from("upward staircase");
+        this.char = '<';
+        this.color = colors::WHITE;
+        this.upstairs = true;
+
+        this
+    }
+    pub fn trap(loc: Location, kind: TrapKind) -> Self {
+        let mut this = Object::new();
+        this.loc = loc;
+        this.name = match kind {
+            TrapKind::Spikes => String::from("spike trap"),
+            TrapKind::Confusion => String::from("confusion trap"),
+        };
+        this.char = '^';
+        this.color = colors::LIGHT_RED;
+        this.trap = Some(Trap { kind, hidden: true });
+
+        this
+    }
+    pub fn gear<T: Into<String>>(loc: Location, name: T, char: char, equipment: Equipment) -> Self {
+        let mut this = Object::new();
+        this.loc = loc;
+        this.name = name.into();
+        this.char = char;
+        this.color = colors::SKY;
+        this.equipment = Some(equipment);
+        this.render_priority = RenderPriority::Item;
+
+        this
+    }
+    pub fn sword(loc: Location) -> Self {
+        Object::gear(
+            loc,
+            "sword",
+            '/',
+            Equipment {
+                slot: Slot::Weapon,
+                power_bonus: 3,
+                defense_bonus: 0,
+                range: 0,
+                damage: None,
+                equipped: false,
+                enchantment: 0,
+            },
+        )
+    }
+    pub fn axe(loc: Location) -> Self {
+        Object::gear(
+            loc,
+            "battle axe",
+            '(',
+            Equipment {
+                slot: Slot::Weapon,
+                power_bonus: 0,
+                defense_bonus: 0,
+                range: 0,
+                damage: Some(AXE_DAMAGE),
+                equipped: false,
+                enchantment: 0,
+            },
+        )
+    }
+    pub fn armor(loc: Location) -> Self {
+        Object::gear(
+            loc,
+            "leather armor",
+            '[',
+            Equipment {
+                slot: Slot::Armor,
+                power_bonus: 0,
+                defense_bonus: 2,
+                range: 0,
+                damage: None,
+                equipped: false,
+                enchantment: 0,
+            },
+        )
+    }
+    pub fn dagger(loc: Location) -> Self {
+        Object::gear(
+            loc,
+            "dagger",
+            '-',
+            Equipment {
+                slot: Slot::OffHand,
+                power_bonus: 2,
+                defense_bonus: 0,
+                range: 0,
+                damage: None,
+                equipped: false,
+                enchantment: 0,
+            },
+        )
+    }
+    pub fn shield(loc: Location) -> Self {
+        Object::gear(
+            loc,
+            "wooden shield",
+            ']',
+            Equipment {
+                slot: Slot::OffHand,
+                power_bonus: 0,
+                defense_bonus: 1,
+                range: 0,
+                damage: None,
+                equipped: false,
+                enchantment: 0,
+            },
+        )
+    }
+    pub fn bow(loc: Location) -> Self {
+        Object::gear(
+            loc,
+            "bow",
+            '}',
+            Equipment {
+                slot: Slot::Weapon,
+                power_bonus: 0,
+                defense_bonus: 0,
+                range: BOW_RANGE,
+                damage: None,
+                equipped: false,
+                enchantment: 0,
+            },
+        )
+    }
+    pub fn hermit(loc: Location) -> Self {
+        let mut this = Object::new();
+        this.loc = loc;
+        this.name = String::from("hermit");
+        this.char = '@';
+        this.color = colors::LIGHT_GREEN;
+        this.blocks = true;
+        this.alive = true;
+        this.render_priority = RenderPriority::Creature;
+        this.dialogue = Some(dialogue::HERMIT);
+
+        this
+    }
+}
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct Fighter {
+    pub max_health: i32,
+    pub health: i32,
+    pub defense: i32,
+    pub power: i32,
+    pub on_death: DeathCallback,
+    pub health_regen: f32,
+    /// Experience gathered over all levels
+    pub xp: i32,
+    pub level: i32,
+}
+
+impl Fighter {
+    fn take_damage(&mut self, damage: i32) {
+        self.health -= damage;
+    }
+    /// There is health left, a fighter without any dies at the end of the
+    /// attack or the turn that took it
+    pub fn is_alive(&self) -> bool {
+        self.health > 0
+    }
+    fn heal(&mut self, amount: i32) {
+        self.health = cmp::min(self.health + amount, self.max_health);
+    }
+    /// Green when healthy, yellow when hurt and red when close to death
+    pub fn health_color(&self) -> Color {
+        let ratio = self.health as f32 / self.max_health as f32;
+        if ratio > 0.6 {
+            colors::GREEN
+        } else if ratio > 0.3 {
+            colors::YELLOW
+        } else {
+            colors::RED
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub enum DeathCallback {
+    Player,
+    Monster,
+}
+
+impl DeathCallback {
+    fn call(&self, object: &mut Object) -> Messages {
+        use DeathCallback::*;
+        match self {
+            Player => kill_player(object),
+            Monster => kill_monster(object),
+        }
+    }
+}
+
+/// Gear that can be worn in a slot for a bonus
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct Equipment {
+    pub slot: Slot,
+    pub power_bonus: i32,
+    pub defense_bonus: i32,
+    /// How far a ranged weapon shoots, 0 for melee weapons and other gear
+    pub range: i32,
+    /// Dice rolled for the damage instead of the wielder's power, armor is
+    /// taken off them in full
+    pub damage: Option<rng::Dice>,
+    pub equipped: bool,
+    /// Added to the bonus by scrolls of enchantment, and to the damage dice
+    #[serde(default)]
+    pub enchantment: i32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum Slot {
+    /// The main hand
+    Weapon,
+    /// Either a light weapon for a follow-up strike or a shield
+    OffHand,
+    Armor,
+    Ring,
+}
+
+/// How tough the caves are, chosen when starting a new game
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Difficulty {
+    Easy,
+    #[default]
+    Normal,
+    Hard,
+}
+
+impl Difficulty {
+    /// The next harder one, wrapping around to the easiest
+    pub fn next(self) -> Self {
+        match self {
+            Difficulty::Easy => Difficulty::Normal,
+            Difficulty::Normal => Difficulty::Hard,
+            Difficulty::Hard => Difficulty::Easy,
+        }
+    }
+
+    /// Percentage of the usual health and power monsters have
+    fn monster_strength(self) -> i32 {
+        match self {
+            Difficulty::Easy => 75,
+            Difficulty::Normal => 100,
+            Difficulty::Hard => 150,
+        }
+    }
+
+    /// Factor on how fast the player heals
+    fn player_regen(self) -> f32 {
+        match self {
+            Difficulty::Hard => 0.5,
+            _ => 1.0,
+        }
+    }
+
+    /// Make a monster as tough as the difficulty demands
+    pub fn adjust(self, object: &mut Object) {
+        let strength = self.monster_strength();
+        let scale = |value: i32| cmp::max(value * strength / 100, 1);
+        if let (Some(_), Some(fighter)) = (&object.ai, object.fighter.as_mut()) {
+            fighter.max_health = scale(fighter.max_health);
+            fighter.health = scale(fighter.health);
+            fighter.power = scale(fighter.power);
+        }
+    }
+}
+
+/// The colors of the floor and the walls of a level
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Theme {
+    pub unexplored: Color,
+    pub dark_wall: Color,
+    pub light_wall: Color,
+    pub dark_ground: Color,
+    pub light_ground: Color,
+}
+
+impl Theme {
+    /// Plain grey rock near the surface
+    pub const STONE: Theme = Theme {
+        unexplored: colors::BLACK,
+        dark_wall: colors::DARKEST_GREY,
+        light_wall: colors::DARKER_GREY,
+        dark_ground: colors::DARKER_GREY,
+        light_ground: colors::DARK_GREY,
+    };
+    /// Damp caves overgrown with moss
+    pub const MOSS: Theme = Theme {
+        unexplored: colors::BLACK,
+        dark_wall: Color::new(20, 35, 20),
+        light_wall: Color::new(40, 70, 40),
+        dark_ground: Color::new(35, 50, 30),
+        light_ground: Color::new(70, 95, 60),
+    };
+    /// Frozen halls deeper down
+    pub const ICE: Theme = Theme {
+        unexplored: colors::BLACK,
+        dark_wall: Color::new(20, 30, 45),
+        light_wall: Color::new(45, 65, 95),
+        dark_ground: Color::new(40, 55, 70),
+        light_ground: Color::new(80, 100, 130),
+    };
+    /// Scorched rock close to the fires below
+    pub const LAVA: Theme = Theme {
+        unexplored: colors::BLACK,
+        dark_wall: Color::new(40, 18, 15),
+        light_wall: Color::new(85, 35, 25),
+        dark_ground: Color::new(60, 35, 30),
+        light_ground: Color::new(110, 65, 50),
+    };
+
+    /// The themes from the surface down
+    const ALL: [Theme; 4] = [Theme::STONE, Theme::MOSS, Theme::ICE, Theme::LAVA];
+
+    /// The theme of a depth, fading from one theme into the next over a few
+    /// levels and staying with the last one below that
+    pub fn at_depth(depth: i32) -> Theme {
+        let position = cmp::max(depth - 1, 0) as f32 / THEME_DEPTHS as f32;
+        let index = position as usize;
+        match (Theme::ALL.get(index), Theme::ALL.get(index + 1)) {
+            (Some(&from), Some(&to)) => from.blend(to, position.fract()),
+            _ => Theme::LAVA,
+        }
+    }
+
+    /// A mix of two themes, from none to all of the other one
+    fn blend(self, other: Theme, coefficient: f32) -> Theme {
+        let mix = |a: Color, b: Color| colors::lerp(a, b, coefficient);
+        Theme {
+            unexplored: mix(self.unexplored, other.unexplored),
+            dark_wall: mix(self.dark_wall, other.dark_wall),
+            light_wall: mix(self.light_wall, other.light_wall),
+            dark_ground: mix(self.dark_ground, other.dark_ground),
+            light_ground: mix(self.light_ground, other.light_ground),
+        }
+    }
+}
+
+/// What is drawn on top when objects share a tile, from the bottom up
+#[derive(
+    Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize,
+)]
+pub enum RenderPriority {
+    /// Stairs, traps and other features of the floor
+    #[default]
+    Floor,
+    Corpse,
+    Item,
+    Creature,
+    Player,
+}
+
+/// Springs on whatever steps on it
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct Trap {
+    pub kind: TrapKind,
+    /// Looks like plain floor until it is sprung
+    pub hidden: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum TrapKind {
+    Spikes,
+    Confusion,
+}
+
+/// A lasting condition, resolved once per full turn
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum StatusEffect {
+    /// Loses health every turn
+    Poisoned { turns: i32, per_turn: i32 },
+    /// Gains health every turn
+    Regenerating { turns: i32, per_turn: i32 },
+    /// Stumbles around
+    Confused { turns: i32 },
+}
+
+impl StatusEffect {
+    /// Number of turns until the effect wears off
+    pub fn turns(&self) -> i32 {
+        match *self {
+            StatusEffect::Poisoned { turns, .. }
+            | StatusEffect::Regenerating { turns, .. }
+            | StatusEffect::Confused { turns } => turns,
+        }
+    }
+    fn turns_mut(&mut self) -> &mut i32 {
+        match self {
+            StatusEffect::Poisoned { turns, .. }
+            | StatusEffect::Regenerating { turns, .. }
+            | StatusEffect::Confused { turns } => turns,
+        }
+    }
+    /// Whoever suffers from the effect is ...
+    pub fn adjective(&self) -> &'static str {
+        match self {
+            StatusEffect::Poisoned { .. } => "poisoned",
+            StatusEffect::Regenerating { .. } => "regenerating",
+            StatusEffect::Confused { .. } => "confused",
+        }
+    }
+    fn same_kind(&self, other: &StatusEffect) -> bool {
+        std::mem::discriminant(self) == std::mem::discriminant(other)
+    }
+}
+
+/// Something special a monster can do
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum Ability {
+    /// Shoots at targets up to this many tiles away
+    Ranged { range: i32 },
+    /// Heals this much every full turn on top of the usual regeneration
+    Regenerate { per_turn: i32 },
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Noise {
+    pub bark: String,
+    pub mumble: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Movement {
+    pub speed: i32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum Item {
+    Heal,
+    Lightning,
+    Confusion,
+    Recall,
+    Lantern,
+    Candle,
+    PoisonGas,
+    /// Remains of a monster, can be eaten
+    Corpse,
+    Food,
+    /// Shot with a bow, one at a time
+    Arrow,
+    /// Raises the bonus of an equipped weapon or armor
+    Enchant,
+    /// Lifts the curses from the worn gear
+    RemoveCurse,
+}
+
+impl Item {
+    /// The player has to pick a target before the item can be used
+    pub fn needs_target(&self) -> bool {
+        matches!(self, Item::Lightning | Item::Confusion | Item::PoisonGas)
+    }
+}
+
+/// Spells cast by monsters
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub enum Spell {
+    Confuse,
+    Bolt,
+}
+
+// --------------------------------- Actions ----------------------------------
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub enum Action {
+    Move(Id, Direction),
+    Attack(Id, Id),
+    /// Attack whatever is on the adjacent tile without moving
+    AttackDirection(Id, Direction),
+    PickUp(Id, Id),
+    UseItem(Id, usize),
+    /// Use an item on a target, `None` if targeting was cancelled
+    UseItemOn(Id, usize, Option<Id>),
+    Equip(Id, usize),
+    Unequip(Id, usize),
+    Drop(Id, usize),
+    /// Read the scroll of enchantment at the first index on the gear at the
+    /// second
+    Enchant(Id, usize, usize),
+    /// Shoot the ranged weapon at a location
+    Shoot(Id, Location),
+    Cast(Id, Id, Spell),
+    Descend(Id),
+    /// Go back up to the previous level
+    Ascend(Id),
+    /// Write the game to the save file
+    Save,
+    Bark(Id),
+    Mumble(Id),
+    /// A wounded monster loses its nerve
+    Panic(Id),
+    Wait(Id),
+    Nothing,
+}
+
+/// What to improve when the player levels up
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub enum Bonus {
+    Constitution,
+    Strength,
+    Agility,
+}
+
+/// Debug commands that are only available in wizard mode
+#[derive(Debug)]
+pub enum Cheat {
+    Reveal,
+    Godmode,
+    Teleport(Location),
+    Spawn(String),
+    /// Put an item into the player's pack
+    Give(String),
+    /// Restore the player's health
+    Heal,
+    /// Let the light grow dimmer away from the player, or light it evenly
+    LightFalloff,
+    /// Switch the field of view algorithm
+    Fov(FovAlgorithm),
+    /// Show the explored parts of the map outside the field of view
+    Ambient,
+    /// Let creatures hide what stands behind them
+    CreaturesBlockSight,
+    /// Keep the monsters from acting
+    Peaceful,
+    /// Explore without pressing the key for it
+    Explore,
+    /// Travel to the stairs without pressing the key for it
+    Travel,
+}
+
+impl Action {
+    pub fn took_turn(&self) -> bool {
+        use Action::*;
+        match self {
+            Move(_, _) => true,
+            Attack(_, _) => true,
+            AttackDirection(_, _) => true,
+            PickUp(_, _) => true,
+            Bark(_) => true,
+            Mumble(_) => true,
+            Panic(_) => true,
+            Wait(_) => true,
+            UseItem(_, _) => false,
+            UseItemOn(_, _, _) => false,
+            Enchant(_, _, _) => false,
+            Equip(_, _) => true,
+            Unequip(_, _) => true,
+            Drop(_, _) => true,
+            Shoot(_, _) => true,
+            Cast(_, _, _) => true,
+            Descend(_) => true,
+            Ascend(_) => true,
+            Save => false,
+            Nothing => false,
+        }
+    }
+}
+
+/// Pick a move or attack action
+pub fn move_or_attack(id: Id, direction: Direction, game: &Game) -> (Option<Action>, Messages) {
+    let destination = destination(&game.objects[id].loc, &direction);
+    if game.object_blocks(&destination) {
+        game.first_fighter_at(&destination).map_or_else(
+            || (None, Messages::new("Cannot attack that.", colors::WHITE)),
+            |defender| (Some(Action::Attack(id, defender)), Messages::empty()),
+        )
+    } else if structure_blocks(&destination, &game.map) {
+        (None, Messages::new("It's blocked.", colors::WHITE))
+    } else {
+        (Some(Action::Move(id, direction)), Messages::empty())
+    }
+}
+
+/// Grab an object
+pub fn grab(id: Id, game: &Game) -> (Option<Action>, Messages) {
+    game.first_item_at(&game.objects[id].loc).map_or_else(
+        || {
+            (
+                None,
+                Messages::new("There is nothing here to pick up.", colors::WHITE),
+            )
+        },
+        |item_id| (Some(Action::PickUp(id, item_id)), Messages::empty()),
+    )
+}
+
+// ------------------------------- Resolution ---------------------------------
+
+enum UseResult {
+    UsedUp,
+    Cancelled,
+}
+
+/// Melee attack, followed up by the off-hand weapon if there is one
+fn melee(attacker: Id, defender: Id, game: &mut Game) -> ActionOutcome {
+    let mut outcome = attack(attacker, defender, game);
+    if game.off_hand_power(attacker).is_some() {
+        let follow_up = strike(attacker, defender, true, game);
+        outcome.messages.append(follow_up.messages);
+        outcome.killed.extend(follow_up.killed);
+        outcome.damage += follow_up.damage;
+    }
+    outcome
+}
+
+/// Attack resolution
+fn attack(attacker: Id, defender: Id, game: &mut Game) -> ActionOutcome {
+    strike(attacker, defender, false, game)
+}
+
+/// A single hit with the main hand or the off hand
+fn strike(attacker: Id, defender: Id, off_hand: bool, game: &mut Game) -> ActionOutcome {
+    // The target may have been removed or killed since the attack was
+    // chosen, and the dead don't fight back
+    if !game.objects.contains(attacker) || !game.objects.contains(defender) {
+        return Messages::empty().into();
+    }
+    if !game.objects[attacker].alive || !game.objects[defender].alive {
+        return Messages::empty().into();
+    }
+    let (power, dice) = if off_hand {
+        (game.off_hand_power(attacker).unwrap_or(0), None)
+    } else {
+        (game.power(attacker), game.damage_dice(attacker))
+    };
+    let defense = game.defense(defender);
+    let objects = &mut game.objects;
+    let stats = &mut game.stats;
+    let msg = match (attacker, defender) {
+        (PLAYER, d) if off_hand => {
+            format!("You follow up on {}", direct(&objects[d].name, false))
+        }
+        (PLAYER, d) => format!("You attack {}", direct(&objects[d].name, false)),
         (a, PLAYER) => format!("{} attacks you", direct(&objects[a].name, true)),
         (a, d) => format!(
             "{} attacks {}",
@@ -729,360 +3551,2841 @@ fn attack(attacker: usize, defender: usize, objects: &mut [Object]) -> Messages
         ),
     };
 
-    let damage = objects[attacker]
-        .fighter
-        .map(|fighter| rng::dx(fighter.power))
-        .and_then(|attack_damage| {
-            objects[defender]
-                .fighter
-                .map(|fighter| attack_damage - rng::dx(fighter.defense))
-        })
-        .unwrap_or(0);
+    let damage = match (objects[attacker].fighter, objects[defender].fighter) {
+        (Some(_), Some(_)) => match dice {
+            // Weapons deal what their dice say, minus what the armor stops
+            Some(dice) => dice.roll() - defense,
+            None => rng::dx(power) - rng::dx(defense),
+        },
+        _ => 0,
+    };
+
+    let loc = objects[defender].loc;
+    let messages = objects[defender]
+        .fighter
+        .as_mut()
+        .map(|fighter| {
+            if damage > 0 {
+                let msg = format!("{} for {} damage!", msg, damage);
+                fighter.take_damage(damage);
+                match (attacker, defender) {
+                    (PLAYER, _) => stats.damage_dealt += damage,
+                    (_, PLAYER) => stats.damage_taken += damage,
+                    _ => {}
+                }
+                Messages::log(MessageKind::Combat, msg)
+            } else {
+                let msg = match attacker {
+                    PLAYER => format!("{} but do no damage.", msg),
+                    _ => format!("{} but does no damage.", msg),
+                };
+                Messages::log(MessageKind::Combat, msg)
+            }
+        })
+        .unwrap_or_else(|| Messages::log(MessageKind::Info, "Cannot attack that!"));
+    let mut outcome = ActionOutcome::from(messages);
+    if damage > 0 {
+        game.emit_sound(SoundKind::Hit, loc);
+        outcome.damage = damage;
+        outcome.killed = killed(defender, game);
+        if let Some(venom) = game.objects[attacker].venom {
+            if outcome.killed.is_empty() && game.objects[defender].fighter.is_some() {
+                game.objects[defender].afflict(venom);
+                let text = match defender {
+                    PLAYER => format!("You are {}!", venom.adjective()),
+                    d => format!(
+                        "{} is {}!",
+                        direct(&game.objects[d].name, true),
+                        venom.adjective()
+                    ),
+                };
+                outcome.messages.push(MessageKind::harm_to(defender), text);
+            }
+        }
+    }
+    if !outcome.killed.is_empty() {
+        outcome.messages.append(slay(attacker, defender, game));
+    }
+    outcome
+}
+
+/// Let a fatally hurt fighter die and reward its killer with experience
+fn slay(killer: Id, victim: Id, game: &mut Game) -> Messages {
+    // Worth taking before the death takes away the fighter
+    let xp = game.objects[victim].fighter.map_or(0, |f| xp_value(&f));
+    let mut messages = game.die(victim);
+    if let Some(fighter) = game.objects[killer].fighter.as_mut() {
+        fighter.xp += xp;
+    }
+    if killer == PLAYER {
+        messages.push(
+            MessageKind::Good,
+            format!("You gain {} experience points.", xp),
+        );
+    }
+    messages
+}
+
+/// Attack the fighter on the adjacent tile, or swing at thin air
+fn attack_direction(id: Id, direction: Direction, game: &mut Game) -> ActionOutcome {
+    let destination = destination(&game.objects[id].loc, &direction);
+    match game.first_fighter_at(&destination) {
+        Some(target) => melee(id, target, game),
+        None if id == PLAYER => Messages::log(MessageKind::Info, "You attack thin air.").into(),
+        None => Messages::empty().into(),
+    }
+}
+
+/// Experience for killing a fighter
+fn xp_value(fighter: &Fighter) -> i32 {
+    fighter.max_health + 10 * fighter.power
+}
+
+/// Experience needed to advance from the given level to the next
+pub fn level_up_threshold(level: i32) -> i32 {
+    LEVEL_UP_BASE + (level - 1) * LEVEL_UP_FACTOR
+}
+
+/// The target if the damage it took was fatal
+fn killed(target: Id, game: &Game) -> Vec<Id> {
+    let object = &game.objects[target];
+    let immortal = target == PLAYER && game.godmode;
+    match object.fighter {
+        Some(fighter) if object.alive && !fighter.is_alive() && !immortal => vec![target],
+        _ => vec![],
+    }
+}
+
+/// Move resolution
+fn move_object(id: Id, direction: Direction, game: &mut Game) -> ActionOutcome {
+    let Direction(dx, dy) = direction;
+    let mut outcome = ActionOutcome::from(Messages::empty());
+    let should_move = game
+        .objects
+        .get(id)
+        .and_then(|o| o.movement.as_ref())
+        .is_some_and(|m| m.speed >= rng::d100());
+
+    if should_move {
+        let could_move = move_by(id, direction, game)
+            || move_by(id, Direction(dx, 0), game)
+            || move_by(id, Direction(0, dy), game);
+        if !could_move {
+            outcome.messages.add("The way is blocked!", colors::WHITE);
+        } else {
+            outcome.moved = true;
+            game.emit_sound(SoundKind::Footstep, game.objects[id].loc);
+            if id == PLAYER {
+                game.stats.steps_taken += 1;
+            }
+            let trap = game
+                .objects_at(&game.objects[id].loc)
+                .find_map(|(trap_id, o)| o.trap.map(|trap| (trap_id, trap)));
+            if let Some((trap_id, trap)) = trap {
+                let messages = spring_trap(id, trap_id, trap, game);
+                outcome.messages.append(messages);
+            }
+        }
+    }
+    outcome
+}
+
+/// Trigger a trap under a fighter and reveal it
+fn spring_trap(id: Id, trap_id: Id, trap: Trap, game: &mut Game) -> Messages {
+    if game.objects[id].fighter.is_none() {
+        return Messages::empty();
+    }
+    if let Some(t) = game.objects[trap_id].trap.as_mut() {
+        t.hidden = false;
+    }
+    game.objects[trap_id].seen = true;
+
+    let victim = match id {
+        PLAYER => String::from("You"),
+        _ => direct(&game.objects[id].name, true),
+    };
+    let text = match trap.kind {
+        TrapKind::Spikes => {
+            if let Some(fighter) = game.objects[id].fighter.as_mut() {
+                fighter.take_damage(TRAP_DAMAGE);
+            }
+            if id == PLAYER {
+                game.stats.damage_taken += TRAP_DAMAGE;
+            }
+            format!(
+                "{} {} on a spike trap for {} damage!",
+                victim,
+                if id == PLAYER { "step" } else { "steps" },
+                TRAP_DAMAGE
+            )
+        }
+        TrapKind::Confusion => {
+            game.objects[id].afflict(StatusEffect::Confused {
+                turns: CONFUSE_NUM_TURNS,
+            });
+            format!(
+                "{} {} on a confusion trap and a cloud of spores bursts out!",
+                victim,
+                if id == PLAYER { "step" } else { "steps" },
+            )
+        }
+    };
+    if id == PLAYER || game.visible(&game.objects[id].loc) {
+        Messages::log(MessageKind::harm_to(id), text)
+    } else {
+        Messages::empty()
+    }
+}
+
+/// Shoot at whatever stands on the target location
+fn shoot(id: Id, target: Location, game: &mut Game) -> ActionOutcome {
+    let range = match (game.ranged_weapon(id), game.objects[id].shooting_range()) {
+        (Some(weapon), _) => weapon.range,
+        (None, Some(range)) => range,
+        (None, None) => {
+            return Messages::new("You have nothing to shoot with.", colors::WHITE).into()
+        }
+    };
+    // The player's bow needs arrows, monsters bring their own
+    let quiver = game
+        .inventory
+        .iter()
+        .position(|o| o.item == Some(Item::Arrow));
+    let needs_arrows = id == PLAYER && game.ranged_weapon(id).is_some();
+    if needs_arrows && game.arrows() == 0 {
+        return Messages::new("Out of arrows.", colors::WHITE).into();
+    }
+    let loc = game.objects[id].loc;
+    if !game.visible(&target) || chebyshev_distance(&loc, &target) > range {
+        return Messages::new("That is out of range.", colors::WHITE).into();
+    }
+    if !has_line_of_sight(&loc, &target, &game.map) {
+        return Messages::new("There is no clear shot.", colors::WHITE).into();
+    }
+    if let (true, Some(index)) = (needs_arrows, quiver) {
+        match game.inventory[index].count.as_mut() {
+            Some(count) if *count > 1 => *count -= 1,
+            _ => {
+                game.inventory.remove(index);
+            }
+        }
+        if rng::chance(ARROW_RECOVERY_CHANCE) {
+            game.spawn(Object::arrows(target, 1));
+        }
+    }
+    match game.first_fighter_at(&target).filter(|&d| d != id) {
+        Some(defender) => attack(id, defender, game),
+        None => Messages::log(MessageKind::Info, "You shoot at nothing.").into(),
+    }
+}
+
+/// Pick up item
+fn pickup_item(actor: Id, item_id: Id, game: &mut Game) -> Messages {
+    let mut messages = Messages::empty();
+    if !game.objects.contains(item_id) {
+        messages.add("There is nothing here to pick up.", colors::WHITE);
+        return messages;
+    }
+    let stack = game
+        .inventory
+        .iter()
+        .position(|o| o.stacks_with(&game.objects[item_id]));
+    if stack.is_none() && game.inventory.len() >= game.inventory_capacity {
+        messages.add("Inventory full", colors::WHITE);
+    } else {
+        let msg = match actor {
+            PLAYER => format!("You pick up {}.", some(&game.objects[item_id], false)),
+            _ => format!(
+                "{} picks up {}.",
+                direct(&game.objects[actor].name, true),
+                some(&game.objects[item_id], false)
+            ),
+        };
+        messages.add(msg, colors::WHITE);
+
+        let item = game.despawn(item_id);
+        game.emit_sound(SoundKind::Pickup, item.loc);
+        match stack {
+            Some(i) => {
+                let count = game.inventory[i].count.unwrap_or(1) + item.count.unwrap_or(1);
+                game.inventory[i].count = Some(count);
+            }
+            None => game.inventory.push(item),
+        }
+    }
+    messages
+}
+
+/// Use an item
+fn use_item(id: Id, item_id: usize, target: Option<Id>, game: &mut Game) -> Messages {
+    if game.inventory[item_id].charges == Some(0) {
+        return Messages::new(
+            "Nothing happens. The wand is out of charges.",
+            colors::WHITE,
+        );
+    }
+    game.inventory[item_id]
+        .item
+        .map(|i| match (i, target) {
+            (Item::Heal, _) => cast_heal(id, item_id, game),
+            (Item::Lightning, Some(target)) => cast_lightning(id, target, game),
+            (Item::Confusion, Some(target)) => cast_confusion(id, target, game),
+            (Item::PoisonGas, Some(target)) => throw_gas(id, target, game),
+            (Item::Lightning, None) | (Item::Confusion, None) | (Item::PoisonGas, None) => (
+                UseResult::Cancelled,
+                Messages::new("Cancelled.", colors::WHITE),
+            ),
+            (Item::Recall, _) => cast_recall(id, item_id, game),
+            (Item::Lantern, _) => light_lantern(id, item_id, game),
+            (Item::Candle, _) => light_candle(id, item_id, game),
+            (Item::Corpse, _) => eat_corpse(id, item_id, game),
+            (Item::Food, _) => eat_food(id, item_id, game),
+            (Item::Arrow, _) => (
+                UseResult::Cancelled,
+                Messages::new("Arrows are shot with a bow.", colors::WHITE),
+            ),
+            (Item::Enchant, _) => read_enchant(game),
+            (Item::RemoveCurse, _) => remove_curse(id, game),
+        })
+        .map(|r| match r {
+            (UseResult::UsedUp, messages) => {
+                use_up(item_id, game);
+                messages
+            }
+            (UseResult::Cancelled, messages) => messages,
+        })
+        .unwrap_or_else(Messages::empty)
+}
+
+/// Take a charge, or one of a stack, or the whole item
+fn use_up(item_id: usize, game: &mut Game) {
+    let item = &mut game.inventory[item_id];
+    match item.charges.as_mut() {
+        Some(charges) => {
+            *charges -= 1;
+            item.name = with_charges(&item.name, *charges);
+        }
+        None => match item.count.as_mut() {
+            Some(count) if *count > 1 => *count -= 1,
+            _ => {
+                game.inventory.remove(item_id);
+            }
+        },
+    }
+    game.stats.items_used += 1;
+}
+
+/// Raise the bonus of a piece of equipped gear with a scroll
+fn enchant(id: Id, scroll: usize, gear: usize, game: &mut Game) -> Messages {
+    let is_scroll = game.inventory.get(scroll).and_then(|o| o.item) == Some(Item::Enchant);
+    if id != PLAYER || !is_scroll || !game.enchantable().contains(&gear) {
+        return Messages::new("You can't enchant that.", colors::WHITE);
+    }
+    let item = &mut game.inventory[gear];
+    let equipment = item.equipment.as_mut().expect("Enchantable gear");
+    if equipment.enchantment >= ENCHANT_CAP {
+        return Messages::new(
+            format!(
+                "{} can't be enchanted any further.",
+                direct(&item.name, true)
+            ),
+            colors::WHITE,
+        );
+    }
+    equipment.enchantment += 1;
+    match equipment.slot {
+        Slot::Armor => equipment.defense_bonus += 1,
+        _ => equipment.power_bonus += 1,
+    }
+    item.name = with_enchantment(&item.name, equipment.enchantment);
+    let msg = format!("{} glows brightly.", direct(&item.name, true));
+    use_up(scroll, game);
+    Messages::new(msg, colors::LIGHT_BLUE)
+}
+
+/// Cast a spell at a target
+fn cast_spell(id: Id, target: Id, spell: Spell, game: &mut Game) -> ActionOutcome {
+    let caster = direct(&game.objects[id].name, true);
+    match (spell, target) {
+        (Spell::Confuse, target) => {
+            game.objects[target].afflict(StatusEffect::Confused {
+                turns: CONFUSE_NUM_TURNS,
+            });
+            let text = match target {
+                PLAYER => format!("{} gestures and you feel dizzy.", caster),
+                target => format!(
+                    "{} gestures and {} looks confused.",
+                    caster,
+                    direct(&game.objects[target].name, false)
+                ),
+            };
+            Messages::new(text, colors::LIGHT_VIOLET).into()
+        }
+        (Spell::Bolt, target) => match game.objects[target].fighter.as_mut() {
+            Some(fighter) => {
+                fighter.take_damage(MAGIC_BOLT_DAMAGE);
+                if target == PLAYER {
+                    game.stats.damage_taken += MAGIC_BOLT_DAMAGE;
+                }
+                let victim = match target {
+                    PLAYER => String::from("you"),
+                    t => direct(&game.objects[t].name, false),
+                };
+                let mut outcome = ActionOutcome::from(Messages::log(
+                    MessageKind::Combat,
+                    format!(
+                        "{} hurls a magic bolt at {} for {} damage!",
+                        caster, victim, MAGIC_BOLT_DAMAGE
+                    ),
+                ));
+                outcome.damage = MAGIC_BOLT_DAMAGE;
+                outcome.killed = killed(target, game);
+                if !outcome.killed.is_empty() {
+                    outcome.messages.append(game.die(target));
+                }
+                outcome
+            }
+            None => Messages::empty().into(),
+        },
+    }
+}
+
+/// Go down the stairs the actor stands on
+fn take_stairs(id: Id, game: &mut Game) -> Messages {
+    let on_stairs = game
+        .objects_at(&game.objects[id].loc)
+        .any(|(_, o)| o.stairs);
+    if id != PLAYER {
+        Messages::empty()
+    } else if !on_stairs {
+        Messages::new("There are no stairs here.", colors::WHITE)
+    } else {
+        game.descend()
+    }
+}
+
+/// Go up the stairs the actor stands on
+fn climb_stairs(id: Id, game: &mut Game) -> Messages {
+    let on_stairs = game
+        .objects_at(&game.objects[id].loc)
+        .any(|(_, o)| o.upstairs);
+    if id != PLAYER {
+        Messages::empty()
+    } else if !on_stairs {
+        Messages::new("There are no stairs leading up here.", colors::WHITE)
+    } else {
+        game.ascend()
+    }
+}
+
+/// Wear a piece of gear from the inventory, replacing what is in its slot
+fn equip(id: Id, item_id: usize, game: &mut Game) -> Messages {
+    let mut messages = Messages::empty();
+    let slot = match game.inventory[item_id].equipment {
+        Some(equipment) if id == PLAYER => equipment.slot,
+        _ => return Messages::new("You can't equip that.", colors::WHITE),
+    };
+    if let Some(current) = game
+        .inventory
+        .iter()
+        .position(|o| o.equipment.is_some_and(|e| e.equipped && e.slot == slot))
+    {
+        if stuck(current, game) {
+            return Messages::new("You can't remove it! It's cursed!", colors::RED);
+        }
+        messages.append(unequip(id, current, game));
+    }
+    if let Some(equipment) = game.inventory[item_id].equipment.as_mut() {
+        equipment.equipped = true;
+    }
+    messages.add(
+        format!(
+            "You equip {}.",
+            direct(&game.inventory[item_id].name, false)
+        ),
+        colors::LIGHT_GREEN,
+    );
+    messages
+}
+
+/// Take off a piece of gear
+fn unequip(id: Id, item_id: usize, game: &mut Game) -> Messages {
+    if stuck(item_id, game) {
+        return Messages::new("You can't remove it! It's cursed!", colors::RED);
+    }
+    match game.inventory[item_id].equipment.as_mut() {
+        Some(equipment) if id == PLAYER && equipment.equipped => {
+            equipment.equipped = false;
+            Messages::new(
+                format!(
+                    "You take off {}.",
+                    direct(&game.inventory[item_id].name, false)
+                ),
+                colors::LIGHT_YELLOW,
+            )
+        }
+        _ => Messages::empty(),
+    }
+}
+
+/// Cursed gear that is worn and can't come off
+fn stuck(item_id: usize, game: &Game) -> bool {
+    let item = &game.inventory[item_id];
+    item.cursed && item.equipment.is_some_and(|e| e.equipped)
+}
+
+/// Drop an item from the inventory
+fn drop_item(id: Id, item_id: usize, game: &mut Game) -> Messages {
+    if stuck(item_id, game) {
+        return Messages::new("You can't remove it! It's cursed!", colors::RED);
+    }
+    let mut item = game.inventory.remove(item_id);
+    if let Some(equipment) = item.equipment.as_mut() {
+        equipment.equipped = false;
+    }
+    let msg = match id {
+        PLAYER => format!("You drop {}.", some(&item, false)),
+        _ => format!(
+            "{} drops {}.",
+            direct(&game.objects[id].name, true),
+            some(&item, false)
+        ),
+    };
+    let loc = game.objects[id].loc;
+    place_item(item, &loc, game);
+    Messages::new(msg, colors::WHITE)
+}
+
+/// Put an item on the floor at or close to a location
+fn place_item(mut item: Object, loc: &Location, game: &mut Game) {
+    item.loc = free_floor(loc, &game.map, &game.objects).unwrap_or(*loc);
+    game.spawn(item);
+}
+
+fn bark(id: Id, objects: &Objects) -> Messages {
+    objects[id]
+        .noise
+        .as_ref()
+        .map(|Noise { bark, .. }| {
+            Messages::new(
+                format!("{} {}s.", indirect(&objects[id].name, true), bark),
+                colors::WHITE,
+            )
+        })
+        .unwrap_or_else(Messages::empty)
+}
+
+/// Wake up the idle monsters that hear a noise and send them to its origin
+///
+/// Noise goes around corners, so the monsters don't need to see where it
+/// came from. Returns the monsters that were alerted.
+pub fn propagate_noise(origin: Location, radius: i32, game: &mut Game) -> Vec<Id> {
+    let alerted: Vec<Id> = game
+        .objects
+        .iter()
+        .filter(|(_, o)| matches!(o.ai, Some(Ai::Idle)))
+        .filter(|(_, o)| distance(&o.loc, &origin) <= radius as f32)
+        .map(|(id, _)| id)
+        .collect();
+    for &id in &alerted {
+        game.objects[id].ai = Some(Ai::Hunting {
+            last_seen: origin,
+            patience: ai::HUNT_PATIENCE,
+        });
+    }
+    alerted
+}
+
+fn mumble(id: Id, objects: &Objects) -> Messages {
+    objects[id]
+        .noise
+        .as_ref()
+        .map(|Noise { mumble, .. }| {
+            Messages::new(
+                format!("{} {}s.", indirect(&objects[id].name, true), mumble),
+                colors::WHITE,
+            )
+        })
+        .unwrap_or_else(Messages::empty)
+}
+
+fn panic(id: Id, objects: &Objects) -> Messages {
+    Messages::new(
+        format!("{} turns to flee!", indirect(&objects[id].name, true)),
+        colors::WHITE,
+    )
+}
+
+fn kill_player(player: &mut Object) -> Messages {
+    let mut messages = Messages::empty();
+    let msg = "You die!";
+    player.alive = false;
+    player.char = '%';
+    player.color = colors::RED;
+
+    messages.push(MessageKind::Danger, msg);
+    messages
+}
+
+fn kill_monster(monster: &mut Object) -> Messages {
+    let mut messages = Messages::empty();
+    monster.alive = false;
+    let msg = format!("{} dies.", direct(&monster.name, true));
+
+    monster.char = '%';
+    monster.color = colors::RED;
+    monster.blocks = false;
+    monster.fighter = None;
+    monster.ai = None;
+    monster.name = format!("Remains of {}", monster.name);
+    monster.item = Some(Item::Corpse);
+    monster.render_priority = RenderPriority::Corpse;
+
+    messages.push(MessageKind::Combat, msg);
+    messages
+}
+
+fn regenerate(object: &mut Object) -> Messages {
+    let bonus: i32 = object
+        .abilities
+        .iter()
+        .map(|ability| match ability {
+            Ability::Regenerate { per_turn } => *per_turn,
+            _ => 0,
+        })
+        .sum();
+    if let Some(f) = object.fighter.as_mut() {
+        let amount = match f.health_regen {
+            p if p <= 1.0 => rng::chance(p) as i32,
+            v => v as i32,
+        };
+        f.heal(amount + bonus);
+    }
+    Messages::empty()
+}
+
+// --------------------------------- Movement ----------------------------------
+/// Distance between two points
+pub fn distance(a: &Location, b: &Location) -> f32 {
+    let Location(ax, ay) = a;
+    let Location(bx, by) = b;
+    let (dx, dy) = (bx - ax, by - ay);
+    ((dx.pow(2) + dy.pow(2)) as f32).sqrt()
+}
+
+/// Number of moves between two points when diagonal moves are allowed
+pub fn chebyshev_distance(a: &Location, b: &Location) -> i32 {
+    let Location(ax, ay) = a;
+    let Location(bx, by) = b;
+    cmp::max((bx - ax).abs(), (by - ay).abs())
+}
+
+/// The tiles on a straight line between two points, without the start
+pub fn line(from: &Location, to: &Location) -> Vec<Location> {
+    let (Location(fx, fy), Location(tx, ty)) = (*from, *to);
+    let steps = chebyshev_distance(from, to);
+    (1..=steps)
+        .map(|step| {
+            let t = step as f32 / steps as f32;
+            let x = fx as f32 + (tx - fx) as f32 * t;
+            let y = fy as f32 + (ty - fy) as f32 * t;
+            Location(x.round() as i32, y.round() as i32)
+        })
+        .collect()
+}
+
+/// Nothing that blocks the view lies on the straight line between two points
+///
+/// The end points themselves may be opaque, so that a wall can be seen.
+pub fn has_line_of_sight(from: &Location, to: &Location, map: &Map) -> bool {
+    line(from, to)
+        .iter()
+        .filter(|loc| *loc != to)
+        .all(|loc| map.get(loc).is_some_and(|tile| !tile.block_sight))
+}
+
+/// Calculate normalized direction between two points
+pub fn direction(a: &Location, b: &Location) -> Direction {
+    let Location(ax, ay) = a;
+    let Location(bx, by) = b;
+
+    let dx = match bx - ax {
+        x if x < 0 => -1,
+        x if x > 0 => 1,
+        _ => 0,
+    };
+    let dy = match by - ay {
+        y if y < 0 => -1,
+        y if y > 0 => 1,
+        _ => 0,
+    };
+    Direction(dx, dy)
+}
+
+/// Find the first step along the shortest path between two points
+///
+/// Other objects that block are treated as walls, so monsters will go
+/// around each other. Paths that are much longer than the direct route are
+/// rejected to keep monsters from wandering off on long detours.
+///
+/// The search asks the map and the spatial index about each tile it
+/// visits and never strays further than such a detour, so it costs the same
+/// however large the map is.
+pub fn path_to(from: &Location, to: &Location, game: &Game) -> Option<Direction> {
+    let walkable = |loc: &Location| {
+        chebyshev_distance(from, loc) <= PATH_MAX_LENGTH
+            && !structure_blocks(loc, &game.map)
+            && (loc == from || loc == to || !game.object_blocks(loc))
+    };
+    let cost = |_: (i32, i32), (x, y): (i32, i32)| {
+        if walkable(&Location(x, y)) {
+            1.0
+        } else {
+            0.0
+        }
+    };
+
+    let Dimension(width, height) = game.map.dimensions();
+    let (Location(fx, fy), Location(tx, ty)) = (*from, *to);
+    let mut path = AStar::new_from_callback(width, height, cost, 1.41);
+    if !path.find((fx, fy), (tx, ty)) || path.is_empty() || path.len() > PATH_MAX_LENGTH {
+        return None;
+    }
+    path.walk_one_step(false)
+        .map(|(x, y)| direction(from, &Location(x, y)))
+}
+
+/// Get the destination when moving from a location in a given direction
+pub fn destination(location: &Location, direction: &Direction) -> Location {
+    let Location(x, y) = location;
+    let Direction(dx, dy) = direction;
+    Location(x + dx, y + dy)
+}
+
+/// Move by the given amount
+fn move_by(id: Id, direction: Direction, game: &mut Game) -> bool {
+    let destination = destination(&game.objects[id].loc, &direction);
+    if in_bounds(&destination, &game.map.dimensions()) && !game.is_blocked(&destination) {
+        game.relocate(id, destination);
+        true
+    } else {
+        false
+    }
+}
+
+// -------------------------------- Collision ---------------------------------
+/// Check if and object is at this position
+pub fn object_blocks(loc: &Location, objects: &Objects) -> bool {
+    objects
+        .values()
+        .filter(|object| object.blocks)
+        .any(|object| &object.loc == loc)
+}
+
+/// Check if a structure blocks at this position, the edge of the map does
+fn structure_blocks(loc: &Location, map: &Map) -> bool {
+    map.get(loc).is_none_or(|tile| tile.blocked)
+}
+
+/// The location is on a map of the given size
+pub fn in_bounds(loc: &Location, dimensions: &Dimension) -> bool {
+    let (Location(x, y), Dimension(width, height)) = (*loc, *dimensions);
+    x >= 0 && y >= 0 && x < width && y < height
+}
+
+/// Center of a view along one axis, such that it stays within the map
+///
+/// A map smaller than the view is centered in it.
+fn clamp_view(focus: i32, view: i32, map: i32) -> i32 {
+    let center = view / 2 + 1;
+    if map <= view {
+        map / 2
+    } else {
+        focus.clamp(center, map - view + center)
+    }
+}
+
+/// Find the closest fighter within range
+pub fn fighters_by_distance(id: Id, objects: &Objects, range: i32) -> Vec<Id> {
+    let loc = &objects[id].loc;
+    let mut in_range: Vec<(i32, Id)> = objects
+        .iter()
+        .filter(|&(i, _)| i != id) // don't target yourself
+        .filter(|(_, o)| o.fighter.is_some()) // only target fighters
+        .map(|(i, o)| (chebyshev_distance(loc, &o.loc), i)) // get the distance
+        .filter(|&(d, _)| d <= range) // only targets in range
+        .collect(); // collect into a vector to enable sorting
+    in_range.sort_by_key(|(d, _)| -d); // descending sort by distance
+    in_range.iter().map(|(_, i)| i).cloned().collect()
+}
+
+/// Find a random fighter within range and line of sight
+fn random_fighter(id: Id, game: &Game, range: i32) -> Option<Id> {
+    let objects = &game.objects;
+    let loc = &objects[id].loc;
+    let targets: Vec<Id> = objects
+        .iter()
+        .map(|(i, o)| (i, &o.loc))
+        .filter(|(i, _)| *i != id)
+        .filter(|(_, l)| distance(loc, l) <= range as f32)
+        .filter(|(_, l)| has_line_of_sight(loc, l, &game.map))
+        .map(|(i, _)| i)
+        .filter(|&t| objects[t].fighter.is_some())
+        .collect();
+    rng::choose(&targets).cloned()
+}
+
+/// Find the closest floor tile that does not hold an item yet
+fn free_floor(loc: &Location, map: &Map, objects: &Objects) -> Option<Location> {
+    let Location(x, y) = *loc;
+    (0..=SPILL_RADIUS).find_map(|radius| {
+        (-radius..=radius)
+            .flat_map(|dy| (-radius..=radius).map(move |dx| Location(x + dx, y + dy)))
+            .filter(|l| map.contains(l))
+            .filter(|l| !structure_blocks(l, map))
+            .find(|l| !objects.values().any(|o| &o.loc == l && o.item.is_some()))
+    })
+}
+
+/// Name of a piece of gear with its enchantment, e.g. "sword +2"
+fn with_enchantment(name: &str, enchantment: i32) -> String {
+    let base = name.split(" +").next().unwrap_or(name);
+    format!("{} +{}", base, enchantment)
+}
+
+/// Name of a charged item with the number of charges left
+fn with_charges(name: &str, charges: i32) -> String {
+    let base = name.split(" (").next().unwrap_or(name);
+    format!("{} ({} charges)", base, charges)
+}
+
+/// A single item with its article, or a whole stack of them
+fn some(object: &Object, upper: bool) -> String {
+    match object.count {
+        Some(count) if count > 1 => object.stack_name(),
+        _ => indirect(&object.name, upper),
+    }
+}
+
+fn indirect(it: &str, upper: bool) -> String {
+    let an = "aeiou".chars().find(|&c| it.starts_with(c)).is_some();
+
+    let article = match (upper, an) {
+        (true, true) => "An",
+        (false, true) => "an",
+        (true, false) => "A",
+        (false, false) => "a",
+    };
+    format!("{} {}", article, it)
+}
+
+fn direct(it: &str, upper: bool) -> String {
+    let article = if upper { "The" } else { "the" };
+    format!("{} {}", article, it)
+}
+
+// --------------------------- Items and Abilities ----------------------------
+fn cast_heal(id: Id, item_id: usize, game: &mut Game) -> (UseResult, Messages) {
+    if game.inventory[item_id].cursed {
+        return match game.objects[id].fighter.as_mut() {
+            Some(fighter) => {
+                fighter.take_damage(CURSED_POTION_DAMAGE);
+                game.stats.damage_taken += CURSED_POTION_DAMAGE;
+                (
+                    UseResult::UsedUp,
+                    Messages::log(MessageKind::Danger, "The potion burns! It was cursed."),
+                )
+            }
+            None => (
+                UseResult::Cancelled,
+                Messages::log(MessageKind::Info, "Only fighters can drink!"),
+            ),
+        };
+    }
+    game.objects[id]
+        .fighter
+        .as_mut()
+        .map(|fighter| {
+            if fighter.health == fighter.max_health {
+                (
+                    UseResult::Cancelled,
+                    Messages::log(MessageKind::Info, "Already at full health!"),
+                )
+            } else {
+                fighter.heal(HEAL_AMOUNT);
+                (
+                    UseResult::UsedUp,
+                    Messages::log(MessageKind::Good, "Healed!"),
+                )
+            }
+        })
+        .unwrap_or_else(|| {
+            (
+                UseResult::Cancelled,
+                Messages::log(MessageKind::Info, "Only fighters can drink!"),
+            )
+        })
+}
+
+fn eat_corpse(id: Id, item_id: usize, game: &mut Game) -> (UseResult, Messages) {
+    let name = game.inventory[item_id].name.to_lowercase();
+    let fighter = match game.objects[id].fighter.as_mut() {
+        Some(fighter) => fighter,
+        None => {
+            return (
+                UseResult::Cancelled,
+                Messages::new("Only fighters can eat!", colors::WHITE),
+            )
+        }
+    };
+    let messages = if rng::chance(CORPSE_POISON_CHANCE) {
+        fighter.take_damage(CORPSE_POISON_DAMAGE);
+        if id == PLAYER {
+            game.stats.damage_taken += CORPSE_POISON_DAMAGE;
+        }
+        Messages::log(
+            MessageKind::Danger,
+            format!("You eat the {}. It has gone bad and you feel sick!", name),
+        )
+    } else {
+        fighter.heal(CORPSE_HEAL_AMOUNT);
+        Messages::log(
+            MessageKind::Good,
+            format!("You eat the {}. It's chewy but filling.", name),
+        )
+    };
+    feed(id, CORPSE_NUTRITION, game);
+    (UseResult::UsedUp, messages)
+}
+
+fn eat_food(id: Id, item_id: usize, game: &mut Game) -> (UseResult, Messages) {
+    let name = game.inventory[item_id].name.to_lowercase();
+    match game.objects[id].hunger {
+        Some(hunger) if hunger >= HUNGER_MAX => (
+            UseResult::Cancelled,
+            Messages::new("You are too full to eat.", colors::WHITE),
+        ),
+        Some(_) => {
+            feed(id, RATION_NUTRITION, game);
+            (
+                UseResult::UsedUp,
+                Messages::new(format!("You eat the {}. Delicious!", name), colors::WHITE),
+            )
+        }
+        None => (
+            UseResult::Cancelled,
+            Messages::new("Only the hungry can eat!", colors::WHITE),
+        ),
+    }
+}
+
+/// Still some of the hunger of an object
+fn feed(id: Id, nutrition: i32, game: &mut Game) {
+    if let Some(hunger) = game.objects[id].hunger.as_mut() {
+        *hunger = cmp::min(*hunger + nutrition, HUNGER_MAX);
+    }
+}
+
+fn cast_lightning(id: Id, target: Id, game: &mut Game) -> (UseResult, Messages) {
+    if !in_range(id, target, LIGHTNING_RANGE, game) {
+        return (
+            UseResult::Cancelled,
+            Messages::new("The target is out of range.", colors::WHITE),
+        );
+    }
+    game.objects[target]
+        .fighter
+        .as_mut()
+        .expect("Target must be a fighter")
+        .take_damage(LIGHTNING_DAMAGE);
+    if id == PLAYER {
+        game.stats.damage_dealt += LIGHTNING_DAMAGE;
+    }
+    let bolt = Animation::lightning(&game.objects[id].loc, &game.objects[target].loc);
+    game.animations.push(bolt);
+    let mut messages = Messages::log(
+        MessageKind::Combat,
+        format!("You zap {} ", direct(&game.objects[target].name, false)),
+    );
+    if !killed(target, game).is_empty() {
+        messages.append(slay(id, target, game));
+    }
+    (UseResult::UsedUp, messages)
+}
+
+fn cast_confusion(id: Id, target: Id, game: &mut Game) -> (UseResult, Messages) {
+    if !in_range(id, target, CONFUSE_RANGE, game) {
+        return (
+            UseResult::Cancelled,
+            Messages::new("The target is out of range.", colors::WHITE),
+        );
+    }
+    game.objects[target].afflict(StatusEffect::Confused {
+        turns: CONFUSE_NUM_TURNS,
+    });
+    (
+        UseResult::UsedUp,
+        Messages::new(
+            format!(
+                "{} looks confused.",
+                direct(&game.objects[target].name, true)
+            ),
+            colors::WHITE,
+        ),
+    )
+}
+
+/// The target is a living fighter within range and sight of the user
+fn in_range(id: Id, target: Id, range: i32, game: &Game) -> bool {
+    let (user, target) = (&game.objects[id], &game.objects[target]);
+    target.fighter.is_some_and(|f| f.health > 0)
+        && target.ai.is_some()
+        && chebyshev_distance(&user.loc, &target.loc) <= range
+        && has_line_of_sight(&user.loc, &target.loc, &game.map)
+}
+
+fn throw_gas(id: Id, target: Id, game: &mut Game) -> (UseResult, Messages) {
+    if !in_range(id, target, GAS_RANGE, game) {
+        return (
+            UseResult::Cancelled,
+            Messages::new("The target is out of range.", colors::WHITE),
+        );
+    }
+    let center = game.objects[target].loc;
+    game.effects.push(AreaEffect::poison_gas(center));
+    (
+        UseResult::UsedUp,
+        Messages::new(
+            format!(
+                "The flask shatters next to {} and a green cloud billows out.",
+                direct(&game.objects[target].name, false)
+            ),
+            colors::GREEN,
+        ),
+    )
+}
+
+/// Lift the curses from all the gear the player wears
+fn remove_curse(id: Id, game: &mut Game) -> (UseResult, Messages) {
+    if id != PLAYER {
+        return (UseResult::Cancelled, Messages::empty());
+    }
+    let mut lifted = 0;
+    for item in game.inventory.iter_mut() {
+        if item.cursed && item.equipment.is_some_and(|e| e.equipped) {
+            item.cursed = false;
+            lifted += 1;
+        }
+    }
+    let text = match lifted {
+        0 => "You feel as if you need some help.",
+        _ => "You feel like someone is helping you.",
+    };
+    (UseResult::UsedUp, Messages::new(text, colors::LIGHT_BLUE))
+}
+
+/// A scroll of enchantment read without choosing the gear, wasted if there is
+/// nothing to enchant
+fn read_enchant(game: &Game) -> (UseResult, Messages) {
+    if game.enchantable().is_empty() {
+        (
+            UseResult::UsedUp,
+            Messages::new("You have nothing to enchant.", colors::WHITE),
+        )
+    } else {
+        (
+            UseResult::Cancelled,
+            Messages::new("Choose what to enchant.", colors::WHITE),
+        )
+    }
+}
+
+fn cast_recall(id: Id, _item_id: usize, game: &mut Game) -> (UseResult, Messages) {
+    if id != PLAYER {
+        (UseResult::Cancelled, Messages::empty())
+    } else if game.recall.is_some() {
+        (
+            UseResult::Cancelled,
+            Messages::new("The air is already humming.", colors::WHITE),
+        )
+    } else {
+        game.recall = Some(RECALL_DELAY);
+        (
+            UseResult::UsedUp,
+            Messages::new("The air hums around you...", colors::LIGHT_BLUE),
+        )
+    }
+}
+
+fn light_lantern(id: Id, _item_id: usize, game: &mut Game) -> (UseResult, Messages) {
+    if id != PLAYER {
+        (UseResult::Cancelled, Messages::empty())
+    } else {
+        game.torch_radius += LANTERN_BONUS;
+        (
+            UseResult::UsedUp,
+            Messages::new("The lantern pushes back the darkness.", colors::YELLOW),
+        )
+    }
+}
+
+fn light_candle(id: Id, _item_id: usize, game: &mut Game) -> (UseResult, Messages) {
+    if id != PLAYER {
+        (UseResult::Cancelled, Messages::empty())
+    } else {
+        game.candle = CANDLE_DURATION;
+        (
+            UseResult::UsedUp,
+            Messages::new(
+                "The candle's smoke smothers your torch. The darkness creeps closer.",
+                colors::YELLOW,
+            ),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dungeon::Layout;
+
+    fn game() -> Game {
+        Game::new(
+            "Tester",
+            Layout::new(Dimension(40, 30), Dimension(6, 8), 10, 0, 0),
+            1,
+            Difficulty::Normal,
+        )
+    }
+
+    /// An open map with only the player, at (10, 10), and no monster turns
+    fn arena() -> Game {
+        let mut game = game();
+        game.clear_objects();
+        game.relocate(PLAYER, Location(10, 10));
+        game.set_map(Map::new(Dimension(40, 30), Tile::empty()));
+        game.peaceful = true;
+        game
+    }
+
+    /// A fighter that takes a long time to wear down and never heals
+    fn sturdy(mut object: Object) -> Object {
+        if let Some(fighter) = object.fighter.as_mut() {
+            fighter.health = 1000;
+            fighter.max_health = 1000;
+            fighter.health_regen = 0.0;
+        }
+        object
+            .abilities
+            .retain(|a| !matches!(a, Ability::Regenerate { .. }));
+        object
+    }
+
+    #[test]
+    fn dealing_damage_counts_damage_dealt() {
+        let mut game = game();
+        let Location(x, y) = game.objects[PLAYER].loc;
+        let orc = game.spawn(Object::orc(Location(x + 1, y)));
+
+        game.play(&vec![Action::Attack(PLAYER, orc)]);
+
+        let fighter = game.objects[orc].fighter.unwrap();
+        assert!(game.stats.damage_dealt > 0);
+        assert_eq!(game.stats.damage_dealt, fighter.max_health - fighter.health);
+        assert_eq!(game.stats.damage_taken, 0);
+    }
+
+    #[test]
+    fn fatal_attacks_report_the_kill() {
+        let mut game = game();
+        let Location(x, y) = game.objects[PLAYER].loc;
+        let orc = game.spawn(Object::orc(Location(x + 1, y)));
+        if let Some(fighter) = game.objects[orc].fighter.as_mut() {
+            fighter.health = 1;
+        }
+
+        let outcome = loop {
+            let mut outcomes = game.play(&vec![Action::Attack(PLAYER, orc)]);
+            let outcome = outcomes.pop().unwrap();
+            if outcome.damage > 0 {
+                break outcome;
+            }
+            assert!(outcome.killed.is_empty());
+        };
+
+        assert_eq!(outcome.killed, vec![orc]);
+        assert!(!outcome.moved);
+    }
+
+    #[test]
+    fn a_killing_blow_stops_the_target_from_striking_back() {
+        let mut game = game();
+        let Location(x, y) = game.objects[PLAYER].loc;
+        let orc = game.spawn(Object::orc(Location(x + 1, y)));
+        if let Some(fighter) = game.objects[orc].fighter.as_mut() {
+            fighter.health = 1;
+            fighter.defense = 0;
+        }
+
+        let outcomes = game.play(&vec![
+            Action::Attack(PLAYER, orc),
+            Action::Attack(orc, PLAYER),
+        ]);
+
+        assert_eq!(outcomes[0].killed, vec![orc]);
+        assert!(!game.objects[orc].alive);
+        assert!(outcomes[1].messages.is_empty());
+        assert_eq!(game.stats.damage_taken, 0);
+    }
+
+    #[test]
+    fn fingerprint_ignores_refreshes() {
+        let mut game = game();
+        let before = game.fingerprint();
+
+        game.refresh();
+        assert_eq!(game.fingerprint(), before);
+
+        game.update(Action::Wait(PLAYER));
+        assert_ne!(game.fingerprint(), before);
+    }
+
+    #[test]
+    fn fingerprint_covers_the_map() {
+        let mut game = arena();
+        let before = game.fingerprint();
+
+        game.map.set(&Location(20, 20), Tile::wall());
+        assert_ne!(game.fingerprint(), before);
+
+        game.map.set(&Location(20, 20), Tile::empty());
+        assert_eq!(game.fingerprint(), before);
+    }
+
+    #[test]
+    fn fnv_hashes_like_the_reference() {
+        let hash = |bytes: &[u8]| {
+            let mut hasher = Fnv::default();
+            hasher.write(bytes);
+            hasher.finish()
+        };
+        assert_eq!(hash(b""), 0xcbf2_9ce4_8422_2325);
+        assert_eq!(hash(b"a"), 0xaf63_dc4c_8601_ec8c);
+    }
+
+    #[test]
+    fn potions_stack_in_one_slot() {
+        let mut game = game();
+        game.inventory_capacity = 1;
+        game.objects[PLAYER].fighter.as_mut().unwrap().health = 1;
+        let loc = game.objects[PLAYER].loc;
+        for _ in 0..3 {
+            let potion = game.spawn(Object::potion(loc, Item::Heal, "healing potion"));
+            game.update(Action::PickUp(PLAYER, potion));
+        }
+        let scroll = game.spawn(Object::scroll(loc, Item::Confusion, "confusion"));
+        game.update(Action::PickUp(PLAYER, scroll));
+
+        assert_eq!(game.inventory.len(), 1);
+        assert_eq!(game.inventory[0].stack_name(), "healing potion (x3)");
+        assert!(game.objects.contains(scroll));
+
+        game.update(Action::UseItem(PLAYER, 0));
+        assert_eq!(game.inventory[0].count, Some(2));
+        assert_eq!(game.inventory[0].stack_name(), "healing potion (x2)");
+    }
+
+    #[test]
+    fn maps_of_any_size_can_be_set() {
+        let mut game = game();
+        game.clear_objects();
+        game.relocate(PLAYER, Location(70, 50));
+        game.set_map(Map::new(Dimension(80, 60), Tile::empty()));
+        assert!(game.visible(&Location(72, 52)));
+        assert!(in_bounds(&Location(79, 59), &game.map.dimensions()));
+
+        game.relocate(PLAYER, Location(5, 5));
+        game.set_map(Map::new(Dimension(20, 10), Tile::empty()));
+        assert!(game.visible(&Location(6, 6)));
+        assert!(!in_bounds(&Location(20, 5), &game.map.dimensions()));
+    }
+
+    #[test]
+    fn bows_shoot_what_is_in_sight() {
+        let mut game = arena();
+        game.map.set(&Location(0, 0), Tile::wall());
+        let orc = game.spawn(Object::orc(Location(14, 14)));
+        let far = game.spawn(Object::orc(Location(10 + BOW_RANGE + 1, 10)));
+        game.inventory.push(Object::bow(Location(0, 0)));
+        game.inventory.push(Object::arrows(Location(0, 0), 10));
+        game.update(Action::Equip(PLAYER, 0));
+        game.refresh();
+
+        let turn = game.turn;
+        game.update(Action::Shoot(PLAYER, Location(12, 10)));
+        assert_eq!(game.turn, turn + 1);
+        assert!(game
+            .messages
+            .iter()
+            .any(|(text, _)| text == "You shoot at nothing."));
+
+        game.update(Action::Shoot(PLAYER, Location(10 + BOW_RANGE + 1, 10)));
+        assert!(game.objects.contains(far));
+        assert_eq!(game.objects[far].fighter.unwrap().health, 10);
+
+        // Regenerating in the meantime must not save it
+        game.objects[orc].fighter.as_mut().unwrap().health = 1;
+        game.update(Action::Shoot(PLAYER, Location(14, 14)));
+        assert!(game.objects.get(orc).is_none_or(|o| o.fighter.is_none()));
+    }
+
+    #[test]
+    fn bows_use_up_arrows() {
+        let mut game = arena();
+        game.inventory.push(Object::bow(Location(0, 0)));
+        game.inventory.push(Object::arrows(Location(0, 0), 2));
+        game.update(Action::Equip(PLAYER, 0));
+        game.refresh();
+
+        let target = Location(13, 10);
+        game.update(Action::Shoot(PLAYER, target));
+        assert_eq!(game.arrows(), 1);
+        game.update(Action::Shoot(PLAYER, target));
+        assert_eq!(game.arrows(), 0);
+        assert!(game.inventory.iter().all(|o| o.item != Some(Item::Arrow)));
+
+        game.update(Action::Shoot(PLAYER, target));
+        assert!(game
+            .messages
+            .iter()
+            .any(|(text, _)| text == "Out of arrows."));
+
+        // Whatever could be recovered lies where it was shot
+        let recovered: Vec<Id> = game
+            .objects_at(&target)
+            .filter(|(_, o)| o.item == Some(Item::Arrow))
+            .map(|(id, _)| id)
+            .collect();
+        assert!(recovered.len() <= 2);
+        game.relocate(PLAYER, target);
+        for &arrow in &recovered {
+            game.update(Action::PickUp(PLAYER, arrow));
+        }
+        assert_eq!(game.arrows(), recovered.len() as i32);
+    }
+
+    #[test]
+    fn wounded_fighters_turn_red() {
+        let mut fighter = Object::orc(Location(0, 0)).fighter.unwrap();
+        assert_eq!(fighter.health_color(), colors::GREEN);
+        fighter.take_damage(5);
+        assert_eq!(fighter.health_color(), colors::YELLOW);
+        fighter.take_damage(3);
+        assert_eq!(fighter.health_color(), colors::RED);
+    }
+
+    #[test]
+    fn lookups_skip_what_does_not_matter() {
+        let mut game = game();
+        let Location(x, y) = game.objects[PLAYER].loc;
+        let loc = Location(x + 1, y);
+        let potion = game.spawn(Object::potion(loc, Item::Heal, "healing potion"));
+        let orc = game.spawn(Object::orc(loc));
+
+        assert_eq!(game.first_fighter_at(&loc), Some(orc));
+        assert_eq!(game.first_item_at(&loc), Some(potion));
+        assert!(matches!(
+            move_or_attack(PLAYER, Direction(1, 0), &game),
+            (Some(Action::Attack(PLAYER, target)), _) if target == orc
+        ));
+    }
+
+    #[test]
+    fn looking_names_the_topmost_thing() {
+        let mut game = arena();
+        game.map.set(&Location(12, 10), Tile::wall());
+        game.spawn(Object::potion(
+            Location(11, 10),
+            Item::Heal,
+            "healing potion",
+        ));
+        game.spawn(Object::troll(Location(11, 10)));
+        game.spawn(Object::stairs(Location(11, 11)));
+        game.spawn(Object::scroll(
+            Location(11, 11),
+            Item::Recall,
+            "word of recall",
+        ));
+        let mut orc = Object::orc(Location(11, 11));
+        kill_monster(&mut orc);
+        game.spawn(orc);
+        game.refresh();
+
+        assert_eq!(game.describe(&Location(10, 10)), "yourself");
+        assert_eq!(game.describe(&Location(11, 11)), "a word of recall");
+        assert_eq!(game.describe(&Location(11, 10)), "a troll");
+        assert_eq!(game.describe(&Location(12, 10)), "a wall");
+        assert_eq!(game.describe(&Location(10, 11)), "the floor");
+    }
+
+    #[test]
+    fn enchanting_raises_the_power_of_a_weapon() {
+        let mut game = game();
+        game.inventory.clear();
+        let scroll = || Object::scroll(Location(0, 0), Item::Enchant, "enchantment");
+        game.inventory.push(scroll());
+        game.update(Action::UseItem(PLAYER, 0));
+        assert!(game.inventory.is_empty());
+        assert!(game
+            .messages
+            .iter()
+            .any(|(text, _)| text == "You have nothing to enchant."));
+
+        game.inventory.push(Object::sword(Location(0, 0)));
+        game.update(Action::Equip(PLAYER, 0));
+        let power = game.power(PLAYER);
+        for _ in 0..ENCHANT_CAP + 1 {
+            game.inventory.push(scroll());
+        }
+        assert_eq!(game.enchantable(), vec![0]);
+        for enchantment in 1..=ENCHANT_CAP {
+            game.update(Action::Enchant(PLAYER, 1, 0));
+            assert_eq!(game.power(PLAYER), power + enchantment);
+            assert_eq!(game.inventory[0].name, format!("sword +{}", enchantment));
+        }
+        assert_eq!(game.inventory.len(), 2);
+
+        // The last scroll is kept once the cap is reached
+        game.update(Action::Enchant(PLAYER, 1, 0));
+        assert_eq!(game.power(PLAYER), power + ENCHANT_CAP);
+        assert_eq!(game.inventory.len(), 2);
+    }
+
+    #[test]
+    fn sight_reports_distance_and_cover() {
+        let mut game = arena();
+        game.map.set(&Location(12, 10), Tile::wall());
+        game.refresh();
+
+        assert_eq!(
+            game.describe_sight(&Location(11, 11)),
+            "1 tile away, line of sight clear."
+        );
+        assert_eq!(
+            game.describe_sight(&Location(10, 14)),
+            "4 tiles away, line of sight clear."
+        );
+        // The wall itself can be seen, but not what is behind it
+        assert_eq!(
+            game.describe_sight(&Location(12, 10)),
+            "2 tiles away, line of sight clear."
+        );
+        assert_eq!(
+            game.describe_sight(&Location(14, 10)),
+            "4 tiles away, line of sight blocked."
+        );
+    }
+
+    #[test]
+    fn odds_estimate_fights_both_ways() {
+        let mut game = arena();
+        game.inventory.clear();
+        let orc = game.spawn(Object::orc(Location(12, 10)));
+        let set = |fighter: &mut Fighter, health, power, defense| {
+            fighter.health = health;
+            fighter.power = power;
+            fighter.defense = defense;
+        };
+        set(game.objects[PLAYER].fighter.as_mut().unwrap(), 30, 5, 2);
+        set(game.objects[orc].fighter.as_mut().unwrap(), 10, 4, 1);
+        game.refresh();
+
+        let yours = game.odds(PLAYER, orc).unwrap();
+        assert_eq!(yours.damage, (0, 4));
+        assert_eq!(yours.hits_to_kill, Some(5));
+        let theirs = game.odds(orc, PLAYER).unwrap();
+        assert_eq!(theirs.damage, (0, 3));
+        assert_eq!(theirs.hits_to_kill, Some(20));
+        assert_eq!(
+            game.describe_odds(&Location(12, 10)).unwrap(),
+            "You deal 0-4 damage, ~5 hits, it deals 0-3 damage, ~20 hits"
+        );
+        assert_eq!(game.describe_odds(&Location(11, 10)), None);
+
+        // Weapons go by their dice
+        game.inventory.push(Object::gear(
+            Location(0, 0),
+            "sword",
+            '/',
+            Equipment {
+                slot: Slot::Weapon,
+                power_bonus: 0,
+                defense_bonus: 0,
+                range: 0,
+                damage: Some("1d6+1".parse().unwrap()),
+                equipped: false,
+                enchantment: 0,
+            },
+        ));
+        game.update(Action::Equip(PLAYER, 0));
+        let yours = game.odds(PLAYER, orc).unwrap();
+        assert_eq!(yours.damage, (1, 6));
+        assert_eq!(yours.hits_to_kill, Some(3));
+
+        game.objects[orc].fighter.as_mut().unwrap().power = 0;
+        assert_eq!(game.odds(orc, PLAYER).unwrap().hits_to_kill, None);
+    }
+
+    #[test]
+    fn remains_can_be_looted_and_eaten() {
+        let mut game = arena();
+        let orc = game.spawn(Object::orc(Location(10, 10)));
+        game.objects[orc].fighter.as_mut().unwrap().health = 0;
+        game.refresh();
+        assert_eq!(game.objects[orc].item, Some(Item::Corpse));
+
+        let (pickup, _) = grab(PLAYER, &game);
+        game.update(pickup.unwrap());
+        assert_eq!(game.inventory.len(), 1);
+        assert_eq!(game.inventory[0].name, "Remains of orc");
+
+        game.update(Action::UseItem(PLAYER, 0));
+        assert!(game.inventory.is_empty());
+        assert_eq!(game.stats.items_used, 1);
+    }
+
+    #[test]
+    fn the_map_edge_blocks() {
+        let mut game = game();
+        game.set_map(Map::new(Dimension(40, 30), Tile::empty()));
+        game.relocate(PLAYER, Location(0, 0));
+        if let Some(movement) = game.objects[PLAYER].movement.as_mut() {
+            movement.speed = 100;
+        }
+
+        for direction in [Direction(-1, 0), Direction(0, -1), Direction(-1, -1)].iter() {
+            let (action, _) = move_or_attack(PLAYER, *direction, &game);
+            assert!(action.is_none());
+            game.update(Action::Move(PLAYER, *direction));
+            assert_eq!(game.objects[PLAYER].loc, Location(0, 0));
+        }
+        assert!(game.map.get(&Location(40, 0)).is_none());
+        assert!(!in_bounds(&Location(0, 30), &game.map.dimensions()));
+    }
+
+    #[test]
+    fn fights_wake_up_monsters_nearby() {
+        let mut game = game();
+        let mut map = Map::new(Dimension(40, 30), Tile::empty());
+        for y in 0..30 {
+            map.set(&Location(15, y), Tile::wall());
+        }
+        game.set_map(map);
+        game.relocate(PLAYER, Location(10, 10));
+        let orc = game.spawn(Object::orc(Location(11, 10)));
+        let near = game.spawn(Object::orc(Location(17, 10)));
+        let far = game.spawn(Object::orc(Location(30, 10)));
+        for id in [near, far].iter() {
+            game.objects[*id].ai = Some(Ai::Idle);
+        }
+
+        game.play(&vec![Action::Attack(PLAYER, orc)]);
+
+        assert!(matches!(
+            game.objects[near].ai,
+            Some(Ai::Hunting {
+                last_seen: Location(10, 10),
+                ..
+            })
+        ));
+        assert!(matches!(game.objects[far].ai, Some(Ai::Idle)));
+        assert_eq!(propagate_noise(Location(30, 10), 1, &mut game), vec![far]);
+    }
+
+    #[test]
+    fn tall_creatures_can_block_the_view() {
+        let mut game = arena();
+        let ogre = game.spawn(Object::ogre(Location(12, 10)));
+        game.spawn(Object::orc(Location(10, 12)));
+        game.refresh();
+        assert!(game.visible(&Location(14, 10)));
+
+        game.set_creatures_block_sight(true);
+        assert!(game.visible(&Location(12, 10)));
+        assert!(!game.visible(&Location(14, 10)));
+        assert!(game.visible(&Location(10, 14)));
+
+        game.relocate(ogre, Location(12, 12));
+        game.refresh();
+        assert!(game.visible(&Location(14, 10)));
+        assert!(!game.visible(&Location(14, 14)));
+    }
+
+    #[test]
+    fn lightning_flashes_along_its_path() {
+        let mut game = arena();
+        let orc = game.spawn(Object::orc(Location(13, 11)));
+        game.inventory.push(Object::scroll(
+            Location(0, 0),
+            Item::Lightning,
+            "lightning bolt",
+        ));
+
+        game.update(Action::UseItemOn(PLAYER, 0, Some(orc)));
+        assert_eq!(game.animations.len(), 1);
+        assert_eq!(
+            game.animations[0].tiles,
+            vec![Location(11, 10), Location(12, 11), Location(13, 11)]
+        );
+
+        let mut con = Offscreen::new(20, 20);
+        let frames = std::iter::from_fn(|| Some(game.animate(&mut con)))
+            .take_while(|&animating| animating)
+            .count();
+        assert_eq!(frames as i32, LIGHTNING_FRAMES);
+        assert!(game.animations.is_empty());
+    }
+
+    #[test]
+    fn lightning_kills_and_rewards_the_caster() {
+        let mut game = arena();
+        let Location(x, y) = game.objects[PLAYER].loc;
+        let orc = game.spawn(Object::orc(Location(x + 1, y)));
+        let xp = xp_value(&game.objects[orc].fighter.unwrap());
+        game.inventory.push(Object::scroll(
+            Location(0, 0),
+            Item::Lightning,
+            "lightning bolt",
+        ));
+
+        game.update(Action::UseItemOn(PLAYER, 0, Some(orc)));
+        assert!(!game.objects[orc].alive);
+        assert_eq!(game.objects[PLAYER].fighter.unwrap().xp, xp);
+        assert_eq!(game.stats.damage_dealt, LIGHTNING_DAMAGE);
+        assert!(game
+            .messages
+            .iter()
+            .any(|(m, _)| m.ends_with(&format!("You gain {} experience points.", xp))));
+    }
+
+    #[test]
+    fn maps_hold_tiles_by_location() {
+        let mut map = Map::new(Dimension(4, 3), Tile::empty());
+        map.set(&Location(3, 1), Tile::wall());
+        map.set(&Location(4, 1), Tile::wall());
+
+        assert!(map.get(&Location(3, 1)).is_some_and(|t| t.blocked));
+        assert!(map.get(&Location(1, 3)).is_none());
+        assert!(map.get(&Location(-1, 0)).is_none());
+        assert_eq!(map.tiles().filter(|t| t.blocked).count(), 1);
+        let coords: Vec<Location> = map.iter_coords().collect();
+        assert_eq!(coords.len(), 12);
+        assert_eq!(coords[1], Location(1, 0));
+        assert_eq!(coords.last(), Some(&Location(3, 2)));
+    }
+
+    #[test]
+    fn the_camera_trails_the_player() {
+        let mut game = game();
+        game.set_map(Map::new(Dimension(40, 30), Tile::empty()));
+        game.relocate(PLAYER, Location(20, 15));
+        game.camera = Location(20, 15);
+
+        game.relocate(PLAYER, Location(23, 13));
+        game.refresh();
+        assert_eq!(game.camera, Location(20, 15));
+
+        game.relocate(PLAYER, Location(30, 15));
+        game.refresh();
+        assert_eq!(game.camera, Location(22, 15));
+
+        game.camera = Location(0, 29);
+        assert_eq!(game.view_focus(&Dimension(20, 10)), Location(11, 26));
+        assert_eq!(game.view_focus(&Dimension(50, 10)), Location(20, 26));
+    }
+
+    #[test]
+    fn the_mouse_points_at_explored_tiles() {
+        let mut game = game();
+        game.relocate(PLAYER, Location(20, 15));
+        game.set_map(Map::new(Dimension(40, 30), Tile::empty()));
+        let size = Dimension(30, 20);
+        let focus = game.view_focus(&size);
+        let player = rostlaube::geometry::translate(&size, &size, &Location(20, 15), &focus);
+
+        game.pointer = player;
+        assert_eq!(game.pointed_at(&size), Some(Location(20, 15)));
+        assert_eq!(game.describe(&Location(20, 15)), "yourself");
+
+        game.pointer = Some(Location(0, 0));
+        let corner = rostlaube::geometry::translate_back(&size, &Location(0, 0), &focus);
+        assert!(!game.map.get(&corner).unwrap().explored);
+        assert_eq!(game.pointed_at(&size), None);
+    }
+
+    #[test]
+    fn repeated_messages_are_counted() {
+        let mut messages = Messages::new("You wait.", colors::WHITE);
+        messages.add_counted("It's blocked.", colors::WHITE);
+        messages.add_counted("It's blocked.", colors::WHITE);
+        messages.add_counted("It's blocked.", colors::WHITE);
+        messages.add_counted("It's", colors::WHITE);
+
+        let texts: Vec<&str> = messages.iter().map(|(text, _)| text.as_str()).collect();
+        assert_eq!(texts, vec!["You wait.", "It's blocked. (x3)", "It's"]);
+    }
+
+    #[test]
+    fn poison_hurts_until_it_wears_off() {
+        let mut game = arena();
+        let Location(x, y) = game.objects[PLAYER].loc;
+        let orc = game.spawn(Object::orc(Location(x + 3, y)));
+        let poison = StatusEffect::Poisoned {
+            turns: 3,
+            per_turn: 2,
+        };
+        game.objects[PLAYER].afflict(poison);
+        game.objects[PLAYER].afflict(poison);
+        game.objects[orc].afflict(StatusEffect::Poisoned {
+            turns: 3,
+            per_turn: 20,
+        });
+
+        for _ in 0..4 {
+            game.update(Action::Wait(PLAYER));
+        }
+        assert_eq!(game.stats.damage_taken, 6);
+        assert!(game.objects[PLAYER].status.is_empty());
+        assert!(!game.objects[orc].alive);
+        let texts: Vec<&str> = game
+            .messages
+            .iter()
+            .map(|(text, _)| text.as_str())
+            .collect();
+        assert!(texts.contains(&"The orc succumbs to poison."));
+        assert!(texts.contains(&"You are no longer poisoned."));
+    }
+
+    #[test]
+    fn remains_rot_away_after_their_lifetime() {
+        let mut game = arena();
+        game.corpse_lifetime = 3;
+        let loc = Location(12, 10);
+        let orc = game.spawn(Object::orc(loc));
+        game.die(orc);
+        assert_eq!(game.objects[orc].decay, Some(3));
+
+        game.update(Action::Wait(PLAYER));
+        game.update(Action::Wait(PLAYER));
+        assert_eq!(game.objects[orc].decay, Some(1));
+        assert_eq!(game.objects_at(&loc).count(), 1);
+
+        game.update(Action::Wait(PLAYER));
+        assert!(game.objects.get(orc).is_none());
+        assert_eq!(game.objects_at(&loc).count(), 0);
+        assert!(game.messages.iter().any(|(m, _)| m.ends_with("rot away.")));
+    }
+
+    #[test]
+    fn remains_last_forever_with_a_lifetime_of_minus_one() {
+        let mut game = arena();
+        game.corpse_lifetime = -1;
+        let loc = Location(12, 10);
+        let orc = game.spawn(Object::orc(loc));
+        game.die(orc);
+        assert_eq!(game.objects[orc].decay, None);
+
+        for _ in 0..2 * CORPSE_LIFETIME {
+            game.update(Action::Wait(PLAYER));
+        }
+        assert!(game.objects.get(orc).is_some());
+        assert_eq!(game.objects_at(&loc).count(), 1);
+    }
+
+    #[test]
+    fn attacking_a_direction_never_moves() {
+        let mut game = arena();
+        let loc = game.objects[PLAYER].loc;
+        let Location(x, y) = loc;
+        let troll = game.spawn(Object::troll(Location(x, y + 1)));
+
+        game.update(Action::AttackDirection(PLAYER, Direction(1, 0)));
+        assert_eq!(game.objects[PLAYER].loc, loc);
+        assert!(game
+            .messages
+            .iter()
+            .any(|(text, _)| text == "You attack thin air."));
+
+        game.update(Action::AttackDirection(PLAYER, Direction(0, 1)));
+        assert_eq!(game.objects[PLAYER].loc, loc);
+        assert_eq!(game.turn, 2);
+        assert!(game
+            .messages
+            .iter()
+            .any(|(text, _)| text.starts_with("You attack the troll")));
+        assert!(game.objects.contains(troll));
+    }
+
+    #[test]
+    fn messages_are_colored_by_their_kind() {
+        use MessageKind::*;
+        let kinds = [Info, Combat, Warning, Danger, Good];
+        for (i, a) in kinds.iter().enumerate() {
+            for b in &kinds[i + 1..] {
+                assert_ne!(a.color(), b.color(), "{:?} and {:?}", a, b);
+            }
+        }
+
+        let mut game = arena();
+        let Location(x, y) = game.objects[PLAYER].loc;
+        let troll = game.spawn(Object::troll(Location(x + 1, y)));
+        game.update(Action::Attack(PLAYER, troll));
+        let (_, color) = game
+            .messages
+            .iter()
+            .find(|(text, _)| text.starts_with("You attack the troll"))
+            .expect("The attack is reported");
+        assert_eq!(*color, Combat.color());
+    }
+
+    #[test]
+    fn monsters_are_tougher_on_hard() {
+        let normal = Object::orc(Location(0, 0)).fighter.unwrap();
+        let mut easy = Object::orc(Location(0, 0));
+        Difficulty::Easy.adjust(&mut easy);
+        let easy = easy.fighter.unwrap();
+        assert!(easy.max_health < normal.max_health && easy.power < normal.power);
+
+        let layout = Layout::new(Dimension(40, 30), Dimension(6, 8), 30, 3, 0);
+        let regen = game().objects[PLAYER].fighter.unwrap().health_regen;
+        // A level that is sure to have orcs
+        rng::seed(1);
+        let game = Game::new("Tester", layout, 1, Difficulty::Hard);
+        let mut hard = Object::orc(Location(0, 0));
+        Difficulty::Hard.adjust(&mut hard);
+        let hard = hard.fighter.unwrap();
+        assert!(hard.max_health > normal.max_health && hard.power > normal.power);
+        let orcs: Vec<_> = game
+            .objects
+            .values()
+            .filter(|o| o.name == "orc")
+            .map(|o| o.fighter.unwrap())
+            .collect();
+        assert!(!orcs.is_empty());
+        for orc in orcs {
+            assert_eq!(orc.max_health, hard.max_health);
+            assert_eq!(orc.power, hard.power);
+            assert_eq!(orc.health, orc.max_health);
+        }
+        let player = game.objects[PLAYER].fighter.unwrap();
+        assert_eq!(player.health_regen, regen * Difficulty::Hard.player_regen());
+        assert_eq!(player.power, 5);
+    }
+
+    #[test]
+    fn the_alert_shows_news_until_the_next_action() {
+        let mut game = arena();
+        game.ambient = false;
+        let Location(x, y) = game.objects[PLAYER].loc;
+        let troll = game.spawn(Object::troll(Location(x + 1, y)));
+
+        game.update(Action::Attack(PLAYER, troll));
+        let latest = game.messages.latest().cloned();
+        assert!(latest.is_some());
+        assert_eq!(game.alert().cloned(), latest);
+
+        game.update(Action::Wait(PLAYER));
+        assert_eq!(game.alert(), None);
+    }
+
+    #[test]
+    fn hunger_warns_starves_and_is_stilled_by_food() {
+        let mut game = arena();
+        game.objects[PLAYER].hunger = Some(HUNGER_HUNGRY + 1);
+        game.update(Action::Wait(PLAYER));
+        assert!(game
+            .messages
+            .iter()
+            .any(|(text, _)| text == "You are hungry."));
+
+        game.objects[PLAYER].hunger = Some(1);
+        for _ in 0..3 {
+            game.update(Action::Wait(PLAYER));
+        }
+        assert_eq!(game.objects[PLAYER].hunger, Some(0));
+        assert_eq!(game.stats.damage_taken, 3 * STARVATION_DAMAGE);
+
+        let loc = game.objects[PLAYER].loc;
+        let ration = game.spawn(Object::ration(loc));
+        game.update(Action::PickUp(PLAYER, ration));
+        game.update(Action::UseItem(PLAYER, 0));
+        assert_eq!(game.objects[PLAYER].hunger, Some(RATION_NUTRITION));
+        assert!(game.inventory.is_empty());
+
+        game.objects[PLAYER].fighter.as_mut().unwrap().health = 1;
+        game.objects[PLAYER].hunger = Some(1);
+        game.update(Action::Wait(PLAYER));
+        game.update(Action::Wait(PLAYER));
+        assert!(!game.objects[PLAYER].alive);
+        assert!(game
+            .messages
+            .iter()
+            .any(|(text, _)| text == "You starve to death."));
+    }
+
+    #[test]
+    fn spiders_bite_is_poisonous() {
+        let mut game = game();
+        let Location(x, y) = game.objects[PLAYER].loc;
+        let spider = game.spawn(Object::spider(Location(x + 1, y)));
+        if let Some(fighter) = game.objects[PLAYER].fighter.as_mut() {
+            fighter.defense = 0;
+        }
+
+        let outcome = attack(spider, PLAYER, &mut game);
+        assert!(outcome
+            .messages
+            .iter()
+            .any(|(text, _)| text == "You are poisoned!"));
+        assert_eq!(
+            game.objects[PLAYER].status,
+            vec![StatusEffect::Poisoned {
+                turns: POISON_NUM_TURNS,
+                per_turn: POISON_DAMAGE,
+            }]
+        );
+    }
+
+    #[test]
+    fn wizards_can_give_and_heal() {
+        let mut game = game();
+        game.cheat(Cheat::Heal);
+        assert!(game.inventory.is_empty());
+
+        game.enable_wizard_mode();
+        game.objects[PLAYER].fighter.as_mut().unwrap().health = 1;
+        game.cheat(Cheat::Give(String::from("heal")));
+        game.cheat(Cheat::Give(String::from("heal")));
+        game.cheat(Cheat::Give(String::from("sandwich")));
+        game.cheat(Cheat::Heal);
+
+        assert_eq!(game.inventory.len(), 1);
+        assert_eq!(game.inventory[0].count, Some(2));
+        let fighter = game.objects[PLAYER].fighter.unwrap();
+        assert_eq!(fighter.health, fighter.max_health);
+    }
+
+    #[test]
+    fn a_confused_player_never_stands_still() {
+        let mut game = arena();
+        for _ in 0..50 {
+            game.objects[PLAYER].afflict(StatusEffect::Confused { turns: 10 });
+            let before = game.objects[PLAYER].loc;
+            game.update(Action::Move(PLAYER, Direction(1, 0)));
+            assert_ne!(game.objects[PLAYER].loc, before);
+            game.relocate(PLAYER, Location(10, 10));
+        }
+    }
+
+    #[test]
+    fn confusion_needs_a_target() {
+        let mut game = arena();
+        let Location(x, y) = game.objects[PLAYER].loc;
+        let orc = game.spawn(Object::orc(Location(x + 1, y)));
+        let far = game.spawn(Object::orc(Location(x + CONFUSE_RANGE + 1, y)));
+        game.inventory
+            .push(Object::scroll(Location(0, 0), Item::Confusion, "confusion"));
+
+        game.update(Action::UseItemOn(PLAYER, 0, None));
+        game.update(Action::UseItemOn(PLAYER, 0, Some(far)));
+        assert_eq!(game.inventory.len(), 1);
+        assert!(matches!(game.objects[far].ai, Some(Ai::Basic)));
+
+        game.update(Action::UseItemOn(PLAYER, 0, Some(orc)));
+        assert!(game.inventory.is_empty());
+        assert!(game.objects[orc].is_confused());
+    }
+
+    #[test]
+    fn gas_is_thrown_at_the_chosen_target() {
+        let mut game = arena();
+        let near = game.spawn(Object::orc(Location(11, 10)));
+        let chosen = game.spawn(Object::orc(Location(14, 12)));
+        game.inventory.push(Object::potion(
+            Location(0, 0),
+            Item::PoisonGas,
+            "flask of poison gas",
+        ));
+        assert!(Item::PoisonGas.needs_target());
+
+        game.update(Action::UseItemOn(PLAYER, 0, None));
+        assert_eq!(game.inventory.len(), 1);
+        assert!(game.effects.is_empty());
+
+        game.update(Action::UseItemOn(PLAYER, 0, Some(chosen)));
+        assert!(game.inventory.is_empty());
+        assert_eq!(game.effects.len(), 1);
+        assert_eq!(game.effects[0].center, Location(14, 12));
+        assert_ne!(game.effects[0].center, game.objects[near].loc);
+    }
+
+    #[test]
+    fn saved_games_load_the_same() {
+        let mut game = game();
+        let Location(x, y) = game.objects[PLAYER].loc;
+        let hermit = game.spawn(Object::hermit(Location(x + 2, y)));
+        game.inventory.push(Object::sword(Location(0, 0)));
+        game.update(Action::Equip(PLAYER, 0));
+        game.update(Action::Move(PLAYER, Direction(1, 0)));
+
+        let path = std::env::temp_dir().join("rustlike-save-test.json");
+        game.save(&path).expect("Game can be saved");
+        let loaded = Game::load(&path).expect("Game can be loaded");
+        std::fs::remove_file(&path).ok();
+
+        assert_eq!(loaded.fingerprint(), game.fingerprint());
+        assert_eq!(loaded.objects.len(), game.objects.len());
+        assert!(loaded.objects[hermit].dialogue.is_some());
+        assert_eq!(loaded.power(PLAYER), game.power(PLAYER));
+        let walls = |game: &Game| game.map.tiles().map(|t| t.blocked).collect::<Vec<_>>();
+        assert_eq!(walls(&loaded), walls(&game));
+        assert!(loaded.visible(&loaded.objects[PLAYER].loc));
+    }
+
+    #[test]
+    fn message_slices_stop_at_the_end() {
+        let mut messages = Messages::empty();
+        for i in 0..5 {
+            messages.add(format!("Message {}", i), colors::WHITE);
+        }
+
+        assert_eq!(messages.len(), 5);
+        assert_eq!(messages.slice(3, 10).len(), 2);
+        assert_eq!(messages.slice(3, 10)[0].0, "Message 3");
+        assert!(messages.slice(7, 10).is_empty());
+    }
+
+    #[test]
+    fn diagonal_steps_count_as_one() {
+        let origin = Location(5, 5);
+        assert_eq!(chebyshev_distance(&origin, &Location(8, 8)), 3);
+        assert_eq!(chebyshev_distance(&origin, &Location(2, 7)), 3);
+        assert_eq!(chebyshev_distance(&origin, &Location(6, 4)), 1);
+        assert_eq!(chebyshev_distance(&origin, &origin), 0);
+        assert!(distance(&origin, &Location(8, 8)) > 4.0);
+    }
+
+    #[test]
+    fn lightning_reaches_diagonally() {
+        let mut game = game();
+        let Location(x, y) = game.objects[PLAYER].loc;
+        let near = game.spawn(Object::orc(Location(
+            x + LIGHTNING_RANGE,
+            y + LIGHTNING_RANGE,
+        )));
+        let far = game.spawn(Object::orc(Location(x + LIGHTNING_RANGE + 1, y + 1)));
+
+        assert!(!fighters_by_distance(PLAYER, &game.objects, LIGHTNING_RANGE).contains(&far));
+        assert!(in_range(PLAYER, near, LIGHTNING_RANGE, &game));
+        assert!(!in_range(PLAYER, far, LIGHTNING_RANGE, &game));
+    }
+
+    #[test]
+    fn walls_block_the_lightning() {
+        let mut game = arena();
+        game.map.set(&Location(12, 10), Tile::wall());
+        let hidden = game.spawn(Object::orc(Location(13, 10)));
+        let open = game.spawn(Object::orc(Location(13, 12)));
+
+        assert!(!has_line_of_sight(
+            &Location(10, 10),
+            &Location(13, 10),
+            &game.map
+        ));
+        assert!(has_line_of_sight(
+            &Location(10, 10),
+            &Location(12, 10),
+            &game.map
+        ));
+        assert!(!in_range(PLAYER, hidden, LIGHTNING_RANGE, &game));
+        assert!(in_range(PLAYER, open, LIGHTNING_RANGE, &game));
+    }
+
+    #[test]
+    fn wands_run_out_of_charges() {
+        let mut game = arena();
+        let troll = game.spawn(sturdy(Object::troll(Location(11, 10))));
+        game.inventory.push(Object::wand(
+            Location(0, 0),
+            Item::Lightning,
+            "wand of lightning",
+            3,
+        ));
+
+        for _ in 0..3 {
+            use_item(PLAYER, 0, Some(troll), &mut game);
+        }
+        let health = game.objects[troll].fighter.unwrap().health;
+        assert_eq!(health, 1000 - 3 * LIGHTNING_DAMAGE);
+
+        use_item(PLAYER, 0, Some(troll), &mut game);
+        assert_eq!(game.objects[troll].fighter.unwrap().health, health);
+        assert_eq!(game.inventory[0].charges, Some(0));
+        assert_eq!(game.inventory[0].name, "wand of lightning (0 charges)");
+    }
+
+    #[test]
+    fn gas_hurts_until_it_clears() {
+        let mut game = arena();
+        let troll = game.spawn(sturdy(Object::troll(Location(13, 10))));
+        game.effects.push(AreaEffect::poison_gas(Location(13, 10)));
+
+        let health = |game: &Game| game.objects[troll].fighter.unwrap().health;
+        for turn in 1..=GAS_DURATION {
+            game.update(Action::Wait(PLAYER));
+            assert_eq!(health(&game), 1000 - turn * GAS_DAMAGE);
+        }
+        assert!(game.effects.is_empty());
+
+        game.update(Action::Wait(PLAYER));
+        assert_eq!(health(&game), 1000 - GAS_DURATION * GAS_DAMAGE);
+        assert_eq!(game.stats.damage_taken, 0);
+    }
+
+    #[test]
+    fn games_can_start_deeper_down() {
+        let mut game = Game::new(
+            "Tester",
+            Layout::new(Dimension(40, 30), Dimension(6, 8), 10, 0, 0),
+            3,
+            Difficulty::Normal,
+        );
+        assert_eq!(game.depth, 3);
+        assert_eq!(game.theme(), Theme::at_depth(3));
+        game.change_level(2);
+        assert_eq!(game.depth, 2);
+    }
+
+    #[test]
+    fn the_first_level_is_laid_out_by_the_chosen_algorithm() {
+        let layout = Layout {
+            algorithm: dungeon::GenAlgorithm::Bsp,
+            ..Layout::new(Dimension(40, 30), Dimension(6, 8), 10, 0, 0)
+        };
+        let walls = |map: &Map| map.tiles().map(|tile| tile.blocked).collect::<Vec<_>>();
+
+        rng::seed(3);
+        let game = Game::new("Tester", layout, 1, Difficulty::Normal);
+        rng::seed(3);
+        let mut objects = Objects::new();
+        objects.insert(Object::player(Location(0, 0), "Tester"));
+        let map = dungeon::generate(&layout, &mut objects, 1);
+
+        assert_eq!(walls(&game.map), walls(&map));
+    }
+
+    #[test]
+    fn recall_returns_to_the_first_level() {
+        let mut game = game();
+        game.peaceful = true;
+        let entrance = game.entrance;
+        game.change_level(3);
+        assert_eq!(game.depth, 3);
+
+        game.recall = Some(1);
+        game.update(Action::Wait(PLAYER));
+        assert_eq!(game.depth, 1);
+        assert_eq!(game.recall, None);
+        let loc = game.objects[PLAYER].loc;
+        assert!(chebyshev_distance(&loc, &entrance) <= SPILL_RADIUS);
+    }
+
+    #[test]
+    fn the_player_chokes_in_gas_too() {
+        let mut game = arena();
+        game.effects
+            .push(AreaEffect::poison_gas(game.objects[PLAYER].loc));
+        game.update(Action::Wait(PLAYER));
+        assert_eq!(game.stats.damage_taken, GAS_DAMAGE);
+        assert!(game
+            .messages
+            .iter()
+            .any(|(text, _)| text == "You choke in the poison gas."));
+    }
+
+    #[test]
+    fn traps_spring_and_show_up() {
+        let mut game = arena();
+        if let Some(movement) = game.objects[PLAYER].movement.as_mut() {
+            movement.speed = 100;
+        }
+        let trap = game.spawn(Object::trap(Location(11, 10), TrapKind::Spikes));
+        game.refresh();
+        assert!(!game.objects[trap].visible);
+
+        game.update(Action::Move(PLAYER, Direction(1, 0)));
+
+        assert_eq!(game.objects[PLAYER].loc, Location(11, 10));
+        assert_eq!(game.stats.damage_taken, TRAP_DAMAGE);
+        assert!(game.objects[trap].trap.is_some_and(|t| !t.hidden));
+        assert!(game.objects[trap].visible);
+    }
+
+    #[test]
+    fn walking_paths_keep_to_known_ground_and_avoid_traps() {
+        let mut game = game();
+        game.clear_objects();
+        game.relocate(PLAYER, Location(5, 5));
+        game.set_map(Map::new(Dimension(40, 30), Tile::empty()));
+        // A corridor the player has seen, but for one tile
+        for loc in game.map.iter_coords().collect::<Vec<_>>() {
+            let Location(x, y) = loc;
+            let mut tile = Tile::empty();
+            tile.explored = y == 5 && (5..=20).contains(&x) && x != 12;
+            game.map.set(&loc, tile);
+        }
+        let mut trap = Object::trap(Location(10, 5), TrapKind::Spikes);
+        if let Some(t) = trap.trap.as_mut() {
+            t.hidden = false;
+        }
+        let trap = game.spawn(trap);
+        let path_to = |game: &Game, goal: Location| game.bfs_path(|loc| *loc == goal);
+
+        assert_eq!(path_to(&game, Location(11, 5)), None);
+        if let Some(t) = game.objects[trap].trap.as_mut() {
+            t.hidden = true;
+        }
+        assert_eq!(path_to(&game, Location(11, 5)).map(|p| p.len()), Some(6));
+        assert_eq!(path_to(&game, Location(15, 5)), None);
+        assert!(game.explore_path().is_some());
+    }
+
+    #[test]
+    fn ids_survive_picking_things_up() {
+        let mut game = game();
+        let loc = game.objects[PLAYER].loc;
+        let potion = game.spawn(Object::potion(loc, Item::Heal, "healing potion"));
+        let orc = game.spawn(Object::orc(Location(0, 0)));
+
+        game.update(Action::PickUp(PLAYER, potion));
+        assert_eq!(game.inventory.len(), 1);
+        assert_eq!(game.objects[orc].name, "orc");
+
+        // Picking up the same item again, e.g. from a stale action, does nothing
+        game.update(Action::PickUp(PLAYER, potion));
+        assert_eq!(game.inventory.len(), 1);
+
+        let troll = game.spawn(Object::troll(Location(0, 0)));
+        assert_ne!(troll, potion);
+        assert!(!game.objects.contains(potion));
+    }
+
+    #[test]
+    fn descending_keeps_only_the_player_and_the_pack() {
+        let mut game = Game::new(
+            "Tester",
+            Layout::new(Dimension(40, 30), Dimension(6, 8), 10, 3, 2),
+            1,
+            Difficulty::Normal,
+        );
+        game.inventory
+            .push(Object::potion(Location(0, 0), Item::Heal, "healing potion"));
+        let stairs = game
+            .objects
+            .iter()
+            .find(|(_, o)| o.stairs)
+            .map(|(id, _)| id)
+            .expect("Stairs on every level");
+        let loc = game.objects[stairs].loc;
+        game.relocate(PLAYER, loc);
+
+        game.update(Action::Descend(PLAYER));
+
+        assert_eq!(game.depth, 2);
+        assert_eq!(game.inventory.len(), 1);
+        assert_eq!(game.entrance, game.objects[PLAYER].loc);
+        assert_eq!(game.objects.values().filter(|o| o.stairs).count(), 1);
+        assert!(game.visible(&game.objects[PLAYER].loc));
+    }
+
+    #[test]
+    fn levels_are_kept_as_they_were_left() {
+        let mut game = Game::new(
+            "Tester",
+            Layout::new(Dimension(40, 30), Dimension(6, 8), 10, 3, 2),
+            1,
+            Difficulty::Normal,
+        );
+        let stairs = |game: &Game| {
+            game.objects
+                .values()
+                .find(|o| o.stairs)
+                .map(|o| o.loc)
+                .expect("Stairs on every level")
+        };
+        let first_stairs = stairs(&game);
+        let explored = game.map.tiles().filter(|tile| tile.explored).count();
+        let potion = game.spawn(Object::potion(Location(1, 1), Item::Heal, "healing potion"));
+        game.objects[potion].seen = true;
+        game.relocate(PLAYER, first_stairs);
+        game.update(Action::Descend(PLAYER));
+        let entrance = game.entrance;
+        let second_map = game
+            .map
+            .tiles()
+            .map(|tile| tile.blocked)
+            .collect::<Vec<_>>();
+
+        game.update(Action::Ascend(PLAYER));
+        assert_eq!(game.depth, 1);
+        assert_eq!(game.objects[PLAYER].loc, first_stairs);
+        assert!(game.map.tiles().filter(|tile| tile.explored).count() >= explored);
+        assert!(game
+            .objects
+            .values()
+            .any(|o| o.name == "healing potion" && o.loc == Location(1, 1) && o.seen));
+
+        game.update(Action::Descend(PLAYER));
+        assert_eq!(game.depth, 2);
+        assert_eq!(game.objects[PLAYER].loc, entrance);
+        assert!(game
+            .objects
+            .values()
+            .any(|o| o.upstairs && o.loc == entrance));
+        assert_eq!(
+            game.map
+                .tiles()
+                .map(|tile| tile.blocked)
+                .collect::<Vec<_>>(),
+            second_map
+        );
+        assert!(game.visible(&entrance));
+    }
+
+    #[test]
+    fn climbing_out_of_the_first_level_wins() {
+        let mut game = Game::new(
+            "Tester",
+            Layout::new(Dimension(40, 30), Dimension(6, 8), 10, 0, 0),
+            1,
+            Difficulty::Normal,
+        );
+        assert!(game.at_exit());
+        game.relocate(PLAYER, Location(0, 0));
+        game.update(Action::Ascend(PLAYER));
+        assert!(!game.escaped);
+
+        let entrance = game.entrance;
+        game.relocate(PLAYER, entrance);
+        game.update(Action::Ascend(PLAYER));
+        assert!(game.escaped);
+        assert_eq!(game.depth, 1);
+    }
+
+    #[test]
+    fn killing_grants_experience_and_levels() {
+        let mut game = game();
+        let Location(x, y) = game.objects[PLAYER].loc;
+        let orc = game.spawn(Object::orc(Location(x + 1, y)));
+        if let Some(fighter) = game.objects[orc].fighter.as_mut() {
+            fighter.health = 1;
+        }
+        while game.objects[orc].alive {
+            game.play(&vec![Action::Attack(PLAYER, orc)]);
+            game.refresh();
+        }
+        let xp = game.objects[PLAYER].fighter.unwrap().xp;
+        assert_eq!(xp, 10 + 10 * 3);
+        assert!(!game.level_up_pending());
+
+        if let Some(fighter) = game.objects[PLAYER].fighter.as_mut() {
+            fighter.xp = level_up_threshold(1);
+        }
+        assert!(game.level_up_pending());
+        let power = game.objects[PLAYER].fighter.unwrap().power;
+        game.level_up(Bonus::Strength);
+
+        let fighter = game.objects[PLAYER].fighter.unwrap();
+        assert_eq!((fighter.level, fighter.power), (2, power + 1));
+        assert!(!game.level_up_pending());
+    }
+
+    #[test]
+    fn cursed_gear_stays_on_until_the_curse_is_lifted() {
+        let mut game = game();
+        game.inventory.clear();
+        let defense = game.defense(PLAYER);
+        let mut armor = Object::armor(Location(0, 0));
+        armor.cursed = true;
+        game.inventory.push(armor);
+        game.inventory.push(Object::armor(Location(0, 0)));
+        game.update(Action::Equip(PLAYER, 0));
+        assert_eq!(game.defense(PLAYER), defense + 2);
+
+        let worn = |game: &Game, i: usize| game.inventory[i].equipment.is_some_and(|e| e.equipped);
+        game.update(Action::Unequip(PLAYER, 0));
+        assert!(game
+            .messages
+            .iter()
+            .any(|(text, _)| text == "You can't remove it! It's cursed!"));
+        game.update(Action::Equip(PLAYER, 1));
+        assert!(!worn(&game, 1));
+        game.update(Action::Drop(PLAYER, 0));
+        assert_eq!(game.inventory.len(), 2);
+        assert!(worn(&game, 0));
+
+        game.inventory.push(Object::scroll(
+            Location(0, 0),
+            Item::RemoveCurse,
+            "remove curse",
+        ));
+        game.update(Action::UseItem(PLAYER, 2));
+        assert_eq!(game.inventory.len(), 2);
+        assert!(!game.inventory[0].cursed);
+        game.update(Action::Unequip(PLAYER, 0));
+        assert!(!worn(&game, 0));
+    }
+
+    #[test]
+    fn cursed_potions_hurt() {
+        let mut game = game();
+        game.inventory.clear();
+        let mut potion = Object::potion(Location(0, 0), Item::Heal, "healing potion");
+        potion.cursed = true;
+        assert!(!potion.stacks_with(&Object::potion(
+            Location(0, 0),
+            Item::Heal,
+            "healing potion"
+        )));
+        game.inventory.push(potion);
+        let health = game.objects[PLAYER].fighter.unwrap().health;
+        game.update(Action::UseItem(PLAYER, 0));
+        assert!(game.inventory.is_empty());
+        assert_eq!(
+            game.objects[PLAYER].fighter.unwrap().health,
+            health - CURSED_POTION_DAMAGE
+        );
+    }
+
+    #[test]
+    fn the_off_hand_holds_a_shield_or_a_weapon() {
+        let mut game = game();
+        game.inventory.clear();
+        let power = game.power(PLAYER);
+        let defense = game.defense(PLAYER);
+        game.inventory.push(Object::sword(Location(0, 0)));
+        game.inventory.push(Object::shield(Location(0, 0)));
+        game.inventory.push(Object::dagger(Location(0, 0)));
+        game.update(Action::Equip(PLAYER, 0));
+        game.update(Action::Equip(PLAYER, 1));
+        assert_eq!(game.defense(PLAYER), defense + 1);
+        assert_eq!(game.off_hand_power(PLAYER), None);
+
+        // The dagger takes the place of the shield
+        game.update(Action::Equip(PLAYER, 2));
+        assert!(game.inventory[0].equipment.is_some_and(|e| e.equipped));
+        assert!(game.inventory[1].equipment.is_some_and(|e| !e.equipped));
+        assert_eq!(game.defense(PLAYER), defense);
+        assert_eq!(game.power(PLAYER), power + 3);
+        assert_eq!(
+            game.off_hand_power(PLAYER),
+            Some((power + 2) * OFF_HAND_POWER / 100)
+        );
+
+        game.update(Action::Equip(PLAYER, 1));
+        assert!(game.inventory[2].equipment.is_some_and(|e| !e.equipped));
+        assert_eq!(game.off_hand_power(PLAYER), None);
+    }
+
+    #[test]
+    fn off_hand_weapons_strike_again() {
+        let mut game = arena();
+        game.inventory.clear();
+        let mut troll = sturdy(Object::troll(Location(11, 10)));
+        if let Some(fighter) = troll.fighter.as_mut() {
+            fighter.defense = 0;
+        }
+        let troll = game.spawn(troll);
+        game.objects[PLAYER].fighter.as_mut().unwrap().power = 10;
+        let follow_ups = |game: &Game| {
+            game.messages
+                .iter()
+                .filter(|(text, _)| text.starts_with("You follow up on"))
+                .count()
+        };
+
+        game.inventory.push(Object::shield(Location(0, 0)));
+        game.update(Action::Equip(PLAYER, 0));
+        game.update(Action::Attack(PLAYER, troll));
+        assert_eq!(follow_ups(&game), 0);
+
+        game.inventory.push(Object::dagger(Location(0, 0)));
+        game.update(Action::Equip(PLAYER, 1));
+        let health = game.objects[troll].fighter.unwrap().health;
+        game.update(Action::Attack(PLAYER, troll));
+        assert_eq!(follow_ups(&game), 1);
+        // Two hits of at least one damage each
+        assert!(game.objects[troll].fighter.unwrap().health <= health - 2);
+    }
+
+    #[test]
+    fn equipping_a_sword_raises_power() {
+        let mut game = game();
+        let loc = game.objects[PLAYER].loc;
+        let power = game.power(PLAYER);
+        game.spawn(Object::sword(loc));
+
+        if let (Some(Action::PickUp(id, item)), _) = grab(PLAYER, &game) {
+            game.update(Action::PickUp(id, item));
+        }
+        assert_eq!(game.inventory.len(), 1);
+        assert!(game.equipped_bonus(PLAYER, Slot::Weapon).is_none());
+
+        game.update(Action::Equip(PLAYER, 0));
+        assert_eq!(game.power(PLAYER), power + 3);
+        assert_eq!(
+            game.equipped_bonus(PLAYER, Slot::Weapon)
+                .unwrap()
+                .power_bonus,
+            3
+        );
+
+        game.inventory.push(Object::sword(loc));
+        game.update(Action::Equip(PLAYER, 1));
+        assert!(game.inventory[0].equipment.is_some_and(|e| !e.equipped));
+        assert_eq!(game.power(PLAYER), power + 3);
+
+        game.update(Action::Unequip(PLAYER, 1));
+        assert_eq!(game.power(PLAYER), power);
+    }
+
+    #[test]
+    fn axes_roll_their_dice_against_flat_armor() {
+        let mut game = arena();
+        let mut orc = sturdy(Object::orc(Location(11, 10)));
+        if let Some(fighter) = orc.fighter.as_mut() {
+            fighter.defense = 1;
+        }
+        let orc = game.spawn(orc);
+        game.inventory.push(Object::axe(Location(0, 0)));
+        game.update(Action::Equip(PLAYER, 0));
+        assert_eq!(game.damage_dice(PLAYER), Some(AXE_DAMAGE));
+
+        for _ in 0..50 {
+            let health = game.objects[orc].fighter.unwrap().health;
+            game.update(Action::Attack(PLAYER, orc));
+            let damage = health - game.objects[orc].fighter.unwrap().health;
+            assert!((1..=7).contains(&damage), "the axe dealt {}", damage);
+        }
+    }
+
+    #[test]
+    fn fighters_out_of_view_are_not_listed() {
+        let mut game = game();
+        let Location(x, y) = game.objects[PLAYER].loc;
+        let Dimension(width, height) = game.map.dimensions();
+        let hidden = (0..width)
+            .flat_map(|x| (0..height).map(move |y| Location(x, y)))
+            .find(|loc| !game.visible(loc))
+            .expect("Some tile out of view");
+
+        let seen = game.spawn(Object::orc(Location(x + 1, y)));
+        let unseen = game.spawn(Object::troll(hidden));
 
-    objects[defender]
-        .fighter
-        .as_mut()
-        .map(|fighter| {
-            if damage > 0 {
-                let msg = format!("{} for {} damage!", msg, damage);
-                fighter.take_damage(damage);
-                Messages::new(msg, colors::WHITE)
-            } else {
-                let msg = match attacker {
-                    PLAYER => format!("{} but do no damage.", msg),
-                    _ => format!("{} but does no damage.", msg),
-                };
-                Messages::new(msg, colors::WHITE)
+        assert!(fighters_by_distance(PLAYER, &game.objects, 1000).contains(&unseen));
+        assert_eq!(game.visible_fighters_by_distance(PLAYER, 1000), vec![seen]);
+    }
+
+    #[test]
+    fn spatial_index_matches_a_full_scan() {
+        let mut game = Game::new(
+            "Tester",
+            Layout::new(Dimension(40, 30), Dimension(6, 8), 10, 3, 2),
+            1,
+            Difficulty::Normal,
+        );
+        let directions = [
+            Direction(0, -1),
+            Direction(0, 1),
+            Direction(-1, 0),
+            Direction(1, 0),
+            Direction(-1, -1),
+            Direction(1, 1),
+        ];
+        for step in 0..100 {
+            game.update(Action::Move(PLAYER, directions[step % directions.len()]));
+            if let (Some(Action::PickUp(id, item)), _) = grab(PLAYER, &game) {
+                game.update(Action::PickUp(id, item));
             }
-        })
-        .unwrap_or_else(|| Messages::new("Cannot attack that!", colors::WHITE))
-}
+        }
 
-/// Move resolution
-fn move_object(id: usize, direction: Direction, map: &Map, objects: &mut [Object]) -> Messages {
-    let Direction(dx, dy) = direction;
-    let mut messages = Messages::empty();
-    let should_move = objects[id]
-        .movement
-        .as_ref()
-        .map_or(false, |m| m.speed >= rng::d100());
+        let Dimension(width, height) = game.map.dimensions();
+        for loc in (0..width).flat_map(|x| (0..height).map(move |y| Location(x, y))) {
+            let mut indexed: Vec<Id> = game.objects_at(&loc).map(|(id, _)| id).collect();
+            let mut scanned: Vec<Id> = game
+                .objects
+                .iter()
+                .filter(|(_, o)| o.loc == loc)
+                .map(|(id, _)| id)
+                .collect();
+            indexed.sort_unstable();
+            scanned.sort_unstable();
+            assert_eq!(indexed, scanned, "Objects at {:?}", loc);
+            assert_eq!(game.object_blocks(&loc), object_blocks(&loc, &game.objects));
+        }
+    }
 
-    if should_move {
-        let could_move = move_by(id, direction, map, objects)
-            || move_by(id, Direction(dx, 0), map, objects)
-            || move_by(id, Direction(0, dy), map, objects);
-        if !could_move {
-            messages.add("The way is blocked!", colors::WHITE);
+    #[derive(Default)]
+    struct Recorder(std::rc::Rc<std::cell::RefCell<Vec<SoundEvent>>>);
+
+    impl SoundSink for Recorder {
+        fn emit(&mut self, sound: SoundEvent) {
+            self.0.borrow_mut().push(sound);
         }
     }
-    messages
-}
 
-/// Pick up item
-fn pickup_item(
-    actor: usize,
-    item_id: usize,
-    objects: &mut Vec<Object>,
-    inventory: &mut Inventory,
-) -> Messages {
-    let mut messages = Messages::empty();
-    if inventory.len() >= 26 {
-        messages.add("Inventory full", colors::WHITE);
-    } else {
-        let item = objects.swap_remove(item_id);
+    #[test]
+    fn combat_is_heard_by_the_sound_sink() {
+        let mut game = game();
+        let heard = std::rc::Rc::default();
+        game.set_sound_sink(Box::new(Recorder(std::rc::Rc::clone(&heard))));
+        let Location(x, y) = game.objects[PLAYER].loc;
+        let orc = game.spawn(Object::orc(Location(x + 1, y)));
+        // Nobody else may make a sound after the orc dies
+        game.peaceful = true;
 
-        let msg = match actor {
-            PLAYER => format!("You pick up {}.", indirect(&item.name, false)),
-            _ => format!(
-                "{} picks up {}.",
-                direct(&objects[actor].name, true),
-                indirect(&item.name, false)
-            ),
+        while game.objects[orc].alive {
+            game.update(Action::Attack(PLAYER, orc));
+        }
+
+        let heard = heard.borrow();
+        let at_orc = SoundEvent {
+            kind: SoundKind::Hit,
+            loc: Location(x + 1, y),
         };
-        messages.add(msg, colors::WHITE);
+        assert!(heard.contains(&at_orc));
+        assert_eq!(
+            heard.last(),
+            Some(&SoundEvent {
+                kind: SoundKind::Death,
+                ..at_orc
+            })
+        );
+    }
 
-        inventory.push(item);
+    #[test]
+    fn wanderers_appear_out_of_view() {
+        let mut game = arena();
+        // Plenty of floor behind a wall, whatever the light radius
+        for y in 0..30 {
+            game.map.set(&Location(20, y), Tile::wall());
+        }
+        game.refresh();
+        for _ in 0..50 {
+            let id = game.spawn_wanderer().expect("Some free floor out of view");
+            assert!(!game.visible(&game.objects[id].loc));
+            assert!(!structure_blocks(&game.objects[id].loc, &game.map));
+        }
     }
-    messages
-}
 
-/// Use an item
-fn use_item(id: usize, item_id: usize, game: &mut Game) -> Messages {
-    game.inventory[item_id]
-        .item
-        .as_ref()
-        .map(|i| match i {
-            Item::Heal => cast_heal,
-            Item::Lightning => cast_lightning,
-            Item::Confusion => cast_confusion,
-        })
-        .map(|f| f(id, item_id, game))
-        .map(|r| match r {
-            (UseResult::UsedUp, messages) => {
-                game.inventory.remove(item_id);
-                messages
-            }
-            (UseResult::Cancelled, messages) => messages,
-        })
-        .unwrap_or_else(|| Messages::empty())
-}
+    #[test]
+    fn monsters_leave_a_peaceful_player_alone() {
+        let mut game = game();
+        let Location(x, y) = game.objects[PLAYER].loc;
+        game.spawn(Object::orc(Location(x + 1, y)));
+        game.spawn(Object::shaman(Location(x - 1, y)));
+        let health = game.objects[PLAYER].fighter.unwrap().health;
 
-fn bark(id: usize, objects: &[Object]) -> Messages {
-    objects[id]
-        .noise
-        .as_ref()
-        .map(|n| match n {
-            Noise { bark, .. } => Messages::new(
-                format!("{} {}s.", indirect(&objects[id].name, true), bark),
-                colors::WHITE,
-            ),
-        })
-        .unwrap_or_else(|| Messages::empty())
-}
+        game.peaceful = true;
+        for _ in 0..20 {
+            game.update(Action::Wait(PLAYER));
+        }
 
-fn mumble(id: usize, objects: &[Object]) -> Messages {
-    objects[id]
-        .noise
-        .as_ref()
-        .map(|n| match n {
-            Noise { mumble, .. } => Messages::new(
-                format!("{} {}s.", indirect(&objects[id].name, true), mumble),
-                colors::WHITE,
-            ),
-        })
-        .unwrap_or_else(|| Messages::empty())
-}
+        assert_eq!(game.objects[PLAYER].fighter.unwrap().health, health);
+        assert!(!game.objects[PLAYER].is_confused());
+    }
 
-fn kill_player(player: &mut Object) -> Messages {
-    let mut messages = Messages::empty();
-    let msg = "You die!";
-    player.alive = false;
-    player.char = '%';
-    player.color = colors::RED;
+    struct RoundCounter(std::rc::Rc<std::cell::Cell<i32>>);
 
-    messages.add(msg, colors::RED);
-    messages
-}
+    impl TurnHook for RoundCounter {
+        fn on_round_end(&mut self, _game: &mut Game, turn: i32) {
+            assert_eq!(turn, self.0.get() + 1);
+            self.0.set(turn);
+        }
+    }
 
-fn kill_monster(monster: &mut Object) -> Messages {
-    let mut messages = Messages::empty();
-    monster.alive = false;
-    let msg = format!("{} dies.", direct(&monster.name, true));
+    #[test]
+    fn hooks_run_at_the_end_of_every_round() {
+        let mut game = game();
+        let rounds = std::rc::Rc::default();
+        game.add_hook(Box::new(RoundCounter(std::rc::Rc::clone(&rounds))));
 
-    monster.char = '%';
-    monster.color = colors::RED;
-    monster.blocks = false;
-    monster.fighter = None;
-    monster.ai = None;
-    monster.name = format!("Remains of {}", monster.name);
+        for _ in 0..5 {
+            game.update(Action::Wait(PLAYER));
+        }
+        // Free actions do not end the round
+        game.update(Action::Nothing);
 
-    messages.add(msg, colors::RED);
-    messages
-}
+        assert_eq!(rounds.get(), 5);
+    }
 
-fn regenerate(object: &mut Object) -> Messages {
-    object.fighter.as_mut().map(|f| {
-        let amount = match f.health_regen {
-            p if p <= 1.0 => rng::chance(p) as i32,
-            v => v as i32,
-        };
-        f.heal(amount);
-    });
-    Messages::empty()
-}
+    #[test]
+    fn resting_heals_until_a_monster_shows_up() {
+        let mut game = game();
+        game.wander_interval = 0;
+        if let Some(fighter) = game.objects[PLAYER].fighter.as_mut() {
+            fighter.health = 1;
+        }
+        game.rest();
+        let fighter = game.objects[PLAYER].fighter.unwrap();
+        assert_eq!(fighter.health, fighter.max_health);
+        assert!(game.turn > 0);
 
-// --------------------------------- Movement ----------------------------------
-/// Distance between two points
-pub fn distance(a: &Location, b: &Location) -> f32 {
-    let Location(ax, ay) = a;
-    let Location(bx, by) = b;
-    let (dx, dy) = (bx - ax, by - ay);
-    ((dx.pow(2) + dy.pow(2)) as f32).sqrt()
-}
+        if let Some(fighter) = game.objects[PLAYER].fighter.as_mut() {
+            fighter.health = 1;
+        }
+        let loc = game.objects[PLAYER].loc;
+        let mut orc = Object::orc(loc);
+        orc.blocks = false;
+        game.spawn(orc);
+        game.refresh();
+        let turn = game.turn;
+        game.rest();
+        assert_eq!(game.turn, turn);
+        assert_eq!(game.objects[PLAYER].fighter.unwrap().health, 1);
+    }
 
-/// Calculate normalized direction between two points
-pub fn direction(a: &Location, b: &Location) -> Direction {
-    let Location(ax, ay) = a;
-    let Location(bx, by) = b;
+    #[test]
+    fn exploring_stops_for_items_monsters_and_the_end_of_the_map() {
+        let mut game = game();
+        game.wander_interval = 0;
+        game.peaceful = true;
+        game.clear_objects();
+        let mut map = Map::new(Dimension(40, 30), Tile::wall());
+        for x in 1..39 {
+            map.set(&Location(x, 5), Tile::empty());
+        }
+        game.relocate(PLAYER, Location(1, 5));
+        game.set_map(map);
+        game.spawn(Object::ration(Location(20, 5)));
 
-    let dx = match bx - ax {
-        x if x < 0 => -1,
-        x if x > 0 => 1,
-        _ => 0,
-    };
-    let dy = match by - ay {
-        y if y < 0 => -1,
-        y if y > 0 => 1,
-        _ => 0,
-    };
-    Direction(dx, dy)
-}
+        game.explore();
+        assert_eq!(game.objects[PLAYER].loc, Location(20, 5));
+        assert!(game
+            .messages
+            .iter()
+            .any(|(m, _)| m.ends_with("You find a ration.")));
 
-/// Get the destination when moving from a location in a given direction
-fn destination(location: &Location, direction: &Direction) -> Location {
-    let Location(x, y) = location;
-    let Direction(dx, dy) = direction;
-    Location(x + dx, y + dy)
-}
+        game.explore();
+        assert_eq!(game.nearest_unexplored(), None);
+        let end = game.objects[PLAYER].loc;
+        game.explore();
+        assert_eq!(game.objects[PLAYER].loc, end);
+        assert!(game
+            .messages
+            .iter()
+            .any(|(m, _)| m.ends_with("There is nothing left to explore.")));
 
-/// Move by the given amount
-fn move_by(id: usize, direction: Direction, map: &Map, objects: &mut [Object]) -> bool {
-    let destination = destination(&objects[id].loc, &direction);
-    if !(structure_blocks(&destination, map) || object_blocks(&destination, objects)) {
-        objects[id].loc = destination;
-        true
-    } else {
-        false
+        let mut map = Map::new(Dimension(40, 30), Tile::empty());
+        map.set(&Location(0, 0), Tile::wall());
+        game.set_map(map);
+        game.spawn(Object::orc(destination(&end, &Direction(-3, 0))));
+        game.refresh();
+        let turn = game.turn;
+        game.explore();
+        assert_eq!(game.turn, turn);
+        assert_eq!(game.objects[PLAYER].loc, end);
     }
-}
 
-// -------------------------------- Collision ---------------------------------
-/// Check if and object is at this position
-pub fn object_blocks(loc: &Location, objects: &[Object]) -> bool {
-    objects
-        .iter()
-        .filter(|object| object.blocks)
-        .any(|object| &object.loc == loc)
-}
+    #[test]
+    fn travelling_walks_to_stairs_that_have_been_seen() {
+        let mut game = game();
+        game.clear_objects();
+        game.wander_interval = 0;
+        game.peaceful = true;
+        let mut map = Map::new(Dimension(40, 30), Tile::wall());
+        // Walked before, travel only takes known ways
+        let mut floor = Tile::empty();
+        floor.explored = true;
+        for x in 1..39 {
+            map.set(&Location(x, 5), floor);
+        }
+        game.relocate(PLAYER, Location(1, 5));
+        game.set_map(map);
+        let last_message = |game: &Game| game.messages.iter().last().unwrap().0.clone();
 
-/// Check if a structure blocks at this position
-fn structure_blocks(loc: &Location, map: &Map) -> bool {
-    let Location(x, y) = *loc;
-    map[x as usize][y as usize].blocked
-}
+        game.travel_to_stairs();
+        assert_eq!(last_message(&game), "No known stairs.");
+        assert_eq!(game.turn, 0);
 
-/// Find the closest fighter within range
-pub fn fighters_by_distance(id: usize, objects: &[Object], range: i32) -> Vec<usize> {
-    let loc = &objects[id].loc;
-    let mut in_range: Vec<(i32, usize)> = objects
-        .iter()
-        .enumerate()
-        .filter(|&(i, _)| i != id) // don't target yourself
-        .filter(|(_, o)| o.fighter.is_some()) // only target fighters
-        .map(|(i, o)| (distance(loc, &o.loc) as i32, i)) // get the distance
-        .filter(|&(d, _)| d <= range) // only targets in range
-        .collect(); // collect into a vector to enable sorting
-    in_range.sort_by_key(|(d, _)| -d); // descending sort by distance
-    in_range.iter().map(|(_, i)| i).cloned().collect()
-}
+        let stairs = game.spawn(Object::stairs(Location(30, 5)));
+        game.objects[stairs].seen = true;
+        game.travel_to_stairs();
+        assert_eq!(game.objects[PLAYER].loc, Location(30, 5));
+        assert_eq!(
+            last_message(&game),
+            "You travel for 29 turns. You reach the stairs."
+        );
+    }
 
-/// Find the closest fighter within range
-fn closest_fighter(id: usize, objects: &[Object], range: i32) -> Option<usize> {
-    fighters_by_distance(id, objects, range).pop()
-}
+    #[test]
+    fn repeating_a_move_moves_again() {
+        let mut game = game();
+        let Location(x, y) = game.objects[PLAYER].loc;
 
-/// Find a random fighter within range
-fn random_fighter(id: usize, objects: &[Object], range: i32) -> Option<usize> {
-    let loc = &objects[id].loc;
-    let targets: Vec<usize> = objects
-        .iter()
-        .enumerate()
-        .map(|(i, o)| (i, &o.loc))
-        .filter(|(i, _)| *i != id)
-        .filter(|(_, l)| distance(loc, l) <= range as f32)
-        .map(|(i, _)| i)
-        .filter(|&t| objects[t].fighter.is_some())
-        .collect();
-    rng::choose(&targets).cloned()
-}
+        game.update(Action::Move(PLAYER, Direction(1, 0)));
+        game.repeat_last_action();
 
-/// Check if a place on the map is blocked
-fn is_blocked(loc: &Location, map: &Map, objects: &[Object]) -> bool {
-    structure_blocks(loc, map) || object_blocks(loc, objects)
-}
+        assert_eq!(game.objects[PLAYER].loc, Location(x + 2, y));
+    }
 
-fn indirect(it: &str, upper: bool) -> String {
-    let an = "aeiou".chars().find(|&c| it.starts_with(c)).is_some();
+    #[test]
+    fn lantern_lights_up_more_tiles() {
+        let mut game = game();
+        game.map = Map::new(game.map.dimensions(), Tile::empty());
+        game.init_fov();
+        game.refresh();
+        let visible = |game: &Game| game.map.tiles().filter(|t| t.visible).count();
+        let before = visible(&game);
 
-    let article = match (upper, an) {
-        (true, true) => "An",
-        (false, true) => "an",
-        (true, false) => "A",
-        (false, false) => "a",
-    };
-    format!("{} {}", article, it)
-}
+        game.inventory
+            .push(Object::lamp(Location(0, 0), Item::Lantern, "lantern"));
+        game.update(Action::UseItem(PLAYER, game.inventory.len() - 1));
 
-fn direct(it: &str, upper: bool) -> String {
-    let article = if upper { "The" } else { "the" };
-    format!("{} {}", article, it)
-}
+        assert_eq!(game.light_radius(), TORCH_RADIUS + LANTERN_BONUS);
+        assert!(visible(&game) > before);
+    }
 
-// --------------------------- Items and Abilities ----------------------------
-fn cast_heal(id: usize, _item_id: usize, game: &mut Game) -> (UseResult, Messages) {
-    game.objects[id]
-        .fighter
-        .as_mut()
-        .map(|fighter| {
-            if fighter.health == fighter.max_health {
-                (
-                    UseResult::Cancelled,
-                    Messages::new("Already at full health!", colors::WHITE),
-                )
-            } else {
-                fighter.heal(HEAL_AMOUNT);
-                (UseResult::UsedUp, Messages::new("Healed!", colors::WHITE))
-            }
-        })
-        .unwrap_or_else(|| {
-            (
-                UseResult::Cancelled,
-                Messages::new("Only fighters can drink!", colors::WHITE),
-            )
-        })
-}
+    #[test]
+    fn deeper_levels_are_darker() {
+        let mut game = game();
+        assert_eq!(game.light_radius(), TORCH_RADIUS);
+        let mut messages = Messages::empty();
+        for _ in 0..DARKNESS_INTERVAL {
+            messages.append(game.descend());
+        }
+        assert_eq!(game.light_radius(), TORCH_RADIUS - 1);
+        assert!(messages
+            .iter()
+            .any(|(text, _)| text.starts_with("The darkness closes in")));
 
-fn cast_lightning(id: usize, _item_id: usize, game: &mut Game) -> (UseResult, Messages) {
-    closest_fighter(id, &game.objects, LIGHTNING_RANGE)
-        .map(|target| {
-            game.objects[target]
-                .fighter
-                .as_mut()
-                .expect("Target must be a fighter")
-                .take_damage(LIGHTNING_DAMAGE);
-            (
-                UseResult::UsedUp,
-                Messages::new(
-                    format!("You zap {} ", direct(&game.objects[target].name, false)),
-                    colors::WHITE,
-                ),
-            )
-        })
-        .unwrap_or_else(|| {
-            (
-                UseResult::Cancelled,
-                Messages::new("There are no targets in range.", colors::WHITE),
-            )
-        })
-}
+        game.depth = 100;
+        assert_eq!(game.light_radius(), MIN_LIGHT_RADIUS);
+    }
 
-fn cast_confusion(id: usize, _item_id: usize, game: &mut Game) -> (UseResult, Messages) {
-    closest_fighter(id, &game.objects, CONFUSE_RANGE)
-        .map(|target| {
-            let ai = game.objects[target]
-                .ai
-                .take()
-                .expect("Fighters must have AI!");
+    #[test]
+    fn the_caves_change_color_on_the_way_down() {
+        assert_eq!(Theme::at_depth(1), Theme::STONE);
+        assert_eq!(Theme::at_depth(1 + THEME_DEPTHS), Theme::MOSS);
+        assert_eq!(Theme::at_depth(1 + 2 * THEME_DEPTHS), Theme::ICE);
+        assert_eq!(Theme::at_depth(100), Theme::LAVA);
 
-            game.objects[target].ai = Some(Ai::Confused {
-                previous: Box::new(ai),
-                num_turns: CONFUSE_NUM_TURNS,
-            });
-            (
-                UseResult::UsedUp,
-                Messages::new(
-                    format!(
-                        "{} looks confused.",
-                        direct(&game.objects[target].name, true)
-                    ),
-                    colors::WHITE,
-                ),
-            )
-        })
-        .unwrap_or_else(|| {
-            (
-                UseResult::Cancelled,
-                Messages::new("There are no targets in range.", colors::WHITE),
-            )
-        })
+        let between = Theme::at_depth(1 + THEME_DEPTHS / 2);
+        assert_ne!(between, Theme::STONE);
+        assert_ne!(between, Theme::MOSS);
+
+        let mut game = game();
+        game.depth = 1 + THEME_DEPTHS;
+        assert_eq!(game.theme(), Theme::MOSS);
+    }
 }
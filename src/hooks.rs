@@ -0,0 +1,17 @@
+//! Extension points in the turn sequence
+//!
+//! Hooks registered on the `Game` run custom per-turn logic, e.g.
+//! environmental effects or status ticks, without changing `Game::update`.
+use crate::game::Game;
+
+/// Logic that runs at fixed points of every turn
+///
+/// Both methods do nothing by default, so a hook only implements the
+/// points it cares about. `turn` is the number of turns played so far.
+pub trait TurnHook {
+    /// After the player's action has been played, before the monsters act
+    fn on_player_turn_end(&mut self, _game: &mut Game, _turn: i32) {}
+
+    /// After the monsters have acted and the turn is over
+    fn on_round_end(&mut self, _game: &mut Game, _turn: i32) {}
+}
@@ -0,0 +1,173 @@
+//! Mapping of keys to what they do in the game
+//!
+//! The defaults cover the vi-keys and the arrow keys for moving around. A
+//! binding can be replaced with `KeyBindings::bind`.
+use crate::{Direction, Key, KeyCode};
+
+/// Something the player can do with a single key press
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Command {
+    MoveNorth,
+    MoveSouth,
+    MoveWest,
+    MoveEast,
+    MoveNorthWest,
+    MoveNorthEast,
+    MoveSouthWest,
+    MoveSouthEast,
+    Wait,
+    Descend,
+    Ascend,
+    OpenInventory,
+    OpenCharacterScreen,
+    OpenMessageLog,
+    RepeatLastAction,
+    Rest,
+    /// Walk towards unexplored areas until something comes up
+    Explore,
+    /// Walk to the closest stairs that have been seen
+    TravelToStairs,
+    Save,
+    Aim,
+    Look,
+    /// Attack in the direction given by the next key
+    Attack,
+}
+
+impl Command {
+    /// Where a movement command goes
+    pub fn direction(&self) -> Option<Direction> {
+        use Command::*;
+        match self {
+            MoveNorth => Some(Direction(0, -1)),
+            MoveSouth => Some(Direction(0, 1)),
+            MoveWest => Some(Direction(-1, 0)),
+            MoveEast => Some(Direction(1, 0)),
+            MoveNorthWest => Some(Direction(-1, -1)),
+            MoveNorthEast => Some(Direction(1, -1)),
+            MoveSouthWest => Some(Direction(-1, 1)),
+            MoveSouthEast => Some(Direction(1, 1)),
+            _ => None,
+        }
+    }
+}
+
+/// A key as it is bound to a command
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Binding {
+    /// A printable character
+    Char(char),
+    /// A printable character typed while holding shift
+    Shift(char),
+    /// A key without a character, like the arrow keys
+    Code(KeyCode),
+}
+
+impl Binding {
+    pub fn of(key: &Key) -> Self {
+        match key.code {
+            KeyCode::Char if key.shift => Binding::Shift(key.printable),
+            KeyCode::Char => Binding::Char(key.printable),
+            code => Binding::Code(code),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct KeyBindings {
+    bindings: Vec<(Binding, Command)>,
+}
+
+impl Default for KeyBindings {
+    fn default() -> Self {
+        use Binding::*;
+        use Command::*;
+        let bindings = vec![
+            (Char('k'), MoveNorth),
+            (Char('j'), MoveSouth),
+            (Char('h'), MoveWest),
+            (Char('l'), MoveEast),
+            (Char('y'), MoveNorthWest),
+            (Char('u'), MoveNorthEast),
+            (Char('b'), MoveSouthWest),
+            (Char('n'), MoveSouthEast),
+            (Code(KeyCode::Up), MoveNorth),
+            (Code(KeyCode::Down), MoveSouth),
+            (Code(KeyCode::Left), MoveWest),
+            (Code(KeyCode::Right), MoveEast),
+            (Char('.'), Wait),
+            (Shift('.'), Descend),
+            (Char('>'), Descend),
+            (Shift(','), Ascend),
+            (Char('<'), Ascend),
+            (Char('i'), OpenInventory),
+            (Char('c'), OpenCharacterScreen),
+            (Char('m'), OpenMessageLog),
+            (Code(KeyCode::Spacebar), RepeatLastAction),
+            (Shift('r'), Rest),
+            (Char('o'), Explore),
+            (Char('t'), TravelToStairs),
+            (Shift('s'), Save),
+            (Char('f'), Aim),
+            (Char('x'), Look),
+            (Char('a'), Attack),
+        ];
+        Self { bindings }
+    }
+}
+
+impl KeyBindings {
+    /// Let a key trigger a command, replacing what it did before
+    pub fn bind(&mut self, binding: Binding, command: Command) {
+        self.bindings.retain(|(b, _)| *b != binding);
+        self.bindings.push((binding, command));
+    }
+
+    /// The command bound to a key press
+    pub fn command(&self, key: &Key) -> Option<Command> {
+        let binding = Binding::of(key);
+        self.bindings
+            .iter()
+            .find(|(b, _)| *b == binding)
+            .map(|(_, command)| *command)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn press(code: KeyCode, printable: char) -> Key {
+        let mut key = Key::default();
+        key.code = code;
+        key.printable = printable;
+        key
+    }
+
+    #[test]
+    fn arrows_and_vi_keys_both_move() {
+        let bindings = KeyBindings::default();
+        let up = bindings.command(&press(KeyCode::Up, '\0'));
+        let k = bindings.command(&press(KeyCode::Char, 'k'));
+        assert_eq!(up, Some(Command::MoveNorth));
+        assert_eq!(k, Some(Command::MoveNorth));
+        assert_eq!(up.and_then(|c| c.direction()), Some(Direction(0, -1)));
+    }
+
+    #[test]
+    fn keys_can_be_remapped() {
+        let mut bindings = KeyBindings::default();
+        bindings.bind(Binding::Char('k'), Command::Look);
+        bindings.bind(Binding::Char('w'), Command::MoveNorth);
+
+        assert_eq!(
+            bindings.command(&press(KeyCode::Char, 'k')),
+            Some(Command::Look)
+        );
+        assert_eq!(
+            bindings.command(&press(KeyCode::Char, 'w')),
+            Some(Command::MoveNorth)
+        );
+        assert_eq!(bindings.command(&press(KeyCode::Char, 'q')), None);
+    }
+}
@@ -5,11 +5,13 @@
 //! Game Loop
 //! ---------
 //!
+//! ```text
 //!     +---> P ---> A ---> I ---> U ---+
 //!     |                               |
 //!     ^          GAME LOOP            v
 //!     |                               |
 //!     +---------| running? |----------+
+//! ```
 //!
 //! * `Present` the scene to the user
 //! * `Accept` input from the user.
@@ -32,6 +34,7 @@
 //! which would then need to exit, before returning back to the original
 //! scene.
 //!
+//! ```text
 //!     |> Main Menu ! START
 //!     => Main Menu -> Game World ! ATTACK
 //!     => Main Menu -> Game World ! LEVEL UP
@@ -45,22 +48,33 @@
 //!     => Main Menu -> Game World ! EXIT
 //!     => Main Menu ! EXIT
 //!     <| OS
+//! ```
 
 pub use rostlaube::colors::{self, Color};
-pub use rostlaube::console::{BackgroundFlag, Console, Offscreen, TextAlignment};
+pub use rostlaube::console::{self, BackgroundFlag, Console, Offscreen, TextAlignment};
 pub use rostlaube::geometry::{Dimension, Direction, Location};
 pub use rostlaube::input::{self, Key, KeyCode};
 pub use rostlaube::map::{self, FovAlgorithm, Map as FovMap};
+pub use rostlaube::pathfinding::AStar;
 pub use rostlaube::rng;
 pub use rostlaube::ui;
 pub use rostlaube::{Event, State, Transition};
 
 // Internal
 pub mod ai;
+pub mod dialogue;
 pub mod dungeon;
 pub mod engine;
+pub mod event_log;
 pub mod game;
+pub mod hooks;
+pub mod keys;
+pub mod objects;
 mod scenes;
+pub mod sound;
+pub mod spatial;
+
+use std::cmp;
 
 use crate::game::Game;
 use scenes::GameSettings;
@@ -76,6 +90,14 @@ const LIMIT_FPS: i32 = 60;
 const MAP_WIDTH: i32 = 80;
 /// Height of the map
 const MAP_HEIGHT: i32 = 43;
+/// Largest width/height of a custom map
+const MAP_MAX_SIZE: i32 = 250;
+/// Longest seed that can be entered, enough for any `u64`
+const SEED_MAX_LEN: usize = 20;
+/// Deepest level a game can start on
+const MAX_STARTING_DEPTH: i32 = 20;
+/// Number of levels laid out up front when pre-generating the caves
+const PREGENERATED_LEVELS: i32 = 20;
 
 /// Maximum width/height of a room
 const ROOM_MAX_SIZE: i32 = 10;
@@ -88,34 +110,121 @@ const MAX_ROOM_MONSTERS: i32 = 3;
 /// Maximum number of items per room
 const MAX_ROOM_ITEMS: i32 = 2;
 
-/// Index of player in vector of objects
-const PLAYER: usize = 0; // The player will always be the first object
+/// Id of the player in the objects
+const PLAYER: objects::Id = objects::Id::FIRST; // The player will always be the first object
+
+/// Options given on the command line
+#[derive(Debug, Default)]
+pub struct Options {
+    /// Make the debug commands of the console available (`--wizard`)
+    pub wizard: bool,
+    /// Trace the engine loop to stdout (`--log`)
+    pub log: bool,
+    /// Monsters never act (`--peaceful`)
+    pub peaceful: bool,
+}
+
+impl Options {
+    pub fn from_args(args: impl Iterator<Item = String>) -> Self {
+        let mut options = Options::default();
+        for arg in args {
+            match arg.as_str() {
+                "--wizard" => options.wizard = true,
+                "--log" => options.log = true,
+                "--peaceful" => options.peaceful = true,
+                _ => {}
+            }
+        }
+        options
+    }
+}
+
+/// A seed is a number, or nothing at all for a random one
+fn valid_seed(input: &str) -> Result<(), String> {
+    let input = input.trim();
+    if input.is_empty() || input.parse::<u64>().is_ok() {
+        Ok(())
+    } else {
+        Err(String::from("The seed must be a whole number."))
+    }
+}
 
 /// Main entry point
-pub fn run() {
+pub fn run(options: Options) {
     let mut engine = rostlaube::Engine::new(SCREEN_WIDTH, SCREEN_HEIGHT, LIMIT_FPS);
+    engine.set_log(options.log);
 
-    engine
+    while let Some(game) = engine
         .run(Default::default(), scenes::main_menu())
         .and_then(|settings| match settings {
-            GameSettings::NewGame { player_name } => Some(Game::new(
-                &player_name,
-                Dimension(MAP_WIDTH, MAP_HEIGHT),
-                Dimension(ROOM_MIN_SIZE, ROOM_MAX_SIZE),
-                MAX_ROOMS,
-                MAX_ROOM_MONSTERS,
-                MAX_ROOM_ITEMS,
-            )),
-            GameSettings::LoadGame { path } => {
-                println!("Load game from: {:?}", path);
-                None
+            GameSettings::NewGame {
+                player_name,
+                map_size,
+                difficulty,
+                algorithm,
+                depth,
+                pregenerate,
+                confirm_traps,
+            } => {
+                // The same seed lays out the same caves
+                let seed = engine.prompt(
+                    "Enter a seed, or leave it empty for random caves:",
+                    SEED_MAX_LEN,
+                    valid_seed,
+                );
+                let seed: Option<u64> = seed.and_then(|s| s.trim().parse().ok());
+                let layout = dungeon::Layout {
+                    algorithm,
+                    ..dungeon::Layout::new(
+                        map_size,
+                        Dimension(ROOM_MIN_SIZE, ROOM_MAX_SIZE),
+                        MAX_ROOMS,
+                        MAX_ROOM_MONSTERS,
+                        MAX_ROOM_ITEMS,
+                    )
+                };
+                let mut game = if pregenerate {
+                    // Caves laid out up front are only worth sharing by seed
+                    let seed = seed.unwrap_or_else(rng::random);
+                    println!("Pre-generating the caves with seed {}", seed);
+                    let depth_count = cmp::max(depth, PREGENERATED_LEVELS);
+                    Game::new_full(seed, &player_name, layout, depth, difficulty, depth_count)
+                } else {
+                    if let Some(seed) = seed {
+                        rng::seed(seed);
+                    }
+                    Game::new(&player_name, layout, depth, difficulty)
+                };
+                if options.wizard {
+                    game.enable_wizard_mode();
+                }
+                game.peaceful = options.peaceful;
+                game.confirm_traps = confirm_traps;
+                Some(game)
             }
+            GameSettings::LoadGame { path } => match Game::load(&path) {
+                Ok(game) => Some(game),
+                Err(e) => {
+                    println!("Could not load game from {:?}: {}", path, e);
+                    None
+                }
+            },
         })
         .map(|game| engine.run(game, scenes::game_world()))
-        .map(|game| {
-            println!("Final game state:");
-            println!("{:?}", game);
-        });
+    {
+        if game.escaped {
+            println!(
+                "{} escaped the caves on turn {}.",
+                game.objects[PLAYER].name, game.turn
+            );
+        }
+        println!("Final game state:");
+        println!("{:?}", game);
+        if !game.restart {
+            break;
+        }
+        engine.resume();
+    }
 
     engine.exit();
 }
@@ -1,4 +1,4 @@
 /// Main entry point
 fn main() {
-    rustlike::run();
-}
\ No newline at end of file
+    rustlike::run(rustlike::Options::from_args(std::env::args()));
+}
@@ -0,0 +1,203 @@
+//! Storage of the objects on the map
+//!
+//! Objects are referred to by an `Id` that stays the same for as long as the
+//! object exists. Removing an object frees its slot for a new object, but the
+//! slot's generation is bumped so that an old id never resolves to the
+//! newcomer.
+use std::fmt;
+use std::ops::{Index, IndexMut};
+
+use serde::{Deserialize, Serialize};
+
+use crate::game::Object;
+
+/// Stable handle to an object
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct Id {
+    index: usize,
+    generation: u32,
+}
+
+impl Id {
+    /// The object created first, which is always the player
+    pub const FIRST: Id = Id {
+        index: 0,
+        generation: 0,
+    };
+}
+
+impl fmt::Display for Id {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}", self.index, self.generation)
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Slot {
+    generation: u32,
+    object: Option<Object>,
+}
+
+/// Objects by their id
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Objects {
+    slots: Vec<Slot>,
+    /// Indices of the slots that are empty
+    free: Vec<usize>,
+}
+
+impl Objects {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add an object, reusing a free slot if there is one
+    pub fn insert(&mut self, object: Object) -> Id {
+        match self.free.pop() {
+            Some(index) => {
+                let slot = &mut self.slots[index];
+                slot.generation += 1;
+                slot.object = Some(object);
+                Id {
+                    index,
+                    generation: slot.generation,
+                }
+            }
+            None => {
+                self.slots.push(Slot {
+                    generation: 0,
+                    object: Some(object),
+                });
+                Id {
+                    index: self.slots.len() - 1,
+                    generation: 0,
+                }
+            }
+        }
+    }
+
+    /// Take an object out, its id is not valid anymore afterwards
+    pub fn remove(&mut self, id: Id) -> Option<Object> {
+        let object = self
+            .slots
+            .get_mut(id.index)
+            .filter(|slot| slot.generation == id.generation)
+            .and_then(|slot| slot.object.take());
+        if object.is_some() {
+            self.free.push(id.index);
+        }
+        object
+    }
+
+    pub fn get(&self, id: Id) -> Option<&Object> {
+        self.slots
+            .get(id.index)
+            .filter(|slot| slot.generation == id.generation)
+            .and_then(|slot| slot.object.as_ref())
+    }
+
+    pub fn get_mut(&mut self, id: Id) -> Option<&mut Object> {
+        self.slots
+            .get_mut(id.index)
+            .filter(|slot| slot.generation == id.generation)
+            .and_then(|slot| slot.object.as_mut())
+    }
+
+    /// The object still exists
+    pub fn contains(&self, id: Id) -> bool {
+        self.get(id).is_some()
+    }
+
+    /// Number of objects
+    pub fn len(&self) -> usize {
+        self.slots.len() - self.free.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Ids of all objects, for going through them while changing the game
+    pub fn ids(&self) -> Vec<Id> {
+        self.iter().map(|(id, _)| id).collect()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (Id, &Object)> {
+        self.slots.iter().enumerate().filter_map(|(index, slot)| {
+            slot.object.as_ref().map(|object| {
+                let id = Id {
+                    index,
+                    generation: slot.generation,
+                };
+                (id, object)
+            })
+        })
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (Id, &mut Object)> {
+        self.slots
+            .iter_mut()
+            .enumerate()
+            .filter_map(|(index, slot)| {
+                let generation = slot.generation;
+                slot.object
+                    .as_mut()
+                    .map(|object| (Id { index, generation }, object))
+            })
+    }
+
+    /// All objects without their ids
+    pub fn values(&self) -> impl Iterator<Item = &Object> {
+        self.iter().map(|(_, object)| object)
+    }
+
+    /// Remove every object the predicate rejects
+    pub fn retain<F: FnMut(Id, &Object) -> bool>(&mut self, mut keep: F) {
+        for id in self.ids() {
+            if !keep(id, &self[id]) {
+                self.remove(id);
+            }
+        }
+    }
+}
+
+impl Index<Id> for Objects {
+    type Output = Object;
+
+    fn index(&self, id: Id) -> &Object {
+        self.get(id)
+            .unwrap_or_else(|| panic!("Object {} does not exist anymore", id))
+    }
+}
+
+impl IndexMut<Id> for Objects {
+    fn index_mut(&mut self, id: Id) -> &mut Object {
+        self.get_mut(id)
+            .unwrap_or_else(|| panic!("Object {} does not exist anymore", id))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Location;
+
+    #[test]
+    fn removed_ids_stay_invalid_when_the_slot_is_reused() {
+        let mut objects = Objects::new();
+        let player = objects.insert(Object::player(Location(0, 0), "Tester"));
+        let orc = objects.insert(Object::orc(Location(1, 0)));
+        let troll = objects.insert(Object::troll(Location(2, 0)));
+        assert_eq!(player, Id::FIRST);
+
+        assert!(objects.remove(orc).is_some());
+        let ogre = objects.insert(Object::ogre(Location(3, 0)));
+
+        assert!(!objects.contains(orc));
+        assert!(objects.get(orc).is_none());
+        assert!(objects.remove(orc).is_none());
+        assert_eq!(objects[ogre].name, "ogre");
+        assert_eq!(objects[troll].name, "troll");
+        assert_eq!(objects.len(), 3);
+    }
+}
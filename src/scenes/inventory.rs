@@ -0,0 +1,226 @@
+use super::*;
+
+/// Number of items listed at once, one for every letter
+const PAGE_SIZE: usize = 26;
+
+/// The player's pack, pushed on top of the game world
+///
+/// A pack with more items than there are letters is shown a page at a time.
+#[derive(Debug, Default)]
+pub struct Inventory {
+    page: usize,
+}
+
+impl Inventory {
+    /// Number of pages needed for the pack, at least one
+    fn pages(game: &Game) -> usize {
+        game.inventory.len().div_ceil(PAGE_SIZE).max(1)
+    }
+}
+
+#[derive(Debug)]
+pub enum Action {
+    Nothing,
+    Exit,
+    SelectItem(usize),
+    /// Turn the given number of pages forward, or back when negative
+    Page(isize),
+}
+
+impl State for Inventory {
+    type World = Game;
+    type Action = Action;
+
+    fn render(&self, con: &mut Offscreen, game: &Self::World) {
+        world::compose(con, game, |con| {
+            let names: Vec<String> = game
+                .inventory
+                .iter()
+                .skip(self.page * PAGE_SIZE)
+                .take(PAGE_SIZE)
+                .map(|o| match o.equipment {
+                    Some(e) if e.equipped => format!("{} (equipped)", o.name),
+                    _ => o.stack_name(),
+                })
+                .collect();
+            let pages = Inventory::pages(game);
+            let (header, options): (_, Vec<&str>) = if game.inventory.is_empty() {
+                (String::from("Your pack is empty.\n"), vec![])
+            } else if pages > 1 {
+                (
+                    format!(
+                        "Press the key next to an item to use it, or any other to cancel.\n\
+                         Page {}/{}, PageUp/PageDown or the wheel to turn.\n",
+                        self.page + 1,
+                        pages
+                    ),
+                    names.iter().map(|n| n.as_str()).collect(),
+                )
+            } else {
+                (
+                    String::from(
+                        "Press the key next to an item to use it, or any other to cancel.\n",
+                    ),
+                    names.iter().map(|n| n.as_str()).collect(),
+                )
+            };
+            let window = engine::menu_window(&header, &options, world::MENU_WIDTH);
+            let (width, height) = (window.width(), window.height());
+            let x = con.width() / 2 - width / 2;
+            let y = con.height() / 2 - height / 2;
+            console::blit(&window, (0, 0), (width, height), con, (x, y), 1.0, 0.7);
+        })
+    }
+
+    fn interpret(&self, event: &Event) -> Self::Action {
+        match event {
+            Event::KeyEvent(Key {
+                code: KeyCode::PageUp,
+                ..
+            }) => Action::Page(-1),
+            Event::KeyEvent(Key {
+                code: KeyCode::PageDown,
+                ..
+            }) => Action::Page(1),
+            Event::KeyEvent(Key { printable, .. }) if printable.is_ascii_lowercase() => {
+                Action::SelectItem(self.page * PAGE_SIZE + *printable as usize - 'a' as usize)
+            }
+            Event::Mouse(mouse) if mouse.wheel_up => Action::Page(-1),
+            Event::Mouse(mouse) if mouse.wheel_down => Action::Page(1),
+            Event::Mouse(_) => Action::Nothing,
+            _ => Action::Exit,
+        }
+    }
+
+    fn update(&mut self, action: Self::Action, game: &mut Self::World) -> Transition<Self> {
+        let transition = match action {
+            Action::SelectItem(index)
+                if game
+                    .inventory
+                    .get(index)
+                    .and_then(|o| o.item)
+                    .is_some_and(|i| i.needs_target()) =>
+            {
+                Transition::ReplaceWith(Box::new(world::Screen::Targeting {
+                    item: Some(index),
+                    cursor: game.objects[PLAYER].loc,
+                }))
+            }
+            Action::SelectItem(index)
+                if game.inventory.get(index).and_then(|o| o.item) == Some(game::Item::Enchant)
+                    && !game.enchantable().is_empty() =>
+            {
+                Transition::ReplaceWith(Box::new(world::Screen::Enchanting { scroll: index }))
+            }
+            Action::SelectItem(index) if index < game.inventory.len() => {
+                let action = match game.inventory[index].equipment {
+                    Some(e) if e.equipped => game::Action::Unequip(PLAYER, index),
+                    Some(_) => game::Action::Equip(PLAYER, index),
+                    None => game::Action::UseItem(PLAYER, index),
+                };
+                game.update(action);
+                Transition::Exit
+            }
+            Action::Page(pages) => {
+                let last = Inventory::pages(game) as isize - 1;
+                self.page = (self.page as isize + pages).clamp(0, last) as usize;
+                Transition::Continue
+            }
+            Action::Nothing => Transition::Continue,
+            _ => Transition::Exit,
+        };
+
+        if !matches!(transition, Transition::Continue) {
+            game.event_log.record(
+                game.turn,
+                EntryKind::Transition,
+                format!("{:?}: {:?}", self, transition),
+            );
+        }
+        transition
+    }
+
+    fn on_exit_requested(&mut self, _game: &mut Self::World) -> Transition<Self> {
+        Transition::NextWith(Box::new(world::Screen::ConfirmExit))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dungeon::Layout;
+    use crate::game::{Difficulty, Item, Object};
+    use crate::Location;
+
+    fn press(code: KeyCode, printable: char) -> Event {
+        let mut key = Key::default();
+        key.code = code;
+        key.printable = printable;
+        Event::KeyEvent(key)
+    }
+
+    #[test]
+    fn large_packs_are_shown_a_page_at_a_time() {
+        let mut game = Game::new(
+            "Tester",
+            Layout::new(Dimension(40, 30), Dimension(6, 8), 0, 0, 0),
+            1,
+            Difficulty::Normal,
+        );
+        game.inventory_capacity = 30;
+        for _ in 0..30 {
+            game.inventory
+                .push(Object::potion(Location(0, 0), Item::Heal, "healing potion"));
+        }
+        let mut inventory = Inventory::default();
+
+        let turn = inventory.interpret(&press(KeyCode::PageDown, '\0'));
+        inventory.update(turn, &mut game);
+        let turn = inventory.interpret(&press(KeyCode::PageDown, '\0'));
+        inventory.update(turn, &mut game);
+        assert_eq!(inventory.page, 1);
+        assert!(matches!(
+            inventory.interpret(&press(KeyCode::Char, 'a')),
+            Action::SelectItem(26)
+        ));
+
+        let turn = inventory.interpret(&press(KeyCode::PageUp, '\0'));
+        inventory.update(turn, &mut game);
+        assert!(matches!(
+            inventory.interpret(&press(KeyCode::Char, 'd')),
+            Action::SelectItem(3)
+        ));
+    }
+
+    #[test]
+    fn the_mouse_wheel_turns_the_pages() {
+        let mut game = Game::new(
+            "Tester",
+            Layout::new(Dimension(40, 30), Dimension(6, 8), 0, 0, 0),
+            1,
+            Difficulty::Normal,
+        );
+        game.inventory_capacity = 30;
+        for _ in 0..30 {
+            game.inventory
+                .push(Object::potion(Location(0, 0), Item::Heal, "healing potion"));
+        }
+        let mut inventory = Inventory::default();
+        let wheel = |up: bool| {
+            Event::Mouse(crate::input::Mouse {
+                wheel_up: up,
+                wheel_down: !up,
+                ..Default::default()
+            })
+        };
+
+        let turn = inventory.interpret(&wheel(false));
+        assert!(matches!(turn, Action::Page(1)));
+        inventory.update(turn, &mut game);
+        assert_eq!(inventory.page, 1);
+
+        let turn = inventory.interpret(&wheel(true));
+        inventory.update(turn, &mut game);
+        assert_eq!(inventory.page, 0);
+    }
+}
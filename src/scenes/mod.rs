@@ -1,9 +1,15 @@
 use crate::colors;
+use crate::console;
+use crate::engine;
+use crate::event_log::EntryKind;
 use crate::game;
+use crate::keys;
+use crate::objects::Id;
 use crate::{BackgroundFlag, Console, Offscreen, TextAlignment};
-use crate::{Direction, Game, PLAYER};
+use crate::{Dimension, Direction, FovAlgorithm, Game, Location, PLAYER};
 use crate::{Event, Key, KeyCode, State, Transition};
 
+mod inventory;
 mod settings;
 mod world;
 
@@ -12,6 +18,13 @@ pub use settings::GameSettings;
 pub fn main_menu() -> settings::Screen {
     settings::Screen::MainMenu {
         player_name: Default::default(),
+        map_size: Dimension(crate::MAP_WIDTH, crate::MAP_HEIGHT),
+        difficulty: Default::default(),
+        algorithm: Default::default(),
+        depth: 1,
+        pregenerate: false,
+        confirm_traps: true,
+        error: None,
     }
 }
 
@@ -1,14 +1,41 @@
 use super::*;
+use crate::dungeon::GenAlgorithm;
+use crate::game::Difficulty;
 
 #[derive(Debug)]
 pub enum GameSettings {
-    NewGame { player_name: String },
-    LoadGame { path: String },
+    NewGame {
+        player_name: String,
+        map_size: Dimension,
+        difficulty: Difficulty,
+        algorithm: GenAlgorithm,
+        /// Level the player starts on
+        depth: i32,
+        /// Lay out all levels before the game starts
+        pregenerate: bool,
+        /// Ask before stepping onto a known trap
+        confirm_traps: bool,
+    },
+    LoadGame {
+        path: String,
+    },
 }
 
 #[derive(Debug)]
 pub enum Screen {
-    MainMenu { player_name: String },
+    MainMenu {
+        player_name: String,
+        map_size: Dimension,
+        difficulty: Difficulty,
+        algorithm: GenAlgorithm,
+        /// Level the player starts on
+        depth: i32,
+        /// Lay out all levels before the game starts
+        pregenerate: bool,
+        /// Ask before stepping onto a known trap
+        confirm_traps: bool,
+        error: Option<String>,
+    },
 }
 
 #[derive(Debug)]
@@ -17,6 +44,14 @@ pub enum Action {
     StartGame,
     ReadChar(char, bool),
     DeleteChar,
+    SetMapSize(i32, i32),
+    SetAlgorithm(GenAlgorithm),
+    SetDepth(i32),
+    SetPregenerate(bool),
+    SetConfirmTraps(bool),
+    CycleDifficulty,
+    LoadGame(String),
+    InvalidCommand(String),
     InvalidKey,
 }
 
@@ -28,7 +63,16 @@ impl State for Screen {
         use Screen::*;
 
         match self {
-            MainMenu { player_name, .. } => {
+            MainMenu {
+                player_name,
+                map_size: Dimension(width, height),
+                difficulty,
+                algorithm,
+                depth,
+                pregenerate,
+                confirm_traps,
+                error,
+            } => {
                 con.set_default_background(colors::BLACK);
                 con.set_default_foreground(colors::WHITE);
 
@@ -60,6 +104,80 @@ impl State for Screen {
                     TextAlignment::Center,
                     format!("Enter name:\n{}", player_name),
                 );
+
+                con.print_ex(
+                    w / 2,
+                    h / 4 + num_lines_intro + 7,
+                    BackgroundFlag::Set,
+                    TextAlignment::Center,
+                    format!(
+                        "Map size: {}x{}\n(`size <width> <height>` in the console)",
+                        width, height
+                    ),
+                );
+
+                con.print_ex(
+                    w / 2,
+                    h / 4 + num_lines_intro + 10,
+                    BackgroundFlag::Set,
+                    TextAlignment::Center,
+                    format!("Difficulty: {:?}\n(Tab to change)", difficulty),
+                );
+
+                con.print_ex(
+                    w / 2,
+                    h / 4 + num_lines_intro + 13,
+                    BackgroundFlag::Set,
+                    TextAlignment::Center,
+                    format!(
+                        "Generator: {:?}\n(`generator <rooms|bsp>` in the console)",
+                        algorithm
+                    ),
+                );
+
+                con.print_ex(
+                    w / 2,
+                    h / 4 + num_lines_intro + 16,
+                    BackgroundFlag::Set,
+                    TextAlignment::Center,
+                    format!(
+                        "Starting depth: {}\n(`depth <level>` in the console)",
+                        depth
+                    ),
+                );
+
+                con.print_ex(
+                    w / 2,
+                    h / 4 + num_lines_intro + 19,
+                    BackgroundFlag::Set,
+                    TextAlignment::Center,
+                    format!(
+                        "Pre-generate levels: {}\n(`pregenerate <on|off>` in the console)",
+                        if *pregenerate { "on" } else { "off" }
+                    ),
+                );
+
+                con.print_ex(
+                    w / 2,
+                    h / 4 + num_lines_intro + 22,
+                    BackgroundFlag::Set,
+                    TextAlignment::Center,
+                    format!(
+                        "Confirm steps onto known traps: {}\n(`confirm <on|off>` in the console)",
+                        if *confirm_traps { "on" } else { "off" }
+                    ),
+                );
+
+                if let Some(error) = error {
+                    con.set_default_foreground(colors::RED);
+                    con.print_ex(
+                        w / 2,
+                        h / 4 + num_lines_intro + 25,
+                        BackgroundFlag::Set,
+                        TextAlignment::Center,
+                        error,
+                    );
+                }
             }
         }
     }
@@ -67,13 +185,14 @@ impl State for Screen {
     fn interpret(&self, event: &Event) -> Self::Action {
         use Action::*;
         use Event::*;
-        use KeyCode::{Backspace, Char, Enter, Escape, Spacebar};
+        use KeyCode::{Backspace, Char, Enter, Escape, Spacebar, Tab};
         use Screen::*;
 
         match self {
             MainMenu { .. } => match event {
                 KeyEvent(Key { code: Escape, .. }) => Cancel,
                 KeyEvent(Key { code: Enter, .. }) => StartGame,
+                KeyEvent(Key { code: Tab, .. }) => CycleDifficulty,
                 KeyEvent(Key {
                     code: Backspace, ..
                 }) => DeleteChar,
@@ -88,10 +207,7 @@ impl State for Screen {
                     shift,
                     ..
                 }) => ReadChar(*printable, *shift),
-                Command(c) => {
-                    println!("Execute {:?}", c);
-                    InvalidKey
-                }
+                Command(c) => execute(c),
                 _ => InvalidKey,
             },
         }
@@ -103,13 +219,79 @@ impl State for Screen {
         use Transition::*;
 
         match self {
-            MainMenu { player_name, .. } => match action {
+            MainMenu {
+                player_name,
+                map_size,
+                difficulty,
+                algorithm,
+                depth,
+                pregenerate,
+                confirm_traps,
+                error,
+            } => match action {
                 StartGame => {
                     settings.replace(GameSettings::NewGame {
                         player_name: player_name.clone(),
+                        map_size: *map_size,
+                        difficulty: *difficulty,
+                        algorithm: *algorithm,
+                        depth: *depth,
+                        pregenerate: *pregenerate,
+                        confirm_traps: *confirm_traps,
                     });
                     Exit
                 }
+                CycleDifficulty => {
+                    *difficulty = difficulty.next();
+                    Continue
+                }
+                SetMapSize(width, height) => {
+                    match validate_map_size(width, height) {
+                        Ok(size) => {
+                            *map_size = size;
+                            error.take();
+                        }
+                        Err(e) => {
+                            error.replace(e);
+                        }
+                    }
+                    Continue
+                }
+                SetAlgorithm(a) => {
+                    *algorithm = a;
+                    error.take();
+                    Continue
+                }
+                SetDepth(d) => {
+                    match validate_depth(d) {
+                        Ok(d) => {
+                            *depth = d;
+                            error.take();
+                        }
+                        Err(e) => {
+                            error.replace(e);
+                        }
+                    }
+                    Continue
+                }
+                SetPregenerate(p) => {
+                    *pregenerate = p;
+                    error.take();
+                    Continue
+                }
+                SetConfirmTraps(c) => {
+                    *confirm_traps = c;
+                    error.take();
+                    Continue
+                }
+                LoadGame(path) => {
+                    settings.replace(GameSettings::LoadGame { path });
+                    Exit
+                }
+                InvalidCommand(e) => {
+                    error.replace(e);
+                    Continue
+                }
                 DeleteChar => {
                     player_name.pop();
                     Continue
@@ -130,3 +312,135 @@ impl State for Screen {
         }
     }
 }
+
+fn execute(command: &str) -> Action {
+    let mut args = command.split_whitespace();
+    match (args.next(), args.next(), args.next(), args.next()) {
+        (Some("size"), Some(width), Some(height), None) => match (width.parse(), height.parse()) {
+            (Ok(width), Ok(height)) => Action::SetMapSize(width, height),
+            _ => Action::InvalidCommand(format!("Invalid map size: {:?}", command)),
+        },
+        (Some("depth"), Some(depth), None, None) => match depth.parse() {
+            Ok(depth) => Action::SetDepth(depth),
+            _ => Action::InvalidCommand(format!("Invalid depth: {:?}", command)),
+        },
+        (Some("load"), Some(path), None, None) => Action::LoadGame(path.into()),
+        (Some("generator"), Some(name), None, None) => match name {
+            "rooms" => Action::SetAlgorithm(GenAlgorithm::Rooms),
+            "bsp" => Action::SetAlgorithm(GenAlgorithm::Bsp),
+            _ => Action::InvalidCommand(format!("Unknown generator: {:?}", name)),
+        },
+        (Some("pregenerate"), Some(switch), None, None) => match switch {
+            "on" => Action::SetPregenerate(true),
+            "off" => Action::SetPregenerate(false),
+            _ => Action::InvalidCommand(format!("Expected on or off: {:?}", switch)),
+        },
+        (Some("confirm"), Some(switch), None, None) => match switch {
+            "on" => Action::SetConfirmTraps(true),
+            "off" => Action::SetConfirmTraps(false),
+            _ => Action::InvalidCommand(format!("Expected on or off: {:?}", switch)),
+        },
+        _ => Action::InvalidCommand(format!("Unknown command: {:?}", command)),
+    }
+}
+
+/// Check that a map can hold at least one room of the largest size
+fn validate_map_size(width: i32, height: i32) -> Result<Dimension, String> {
+    let min = crate::ROOM_MAX_SIZE + 1;
+    let max = crate::MAP_MAX_SIZE;
+    if width < min || height < min {
+        Err(format!("The map must be at least {}x{}.", min, min))
+    } else if width > max || height > max {
+        Err(format!("The map can be at most {}x{}.", max, max))
+    } else {
+        Ok(Dimension(width, height))
+    }
+}
+
+/// Check that the game starts on a level of the caves
+fn validate_depth(depth: i32) -> Result<i32, String> {
+    let max = crate::MAX_STARTING_DEPTH;
+    if (1..=max).contains(&depth) {
+        Ok(depth)
+    } else {
+        Err(format!("The starting depth must be between 1 and {}.", max))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn map_sizes_must_fit_a_room() {
+        let min = crate::ROOM_MAX_SIZE + 1;
+        assert_eq!(validate_map_size(min, 40), Ok(Dimension(min, 40)));
+        assert!(validate_map_size(min - 1, 40).is_err());
+        assert!(validate_map_size(40, 0).is_err());
+        assert!(validate_map_size(crate::MAP_MAX_SIZE + 1, 40).is_err());
+    }
+
+    #[test]
+    fn starting_depths_stay_within_the_caves() {
+        assert_eq!(validate_depth(1), Ok(1));
+        assert_eq!(
+            validate_depth(crate::MAX_STARTING_DEPTH),
+            Ok(crate::MAX_STARTING_DEPTH)
+        );
+        assert!(validate_depth(0).is_err());
+        assert!(validate_depth(crate::MAX_STARTING_DEPTH + 1).is_err());
+
+        let mut screen = crate::scenes::main_menu();
+        let mut settings = None;
+        let action = screen.interpret(&Event::Command("depth 5".into()));
+        screen.update(action, &mut settings);
+        let action = screen.interpret(&Event::Command("depth 0".into()));
+        screen.update(action, &mut settings);
+        screen.update(Action::StartGame, &mut settings);
+        assert!(matches!(
+            settings,
+            Some(GameSettings::NewGame { depth: 5, .. })
+        ));
+    }
+
+    #[test]
+    fn asking_before_stepping_onto_traps_can_be_turned_off() {
+        let mut screen = crate::scenes::main_menu();
+        let mut settings = None;
+        let action = screen.interpret(&Event::Command("confirm off".into()));
+        screen.update(action, &mut settings);
+        screen.update(Action::StartGame, &mut settings);
+        assert!(matches!(
+            settings,
+            Some(GameSettings::NewGame {
+                confirm_traps: false,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn levels_can_be_laid_out_before_the_game_starts() {
+        let mut screen = crate::scenes::main_menu();
+        let mut settings = None;
+        screen.update(Action::StartGame, &mut settings);
+        assert!(matches!(
+            settings,
+            Some(GameSettings::NewGame {
+                pregenerate: false,
+                ..
+            })
+        ));
+
+        let action = screen.interpret(&Event::Command("pregenerate on".into()));
+        screen.update(action, &mut settings);
+        screen.update(Action::StartGame, &mut settings);
+        assert!(matches!(
+            settings,
+            Some(GameSettings::NewGame {
+                pregenerate: true,
+                ..
+            })
+        ));
+    }
+}
@@ -1,21 +1,95 @@
 use super::*;
 
+/// Width of the inventory and level up menus
+pub(super) const MENU_WIDTH: i32 = 50;
+/// Number of messages to scroll with PageUp/PageDown in the message log
+const LOG_PAGE_SIZE: usize = 20;
+/// Number of messages to scroll per turn of the mouse wheel
+const LOG_WHEEL_STEP: isize = 3;
+
 #[derive(Debug)]
 pub enum Screen {
     GameWorld,
     Console,
-    Inventory,
     Character,
+    ConfirmExit,
+    /// Asking whether to climb out of the caves and end the game
+    ConfirmLeave,
+    /// Asking whether to walk onto a known trap
+    ConfirmStep {
+        direction: Direction,
+        trap: Id,
+    },
+    Dialogue {
+        npc: Id,
+        node: usize,
+    },
+    LevelUp,
+    /// Choosing the gear to read a scroll of enchantment on
+    Enchanting {
+        scroll: usize,
+    },
+    Targeting {
+        /// The item to use, or the ranged weapon if there is none
+        item: Option<usize>,
+        cursor: Location,
+    },
+    /// Examine what is on the tiles around the player
+    Look {
+        cursor: Location,
+    },
+    /// Waiting for the direction to attack in
+    Attacking,
+    /// Message history, scrolled back by `offset` messages from the latest
+    MessageLog {
+        offset: usize,
+    },
+    /// The player died, showing how far they got
+    GameOver,
 }
 
 #[derive(Debug)]
+#[allow(clippy::enum_variant_names)]
 pub enum Action {
     Nothing,
     Exit,
     OpenInventory,
     OpenCharacterScreen,
+    OpenMessageLog,
+    /// Scroll the message log back, or forward if negative
+    Scroll(isize),
     ListObjects,
+    ShowFingerprint,
+    ShowEventLog,
+    WriteEventLog(String),
     GameAction(game::Action),
+    Cheat(game::Cheat),
+    Quit,
+    /// Climb out of the caves, which wins the game
+    LeaveCaves,
+    /// Walk onto a known trap after all
+    StepOntoTrap,
+    /// Go back to the main menu for a new game
+    Restart,
+    /// A field of view algorithm was asked for by a name that is not known
+    UnknownFovAlgorithm(String),
+    RepeatLastAction,
+    Rest,
+    Explore,
+    TravelToStairs,
+    Respond(usize),
+    ChooseBonus(game::Bonus),
+    MoveCursor(Direction),
+    ConfirmTarget,
+    /// Pick a target for the ranged weapon
+    Aim,
+    LookAround,
+    /// Pick a direction to attack in without moving
+    ChooseAttackDirection,
+    /// The mouse pointer moved over a cell of the screen
+    Hover(Location),
+    /// A key to look up in the key bindings
+    Pressed(Key),
 }
 
 impl State for Screen {
@@ -26,46 +100,258 @@ impl State for Screen {
         use Screen::*;
 
         match self {
-            GameWorld => {
-                game.render_game_world(con);
-                game.render_messages(con);
-            }
-            Inventory => println!("Show inventory"),
-            Character => println!("Show character"),
+            GameWorld => compose(con, game, |_| {}),
+            LevelUp => compose(con, game, |con| {
+                let window = engine::menu_window(
+                    "Level up! Choose a stat to raise:\n",
+                    &[
+                        "Constitution (+20 HP)",
+                        "Strength (+1 attack)",
+                        "Agility (+1 defense)",
+                    ],
+                    MENU_WIDTH,
+                );
+                let (width, height) = (window.width(), window.height());
+                let x = con.width() / 2 - width / 2;
+                let y = con.height() / 2 - height / 2;
+                console::blit(&window, (0, 0), (width, height), con, (x, y), 1.0, 0.7);
+            }),
+            Enchanting { .. } => compose(con, game, |con| {
+                let names: Vec<&str> = game
+                    .enchantable()
+                    .into_iter()
+                    .map(|i| game.inventory[i].name.as_str())
+                    .collect();
+                let window = engine::menu_window(
+                    "Enchant which item? (Escape to cancel)\n",
+                    &names,
+                    MENU_WIDTH,
+                );
+                let (width, height) = (window.width(), window.height());
+                let x = con.width() / 2 - width / 2;
+                let y = con.height() / 2 - height / 2;
+                console::blit(&window, (0, 0), (width, height), con, (x, y), 1.0, 0.7);
+            }),
+            Targeting { item, cursor } => compose(con, game, |con| {
+                draw_path(con, game, cursor);
+                draw_cursor(con, game, cursor);
+                let prompt = match item {
+                    Some(item) => {
+                        format!("Choose a target for the {}.", game.inventory[*item].name)
+                    }
+                    None => String::from("Choose a target to shoot at."),
+                };
+                con.set_default_foreground(colors::WHITE);
+                con.print_ex(
+                    con.width() / 2,
+                    1,
+                    BackgroundFlag::None,
+                    TextAlignment::Center,
+                    format!("{} (Enter to confirm, Escape to cancel)", prompt),
+                );
+            }),
+            Look { cursor } => compose(con, game, |con| {
+                draw_path(con, game, cursor);
+                draw_cursor(con, game, cursor);
+                con.set_default_foreground(colors::WHITE);
+                con.print_ex(
+                    con.width() / 2,
+                    1,
+                    BackgroundFlag::None,
+                    TextAlignment::Center,
+                    "Look around. (Escape to stop)",
+                );
+            }),
+            Attacking => compose(con, game, |con| {
+                con.set_default_foreground(colors::WHITE);
+                con.print_ex(
+                    con.width() / 2,
+                    1,
+                    BackgroundFlag::None,
+                    TextAlignment::Center,
+                    "Attack in which direction? (Escape to cancel)",
+                );
+            }),
+            Character => game.render_character(con),
+            MessageLog { offset } => game.render_message_log(con, *offset),
             Console => println!("Show console"),
+            ConfirmExit => compose(con, game, |con| {
+                con.set_default_foreground(colors::WHITE);
+                con.print_ex(
+                    con.width() / 2,
+                    con.height() / 2,
+                    BackgroundFlag::None,
+                    TextAlignment::Center,
+                    "Really quit? Unsaved progress will be lost. (y/n)",
+                );
+            }),
+            ConfirmLeave => compose(con, game, |con| {
+                con.set_default_foreground(colors::WHITE);
+                con.print_ex(
+                    con.width() / 2,
+                    con.height() / 2,
+                    BackgroundFlag::None,
+                    TextAlignment::Center,
+                    "Leave the caves? (escape to the surface) (y/n)",
+                );
+            }),
+            ConfirmStep { trap, .. } => compose(con, game, |con| {
+                con.set_default_foreground(colors::WHITE);
+                con.print_ex(
+                    con.width() / 2,
+                    con.height() / 2,
+                    BackgroundFlag::None,
+                    TextAlignment::Center,
+                    format!("Really step onto the {}? (y/n)", game.objects[*trap].name),
+                );
+            }),
+            GameOver => compose(con, game, |con| {
+                let xp = game.objects[PLAYER].fighter.map_or(0, |f| f.xp);
+                let lines = [
+                    String::from("You died."),
+                    String::new(),
+                    format!("Depth reached:  {}", game.depth),
+                    format!("Turns survived: {}", game.turn),
+                    format!("Experience:     {}", xp),
+                    String::new(),
+                    String::from("(r) Restart  (q) Quit"),
+                ];
+                let y = con.height() / 2 - lines.len() as i32 / 2;
+                con.set_default_background(colors::BLACK);
+                con.set_default_foreground(colors::WHITE);
+                for (i, line) in lines.iter().enumerate() {
+                    con.print_ex(
+                        con.width() / 2,
+                        y + i as i32,
+                        BackgroundFlag::Set,
+                        TextAlignment::Center,
+                        line,
+                    );
+                }
+            }),
+            Dialogue { npc, node } => compose(con, game, |con| {
+                let npc = &game.objects[*npc];
+                let node = &npc.dialogue.expect("NPCs have something to say")[*node];
+                let mut lines = vec![format!("{}: \"{}\"", npc.name, node.text), String::new()];
+                for (i, response) in node.responses.iter().enumerate() {
+                    lines.push(format!("{}) {}", i + 1, response.text));
+                }
+                let y = con.height() - 2 - lines.len() as i32;
+                con.set_default_background(colors::BLACK);
+                con.set_default_foreground(colors::WHITE);
+                for (i, line) in lines.iter().enumerate() {
+                    con.print_ex(
+                        2,
+                        y + i as i32,
+                        BackgroundFlag::Set,
+                        TextAlignment::Left,
+                        line,
+                    );
+                }
+            }),
         };
     }
 
     fn interpret(&self, event: &Event) -> Self::Action {
         use Action::*;
         use Event::*;
-        use KeyCode::{Char, Escape};
+        use KeyCode::{Char, Enter, Escape, PageDown, PageUp};
         use Screen::*;
 
         match self {
             GameWorld => match event {
                 KeyEvent(Key { code: Escape, .. }) => Exit,
+                KeyEvent(key) => Pressed(*key),
+                Mouse(mouse) => Hover(Location(mouse.cx as i32, mouse.cy as i32)),
+                Event::Nothing => Action::Nothing,
+                Command(c) => execute(c),
+            },
+            LevelUp => match event {
+                KeyEvent(Key { printable: 'a', .. }) => ChooseBonus(game::Bonus::Constitution),
+                KeyEvent(Key { printable: 'b', .. }) => ChooseBonus(game::Bonus::Strength),
+                KeyEvent(Key { printable: 'c', .. }) => ChooseBonus(game::Bonus::Agility),
+                _ => Action::Nothing,
+            },
+            Targeting { .. } => match event {
+                KeyEvent(Key { code: Escape, .. }) => Exit,
+                KeyEvent(Key { code: Enter, .. }) => ConfirmTarget,
+                KeyEvent(key) => Pressed(*key),
+                _ => Action::Nothing,
+            },
+            Look { .. } | Attacking => match event {
+                KeyEvent(Key { code: Escape, .. }) => Exit,
+                KeyEvent(key) => Pressed(*key),
+                _ => Action::Nothing,
+            },
+            MessageLog { .. } => match event {
+                KeyEvent(Key { code: Escape, .. }) => Exit,
+                KeyEvent(Key { code: PageUp, .. }) => Scroll(LOG_PAGE_SIZE as isize),
+                KeyEvent(Key { code: PageDown, .. }) => Scroll(-(LOG_PAGE_SIZE as isize)),
+                Mouse(mouse) if mouse.wheel_up => Scroll(LOG_WHEEL_STEP),
+                Mouse(mouse) if mouse.wheel_down => Scroll(-LOG_WHEEL_STEP),
+                KeyEvent(key) => Pressed(*key),
+                _ => Action::Nothing,
+            },
+            Character | Console => match event {
+                KeyEvent(_) | Command(_) => Exit,
+                _ => Action::Nothing,
+            },
+            ConfirmExit => match event {
                 KeyEvent(Key {
                     code: Char,
-                    printable: 'i',
+                    printable: 'y',
                     ..
-                }) => OpenInventory,
+                }) => Quit,
+                KeyEvent(_) | Command(_) => Exit,
+                _ => Action::Nothing,
+            },
+            ConfirmLeave => match event {
                 KeyEvent(Key {
                     code: Char,
-                    printable: 'c',
+                    printable: 'y',
                     ..
-                }) => OpenCharacterScreen,
+                }) => LeaveCaves,
+                KeyEvent(_) | Command(_) => Exit,
+                _ => Action::Nothing,
+            },
+            ConfirmStep { .. } => match event {
                 KeyEvent(Key {
                     code: Char,
-                    printable: c,
+                    printable: 'y',
                     ..
-                }) => game_action(c),
-                KeyEvent(_) | Event::Nothing => Action::Nothing,
-                Command(c) => execute(c),
+                }) => StepOntoTrap,
+                KeyEvent(_) | Command(_) => Exit,
+                _ => Action::Nothing,
+            },
+            GameOver => match event {
+                KeyEvent(Key {
+                    code: Char,
+                    printable: 'r',
+                    ..
+                }) => Restart,
+                KeyEvent(Key {
+                    code: Char,
+                    printable: 'q',
+                    ..
+                })
+                | KeyEvent(Key { code: Escape, .. }) => Quit,
+                _ => Action::Nothing,
+            },
+            Enchanting { .. } => match event {
+                KeyEvent(Key { code: Escape, .. }) => Exit,
+                KeyEvent(Key { printable, .. }) if printable.is_ascii_lowercase() => {
+                    Respond(*printable as usize - 'a' as usize)
+                }
+                _ => Action::Nothing,
+            },
+            Dialogue { .. } => match event {
+                KeyEvent(Key { code: Escape, .. }) => Exit,
+                KeyEvent(Key { printable, .. }) => match printable.to_digit(10) {
+                    Some(n) if n > 0 => Respond(n as usize - 1),
+                    _ => Action::Nothing,
+                },
+                _ => Action::Nothing,
             },
-            Inventory => Exit,
-            Character => Exit,
-            Console => Exit,
         }
     }
 
@@ -73,55 +359,607 @@ impl State for Screen {
         use Action::*;
         use Screen::*;
 
-        match self {
+        // Keys mean different things depending on the screen
+        let action = match action {
+            Pressed(key) => match game.key_bindings.command(&key) {
+                Some(command) => self.bound_action(command),
+                None => Nothing,
+            },
+            action => action,
+        };
+        let transition = match self {
             GameWorld => match action {
-                Exit => Transition::Exit,
-                Nothing => Transition::Continue,
-                OpenInventory => Transition::Next(Inventory),
+                // Leaving the game world ends the game, so make sure first
+                Exit => Transition::Next(ConfirmExit),
+                Quit => Transition::Quit,
+                Nothing | Respond(_) | ChooseBonus(_) | MoveCursor(_) | ConfirmTarget
+                | Scroll(_) | Pressed(_) | LeaveCaves | StepOntoTrap | Restart => {
+                    Transition::Continue
+                }
+                Hover(cell) => {
+                    game.pointer = Some(cell);
+                    Transition::Continue
+                }
+                ChooseAttackDirection => Transition::Next(Attacking),
+                OpenMessageLog => Transition::Next(MessageLog { offset: 0 }),
+                OpenInventory => Transition::NextWith(Box::new(inventory::Inventory::default())),
                 OpenCharacterScreen => Transition::Next(Character),
+                LookAround => Transition::Next(Look {
+                    cursor: game.objects[PLAYER].loc,
+                }),
+                Aim => match game.ranged_weapon(PLAYER) {
+                    Some(_) if game.arrows() == 0 => {
+                        game.messages.add("Out of arrows.", colors::WHITE);
+                        Transition::Continue
+                    }
+                    Some(_) => Transition::Next(Targeting {
+                        item: None,
+                        cursor: game.objects[PLAYER].loc,
+                    }),
+                    None => {
+                        game.messages
+                            .add("You have nothing to shoot with.", colors::WHITE);
+                        Transition::Continue
+                    }
+                },
                 GameAction(action) => {
-                    game.update(action);
+                    let talk_to = match action {
+                        game::Action::Move(PLAYER, direction) => {
+                            game.talk_target(PLAYER, direction)
+                        }
+                        _ => None,
+                    };
+                    let trap = match action {
+                        game::Action::Move(PLAYER, direction) if game.confirm_traps => {
+                            let loc = game::destination(&game.objects[PLAYER].loc, &direction);
+                            game.known_trap_at(&loc).map(|trap| (direction, trap))
+                        }
+                        _ => None,
+                    };
+                    match (talk_to, trap) {
+                        (Some(npc), _) => Transition::Next(Dialogue { npc, node: 0 }),
+                        (None, Some((direction, trap))) => {
+                            Transition::Next(ConfirmStep { direction, trap })
+                        }
+                        (None, None)
+                            if matches!(action, game::Action::Ascend(PLAYER)) && game.at_exit() =>
+                        {
+                            Transition::Next(ConfirmLeave)
+                        }
+                        (None, None) => {
+                            game.update(action);
+                            check_level_up(game)
+                        }
+                    }
+                }
+                RepeatLastAction => {
+                    game.repeat_last_action();
+                    check_level_up(game)
+                }
+                Rest => {
+                    game.rest();
+                    check_level_up(game)
+                }
+                Explore => {
+                    game.explore();
+                    check_level_up(game)
+                }
+                TravelToStairs => {
+                    game.travel_to_stairs();
+                    check_level_up(game)
+                }
+                UnknownFovAlgorithm(name) => {
+                    game.messages
+                        .add(format!("Unknown FOV algorithm: {}", name), colors::RED);
                     Transition::Continue
-                },
+                }
+                Cheat(cheat) => {
+                    game.cheat(cheat);
+                    check_level_up(game)
+                }
+                ShowEventLog => {
+                    for entry in game.event_log.iter() {
+                        println!("{}", entry);
+                    }
+                    Transition::Continue
+                }
+                WriteEventLog(path) => {
+                    match game.event_log.write_to(&path) {
+                        Ok(()) => println!("Event log written to {:?}", path),
+                        Err(e) => println!("Could not write event log to {:?}: {}", path, e),
+                    }
+                    Transition::Continue
+                }
                 ListObjects => {
-                    for (i, o) in game.objects.iter().enumerate() {
-                        println!("{}: {:?}", i, o);
+                    for (id, o) in game.objects.iter() {
+                        println!("{}: {:?}", id, o);
+                    }
+                    Transition::Continue
+                }
+                ShowFingerprint => {
+                    println!("Turn {}: {:016x}", game.turn, game.fingerprint());
+                    Transition::Continue
+                }
+            },
+            LevelUp => match action {
+                ChooseBonus(bonus) => {
+                    game.level_up(bonus);
+                    if game.level_up_pending() {
+                        Transition::Continue
+                    } else {
+                        Transition::Exit
                     }
+                }
+                _ => Transition::Continue,
+            },
+            Targeting { item, cursor } => match action {
+                MoveCursor(direction) => {
+                    if let Some(loc) = step_cursor(cursor, &direction, game) {
+                        *cursor = loc;
+                    }
+                    Transition::Continue
+                }
+                ConfirmTarget => match (*item, game.target_at(cursor)) {
+                    (Some(item), Some(target)) => {
+                        game.update(game::Action::UseItemOn(PLAYER, item, Some(target)));
+                        Transition::Exit
+                    }
+                    (Some(_), None) => Transition::Continue,
+                    (None, _) => {
+                        game.update(game::Action::Shoot(PLAYER, *cursor));
+                        Transition::Exit
+                    }
+                },
+                Exit => {
+                    if let Some(item) = *item {
+                        game.update(game::Action::UseItemOn(PLAYER, item, None));
+                    }
+                    Transition::Exit
+                }
+                _ => Transition::Continue,
+            },
+            Look { cursor } => match action {
+                MoveCursor(direction) => {
+                    let explored =
+                        |loc: &Location| game.map.get(loc).is_some_and(|tile| tile.explored);
+                    if let Some(loc) = step_cursor(cursor, &direction, game).filter(explored) {
+                        *cursor = loc;
+                        let description = game.describe(cursor);
+                        game.messages.add(description, colors::WHITE);
+                        if *cursor != game.objects[PLAYER].loc {
+                            let sight = game.describe_sight(cursor);
+                            game.messages.add(sight, colors::LIGHT_GREY);
+                        }
+                        if let Some(odds) = game.describe_odds(cursor) {
+                            game.messages.add(odds, colors::LIGHT_GREY);
+                        }
+                    }
+                    Transition::Continue
+                }
+                Exit => Transition::Exit,
+                _ => Transition::Continue,
+            },
+            Attacking => match action {
+                GameAction(action) => {
+                    game.update(action);
+                    if game.level_up_pending() {
+                        Transition::Replace(LevelUp)
+                    } else {
+                        Transition::Exit
+                    }
+                }
+                Exit => Transition::Exit,
+                _ => Transition::Continue,
+            },
+            MessageLog { offset } => match action {
+                Scroll(lines) => {
+                    let max = game.messages.len().saturating_sub(1) as isize;
+                    *offset = (*offset as isize + lines).clamp(0, max) as usize;
                     Transition::Continue
                 }
+                Exit => Transition::Exit,
+                _ => Transition::Continue,
+            },
+            Character | Console => match action {
+                Nothing => Transition::Continue,
+                _ => Transition::Exit,
+            },
+            ConfirmExit => match action {
+                Quit => Transition::Quit,
+                Nothing => Transition::Continue,
+                _ => Transition::Exit,
+            },
+            ConfirmLeave => match action {
+                LeaveCaves => {
+                    game.update(game::Action::Ascend(PLAYER));
+                    Transition::Quit
+                }
+                Nothing => Transition::Continue,
+                _ => Transition::Exit,
+            },
+            ConfirmStep { direction, .. } => match action {
+                StepOntoTrap => {
+                    game.update(game::Action::Move(PLAYER, *direction));
+                    if game.level_up_pending() {
+                        Transition::Replace(LevelUp)
+                    } else {
+                        Transition::Exit
+                    }
+                }
+                Nothing => Transition::Continue,
+                _ => Transition::Exit,
+            },
+            GameOver => match action {
+                Restart => {
+                    game.restart = true;
+                    Transition::Quit
+                }
+                Quit => Transition::Quit,
+                _ => Transition::Continue,
+            },
+            Enchanting { scroll } => match action {
+                Respond(choice) => match game.enchantable().get(choice) {
+                    Some(&gear) => {
+                        game.update(game::Action::Enchant(PLAYER, *scroll, gear));
+                        Transition::Exit
+                    }
+                    None => Transition::Continue,
+                },
+                Exit => Transition::Exit,
+                _ => Transition::Continue,
+            },
+            Dialogue { npc, node } => match action {
+                Respond(choice) => {
+                    let dialogue = game.objects[*npc]
+                        .dialogue
+                        .expect("NPCs have something to say");
+                    if choice >= dialogue[*node].responses.len() {
+                        Transition::Continue
+                    } else {
+                        match crate::dialogue::respond(game, dialogue, *node, choice) {
+                            Some(next) => {
+                                *node = next;
+                                Transition::Continue
+                            }
+                            None => Transition::Exit,
+                        }
+                    }
+                }
+                Exit => Transition::Exit,
+                _ => Transition::Continue,
+            },
+        };
+
+        // The dead can only look back on their game
+        let transition = match transition {
+            Transition::Quit => Transition::Quit,
+            _ if !game.objects[PLAYER].alive && !matches!(self, GameOver) => {
+                Transition::Replace(GameOver)
+            }
+            transition => transition,
+        };
+
+        if !matches!(transition, Transition::Continue) {
+            game.event_log.record(
+                game.turn,
+                EntryKind::Transition,
+                format!("{:?}: {:?}", self, transition),
+            );
+        }
+        transition
+    }
+
+    fn animate(&mut self, con: &mut Offscreen, game: &mut Self::World) -> bool {
+        game.animate(con)
+    }
+
+    fn on_exit_requested(&mut self, _game: &mut Self::World) -> Transition<Self> {
+        match self {
+            Screen::ConfirmExit => Transition::Quit,
+            _ => Transition::Next(Screen::ConfirmExit),
+        }
+    }
+}
+
+impl Screen {
+    /// What a bound key does on this screen
+    fn bound_action(&self, command: keys::Command) -> Action {
+        use keys::Command::*;
+        match (self, command.direction()) {
+            (Screen::GameWorld, Some(direction)) => {
+                Action::GameAction(game::Action::Move(PLAYER, direction))
+            }
+            (Screen::GameWorld, None) => match command {
+                Wait => Action::GameAction(game::Action::Wait(PLAYER)),
+                Descend => Action::GameAction(game::Action::Descend(PLAYER)),
+                Ascend => Action::GameAction(game::Action::Ascend(PLAYER)),
+                Save => Action::GameAction(game::Action::Save),
+                OpenInventory => Action::OpenInventory,
+                OpenCharacterScreen => Action::OpenCharacterScreen,
+                OpenMessageLog => Action::OpenMessageLog,
+                RepeatLastAction => Action::RepeatLastAction,
+                Rest => Action::Rest,
+                Explore => Action::Explore,
+                TravelToStairs => Action::TravelToStairs,
+                Aim => Action::Aim,
+                Look => Action::LookAround,
+                Attack => Action::ChooseAttackDirection,
+                _ => Action::Nothing,
             },
-            Inventory => Transition::Exit,
-            Character => Transition::Exit,
-            Console => Transition::Exit,
+            (Screen::Targeting { .. }, Some(direction))
+            | (Screen::Look { .. }, Some(direction)) => Action::MoveCursor(direction),
+            (Screen::Attacking, Some(direction)) => {
+                Action::GameAction(game::Action::AttackDirection(PLAYER, direction))
+            }
+            (Screen::MessageLog { .. }, Some(Direction(0, dy))) => Action::Scroll(-dy as isize),
+            _ => Action::Nothing,
         }
     }
 }
 
-fn game_action(c: &char) -> Action {
-    use game::Action::*;
-    let a = match c {
-        'k' => Move(PLAYER, Direction(0, -1)),
-        'j' => Move(PLAYER, Direction(0, 1)),
-        'h' => Move(PLAYER, Direction(-1, 0)),
-        'l' => Move(PLAYER, Direction(1, 0)),
-        'y' => Move(PLAYER, Direction(-1, -1)),
-        'u' => Move(PLAYER, Direction(1, -1)),
-        'b' => Move(PLAYER, Direction(-1, 1)),
-        'n' => Move(PLAYER, Direction(1, 1)),
-        _ => game::Action::Nothing,
-    };
-    Action::GameAction(a)
+/// Let the player pick a bonus once they gathered enough experience
+fn check_level_up(game: &Game) -> Transition<Screen> {
+    if game.level_up_pending() {
+        Transition::Next(Screen::LevelUp)
+    } else {
+        Transition::Continue
+    }
+}
+
+/// Move a cursor one step, but not off the map
+fn step_cursor(cursor: &Location, direction: &Direction, game: &Game) -> Option<Location> {
+    Some(game::destination(cursor, direction))
+        .filter(|loc| game::in_bounds(loc, &game.map.dimensions()))
+}
+
+/// Highlight the tile under a cursor
+fn draw_cursor(con: &mut Offscreen, game: &Game, cursor: &Location) {
+    let size = Dimension(con.width(), con.height());
+    let focus = &game.view_focus(&size);
+    if let Some(Location(x, y)) =
+        rostlaube::geometry::translate(&game.map.dimensions(), &size, cursor, focus)
+    {
+        con.set_char_background(x, y, colors::LIGHT_YELLOW, BackgroundFlag::Set);
+    }
+}
+
+/// Shade the line from the player to a cursor, red from where it is blocked
+fn draw_path(con: &mut Offscreen, game: &Game, cursor: &Location) {
+    let size = Dimension(con.width(), con.height());
+    let focus = &game.view_focus(&size);
+    let mut blocked = false;
+    for loc in game::line(&game.objects[PLAYER].loc, cursor) {
+        if let Some(Location(x, y)) =
+            rostlaube::geometry::translate(&game.map.dimensions(), &size, &loc, focus)
+        {
+            let color = if blocked {
+                colors::DARK_RED
+            } else {
+                colors::DARK_YELLOW
+            };
+            con.set_char_background(x, y, color, BackgroundFlag::Set);
+        }
+        blocked |= game.map.get(&loc).is_none_or(|tile| tile.block_sight);
+    }
+}
+
+/// Draw the world, then the overlay on top of it, then the messages
+pub(super) fn compose<F: FnOnce(&mut Offscreen)>(con: &mut Offscreen, game: &Game, overlay: F) {
+    game.render_game_world(con);
+    overlay(con);
+    game.render_messages(con);
+    game.render_status(con);
 }
 
 fn execute(command: &str) -> Action {
-    match command {
-        "ls" => {
+    use game::Cheat::*;
+    let mut args = command.split_whitespace();
+    match (args.next(), args.next(), args.next()) {
+        (Some("ls"), None, None) => {
             println!("List objects");
             Action::ListObjects
         }
+        (Some("hash"), None, None) => Action::ShowFingerprint,
+        (Some("log"), None, None) => Action::ShowEventLog,
+        (Some("log"), Some(path), None) => Action::WriteEventLog(path.into()),
+        (Some("falloff"), None, None) => Action::Cheat(LightFalloff),
+        (Some("fov"), Some(name), None) => match fov_algorithm(name) {
+            Some(algorithm) => Action::Cheat(Fov(algorithm)),
+            None => Action::UnknownFovAlgorithm(name.into()),
+        },
+        (Some("ambient"), None, None) => Action::Cheat(Ambient),
+        (Some("sight"), None, None) => Action::Cheat(CreaturesBlockSight),
+        (Some("peaceful"), None, None) => Action::Cheat(Peaceful),
+        (Some("autoexplore"), None, None) => Action::Cheat(Explore),
+        (Some("travel"), None, None) => Action::Cheat(Travel),
+        (Some("reveal"), None, None) => Action::Cheat(Reveal),
+        (Some("god"), None, None) => Action::Cheat(Godmode),
+        (Some("spawn"), Some(kind), None) => Action::Cheat(Spawn(kind.into())),
+        (Some("give"), Some(kind), None) => Action::Cheat(Give(kind.into())),
+        (Some("heal"), None, None) => Action::Cheat(Heal),
+        (Some("tp"), Some(x), Some(y)) => match (x.parse(), y.parse()) {
+            (Ok(x), Ok(y)) => Action::Cheat(Teleport(Location(x, y))),
+            _ => {
+                println!("Invalid location: {:?}", command);
+                Action::Nothing
+            }
+        },
         _ => {
             println!("Unknown command: {:?}", command);
             Action::Nothing
         }
     }
 }
+
+/// Field of view algorithm by its console name
+fn fov_algorithm(name: &str) -> Option<FovAlgorithm> {
+    use FovAlgorithm::*;
+    let algorithm = match name {
+        "basic" => Basic,
+        "diamond" => Diamond,
+        "shadow" => Shadow,
+        "permissive0" => Permissive0,
+        "permissive1" => Permissive1,
+        "permissive2" => Permissive2,
+        "permissive3" => Permissive3,
+        "permissive4" => Permissive4,
+        "permissive5" => Permissive5,
+        "permissive6" => Permissive6,
+        "permissive7" => Permissive7,
+        "permissive8" => Permissive8,
+        _ => return None,
+    };
+    Some(algorithm)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dungeon::Layout;
+    use crate::game::Difficulty;
+
+    #[test]
+    fn death_ends_in_the_game_over_screen() {
+        let mut game = Game::new(
+            "Tester",
+            Layout::new(Dimension(40, 30), Dimension(6, 8), 10, 0, 0),
+            1,
+            Difficulty::Normal,
+        );
+        let mut screen = Screen::Attacking;
+        game.objects[PLAYER].alive = false;
+
+        let transition = screen.update(Action::Exit, &mut game);
+        assert!(matches!(transition, Transition::Replace(Screen::GameOver)));
+
+        let mut screen = Screen::GameOver;
+        let transition = screen.update(Action::Nothing, &mut game);
+        assert!(matches!(transition, Transition::Continue));
+        assert!(!game.restart);
+
+        let transition = screen.update(Action::Restart, &mut game);
+        assert!(matches!(transition, Transition::Quit));
+        assert!(game.restart);
+    }
+
+    #[test]
+    fn escape_asks_before_leaving_the_game() {
+        let mut game = Game::new(
+            "Tester",
+            Layout::new(Dimension(40, 30), Dimension(6, 8), 10, 0, 0),
+            1,
+            Difficulty::Normal,
+        );
+        let mut screen = Screen::GameWorld;
+        let mut escape = Key::default();
+        escape.code = KeyCode::Escape;
+
+        let action = screen.interpret(&Event::KeyEvent(escape));
+        let transition = screen.update(action, &mut game);
+        assert!(matches!(transition, Transition::Next(Screen::ConfirmExit)));
+    }
+
+    #[test]
+    fn debug_commands_need_wizard_mode() {
+        let mut game = Game::new(
+            "Tester",
+            Layout::new(Dimension(40, 30), Dimension(6, 8), 10, 0, 0),
+            1,
+            Difficulty::Normal,
+        );
+        let mut screen = Screen::GameWorld;
+        let mut peaceful = |game: &mut Game| {
+            let action = screen.interpret(&Event::Command(String::from("peaceful")));
+            screen.update(action, game);
+            game.peaceful
+        };
+
+        assert!(!peaceful(&mut game));
+        game.enable_wizard_mode();
+        assert!(peaceful(&mut game));
+    }
+
+    #[test]
+    fn stepping_onto_a_known_trap_needs_a_yes() {
+        let mut game = Game::new(
+            "Tester",
+            Layout::new(Dimension(40, 30), Dimension(6, 8), 10, 0, 0),
+            1,
+            Difficulty::Normal,
+        );
+        game.clear_objects();
+        game.relocate(PLAYER, Location(10, 10));
+        game.set_map(game::Map::new(Dimension(40, 30), game::Tile::empty()));
+        game.peaceful = true;
+        for x in [11, 12] {
+            let mut trap = game::Object::trap(Location(x, 10), game::TrapKind::Spikes);
+            if let Some(t) = trap.trap.as_mut() {
+                t.hidden = false;
+            }
+            game.spawn(trap);
+        }
+        let press = |printable: char| {
+            let mut key = Key::default();
+            key.code = KeyCode::Char;
+            key.printable = printable;
+            Event::KeyEvent(key)
+        };
+        let step = || Action::GameAction(game::Action::Move(PLAYER, Direction(1, 0)));
+
+        let mut screen = Screen::GameWorld;
+        let mut confirm = match screen.update(step(), &mut game) {
+            Transition::Next(confirm @ Screen::ConfirmStep { .. }) => confirm,
+            transition => panic!("Expected to be asked first: {:?}", transition),
+        };
+        let action = confirm.interpret(&press('n'));
+        assert!(matches!(
+            confirm.update(action, &mut game),
+            Transition::Exit
+        ));
+        assert_eq!(game.objects[PLAYER].loc, Location(10, 10));
+
+        let action = confirm.interpret(&press('y'));
+        assert!(matches!(
+            confirm.update(action, &mut game),
+            Transition::Exit
+        ));
+        assert_eq!(game.objects[PLAYER].loc, Location(11, 10));
+
+        game.confirm_traps = false;
+        let transition = screen.update(step(), &mut game);
+        assert!(matches!(transition, Transition::Continue));
+        assert_eq!(game.objects[PLAYER].loc, Location(12, 10));
+    }
+
+    #[test]
+    fn the_mouse_wheel_scrolls_the_message_log() {
+        let mut game = Game::new(
+            "Tester",
+            Layout::new(Dimension(40, 30), Dimension(6, 8), 10, 0, 0),
+            1,
+            Difficulty::Normal,
+        );
+        for i in 0..10 {
+            game.messages.add(format!("Message {}", i), colors::WHITE);
+        }
+        let mut screen = Screen::MessageLog { offset: 0 };
+        let wheel = |up: bool| {
+            Event::Mouse(crate::input::Mouse {
+                wheel_up: up,
+                wheel_down: !up,
+                ..Default::default()
+            })
+        };
+
+        let action = screen.interpret(&wheel(true));
+        assert!(matches!(action, Action::Scroll(LOG_WHEEL_STEP)));
+        screen.update(action, &mut game);
+        assert!(
+            matches!(screen, Screen::MessageLog { offset } if offset == LOG_WHEEL_STEP as usize)
+        );
+
+        let action = screen.interpret(&wheel(false));
+        screen.update(action, &mut game);
+        assert!(matches!(screen, Screen::MessageLog { offset: 0 }));
+    }
+}
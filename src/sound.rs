@@ -0,0 +1,40 @@
+//! Sounds made in the game world
+//!
+//! The game has no audio of its own. Resolvers report what could be heard
+//! to a `SoundSink` registered on the `Game`, which may play it back.
+use crate::Location;
+
+/// Kinds of sound the game makes
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SoundKind {
+    /// Something moved onto the tile, look up the tile for the surface
+    Footstep,
+    /// An attack dealt damage
+    Hit,
+    /// A fighter died
+    Death,
+    /// An item was picked up
+    Pickup,
+    /// The player arrived on a different level
+    LevelChange,
+}
+
+/// A sound at a location on the map
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SoundEvent {
+    pub kind: SoundKind,
+    pub loc: Location,
+}
+
+/// Receiver of the sounds emitted by the game
+pub trait SoundSink {
+    fn emit(&mut self, sound: SoundEvent);
+}
+
+/// Sink that ignores every sound
+#[derive(Debug, Default)]
+pub struct Silence;
+
+impl SoundSink for Silence {
+    fn emit(&mut self, _sound: SoundEvent) {}
+}
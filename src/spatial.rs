@@ -0,0 +1,46 @@
+//! Lookup of objects by their location
+use std::collections::HashMap;
+
+use crate::objects::{Id, Objects};
+use crate::Location;
+
+/// Ids of objects indexed by their location on the map
+#[derive(Debug, Default)]
+pub struct SpatialIndex {
+    cells: HashMap<Location, Vec<Id>>,
+}
+
+impl SpatialIndex {
+    /// Index all objects by their current location
+    pub fn new(objects: &Objects) -> Self {
+        let mut index = Self::default();
+        for (id, object) in objects.iter() {
+            index.insert(id, object.loc);
+        }
+        index
+    }
+
+    /// Ids of all objects at a location
+    pub fn at(&self, loc: &Location) -> &[Id] {
+        self.cells.get(loc).map_or(&[], |ids| ids.as_slice())
+    }
+
+    pub fn insert(&mut self, id: Id, loc: Location) {
+        self.cells.entry(loc).or_default().push(id);
+    }
+
+    pub fn remove(&mut self, id: Id, loc: &Location) {
+        if let Some(ids) = self.cells.get_mut(loc) {
+            ids.retain(|&i| i != id);
+            if ids.is_empty() {
+                self.cells.remove(loc);
+            }
+        }
+    }
+
+    /// Move an object from one location to another
+    pub fn relocate(&mut self, id: Id, from: &Location, to: Location) {
+        self.remove(id, from);
+        self.insert(id, to);
+    }
+}
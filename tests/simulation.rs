@@ -0,0 +1,98 @@
+//! Playing the game without a window
+use rustlike::dungeon::Layout;
+use rustlike::game::{Action, Difficulty, Game, Map, Object, Tile};
+use rustlike::objects::Id;
+use rustlike::{Dimension, Direction, Location};
+
+/// The player is always the first object
+const PLAYER: Id = Id::FIRST;
+
+/// A game on an open map with nobody but the player
+fn arena(seed: u64) -> Game {
+    let mut game = Game::new_headless(seed, Dimension(40, 30));
+    game.clear_objects();
+    game.relocate(PLAYER, Location(20, 15));
+    game.set_map(Map::new(Dimension(40, 30), Tile::empty()));
+    game.peaceful = true;
+    game
+}
+
+#[test]
+fn the_same_seed_builds_the_same_dungeon() {
+    let first = Game::new_headless(7, Dimension(80, 43));
+    let second = Game::new_headless(7, Dimension(80, 43));
+    assert_eq!(first.fingerprint(), second.fingerprint());
+    assert_eq!(first.objects[PLAYER].loc, second.objects[PLAYER].loc);
+}
+
+#[test]
+fn the_player_walks_across_the_map() {
+    let mut game = arena(1);
+    game.update(Action::Move(PLAYER, Direction(1, 0)));
+    game.update(Action::Move(PLAYER, Direction(0, 1)));
+    assert_eq!(game.objects[PLAYER].loc, Location(21, 16));
+    assert_eq!(game.turn, 2);
+}
+
+#[test]
+fn attacks_hurt_the_opponent() {
+    let mut game = arena(2);
+    let mut orc = Object::orc(Location(21, 15));
+    if let Some(fighter) = orc.fighter.as_mut() {
+        // Enough to survive the hit, so there is something to check
+        fighter.max_health = 100;
+        fighter.health = 100;
+    }
+    let orc = game.spawn(orc);
+
+    game.update(Action::Attack(PLAYER, orc));
+
+    assert!(game.stats.damage_dealt > 0);
+    assert!(game.objects[orc].fighter.unwrap().health < 100);
+}
+
+#[test]
+fn pre_generated_levels_ignore_what_happens_in_play() {
+    let layout = Layout::new(Dimension(80, 43), Dimension(6, 10), 30, 3, 2);
+    let mut patient = Game::new_full(11, "Tester", layout, 1, Difficulty::Normal, 3);
+    let mut hasty = Game::new_full(11, "Tester", layout, 1, Difficulty::Normal, 3);
+    let walls = |map: &Map| map.tiles().map(|t| t.blocked).collect::<Vec<_>>();
+    let planned = walls(&patient.levels[2].as_ref().unwrap().map);
+
+    patient.peaceful = true;
+    for _ in 0..20 {
+        patient.update(Action::Wait(PLAYER));
+    }
+    for game in [&mut patient, &mut hasty].iter_mut() {
+        game.descend();
+        game.descend();
+    }
+
+    assert_eq!(patient.depth, 3);
+    assert_eq!(walls(&patient.map), planned);
+    assert_eq!(walls(&hasty.map), planned);
+    assert_eq!(patient.objects[PLAYER].loc, hasty.objects[PLAYER].loc);
+}
+
+#[test]
+fn pre_generated_levels_follow_the_settings() {
+    let layout = Layout::new(Dimension(80, 43), Dimension(6, 10), 30, 3, 2);
+    let game = Game::new_full(5, "Hero", layout, 2, Difficulty::Hard, 4);
+    let mut hard = Object::orc(Location(0, 0));
+    Difficulty::Hard.adjust(&mut hard);
+    let hard = hard.fighter.unwrap().max_health;
+
+    assert_eq!(game.depth, 2);
+    assert_eq!(game.objects[PLAYER].name, "Hero");
+    assert_eq!(game.levels.len(), 4);
+    assert!(game.levels[1].is_none());
+    let orcs: Vec<_> = game
+        .levels
+        .iter()
+        .flatten()
+        .flat_map(|level| level.objects.iter())
+        .filter(|o| o.name == "orc")
+        .collect();
+    assert!(!orcs.is_empty());
+    assert!(orcs.iter().all(|o| o.fighter.unwrap().max_health == hard));
+}